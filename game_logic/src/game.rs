@@ -1,29 +1,63 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
 mod core;
+mod event;
 mod factory;
 mod game;
 mod geometry;
+mod grid;
 mod map;
 mod player;
 mod probe;
+mod protocol;
 mod random;
 mod turret;
 
 pub use self::core::*;
+pub use self::event::*;
 pub use self::factory::*;
 pub use self::game::*;
 pub use self::geometry::*;
+pub use self::grid::*;
 pub use self::map::*;
 pub use self::player::*;
 pub use self::probe::*;
 pub use self::turret::*;
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct GameConfig {
     /// dimension of the map (unit: coord),
     pub dim: Coord,
 
+    /// coordinates of the obstacle tiles of the map, that can't be
+    /// claimed nor built on (see `Tile::is_obstacle`)
+    pub obstacles: Vec<Coord>,
+
+    /// coordinates of the bonus tiles of the map and their bonus,
+    /// the owner of a bonus tile (with a non-zero occupation)
+    /// earns the bonus on each income tick (see `Tile::bonus`)
+    pub bonus_tiles: Vec<(Coord, f64)>,
+
+    /// distance from the owned tiles within which a player can see
+    /// the map (unit: coord), see `Game::get_state_for_player`,
+    /// if 0: the whole map is visible
+    pub vision_radius: f64,
+
     /// number of players in the game
     pub n_player: u32,
 
+    /// if true, the start positions of 2 or 4 players are symmetric
+    /// (with respect to the center for 2 players, to both axes for 4),
+    /// else (or for other numbers of players) they are on a circle
+    pub symmetric_spawns: bool,
+
+    /// if true, the obstacles and bonus tiles must be symmetric
+    /// (with respect to the center, or to both axes for 4 players),
+    /// as checked by `validate`, ex: for ranked maps
+    pub symmetric_layout: bool,
+
     /// money players start with
     pub initial_money: f64,
 
@@ -34,21 +68,42 @@ pub struct GameConfig {
     /// base income that each player receive unconditionally
     pub base_income: f64,
 
+    /// if true, all players receive their income on the same
+    /// frame (global schedule), else each player has its own schedule
+    pub align_income_ticks: bool,
+
     /// minimal occupation value on tile required to build a building (factory/turret)
     pub building_occupation_min: u32,
 
+    /// amount added to the price of a building (factory/turret)
+    /// built close to an opponent tile (see `building_surcharge_distance`)
+    pub building_surcharge: f64,
+
+    /// maximal distance (unit: coord) to an opponent tile for a building
+    /// to be surcharged, if 0: no surcharge
+    pub building_surcharge_distance: u32,
+
     /// amount to pay to build a new factory
     pub factory_price: f64,
 
     /// the radius of the final expansion size
     pub factory_expansion_size: u32,
 
+    /// amount to pay to re-run the expansion of a factory
+    /// (see `Player::reexpand_factory`)
+    pub factory_expansion_cost: f64,
+
     /// Costs of possessing one factory (computed in the player's income)
     pub factory_maintenance_costs: f64,
 
     /// maximal number of probe generated by a factory
     pub factory_max_probe: u32,
 
+    /// maximal number of probes in the game (all players included),
+    /// when reached, the production of all factories is paused
+    /// until some probes die, if 0: no limit
+    pub max_probes_total: u32,
+
     /// delay to wait to build a probe from the factory (sec)
     pub factory_build_probe_delay: f64,
 
@@ -61,15 +116,82 @@ pub struct GameConfig {
     // probe hitpoints
     pub probe_hp: u32,
 
+    /// hitpoints regenerated per second by a probe on a tile owned
+    /// by its player (except when attacking), up to its maximal hp,
+    /// if 0: no regeneration
+    pub probe_hp_regen_per_sec: f64,
+
     /// intensity of claiming when farming
     pub probe_claim_intensity: u32,
 
     /// intensity of claiming when exploding
     pub probe_explosion_intensity: u32,
 
+    /// if true, exploding probes also claim the tiles of the player
+    /// in the explosion (with the explosion intensity),
+    /// else only opponent tiles are affected
+    pub probe_explosion_reinforce: bool,
+
+    /// if true, a probe without any tile to farm waits (idle) and
+    /// periodically (every claim delay) looks for a new target,
+    /// else it claims the tile it is on
+    pub probe_idle_without_target: bool,
+
+    /// if true, farming probes prefer the tiles of the player that
+    /// are below the maximum occupation (e.g. deprecated) over new tiles,
+    /// else any valid tile is targeted
+    pub probe_farm_prefer_own: bool,
+
+    /// which tiles are adjacent to a tile (see `Adjacency`), a tile not
+    /// owned by the player can only be farmed if adjacent to its territory
+    pub adjacency: Adjacency,
+
+    /// maximal occupation of a tile of an opponent
+    /// for it to be targeted by the farming probes
+    pub farm_capture_max_enemy_occupation: u32,
+
+    /// if true, farming probes prefer the targets they can reach
+    /// without crossing the scope of an opponent turret \
+    /// Note: each candidate target is checked against all the
+    /// opponent turrets, which adds a cost per target selection
+    pub probe_avoid_turrets: bool,
+
+    /// factor applied to the intensity of the opponents claiming
+    /// a tile guarded by a picket probe (see `ProbePolicy::Picket`),
+    /// if 0: the tile can't be claimed by opponents
+    pub probe_picket_intensity_factor: f64,
+
+    /// distance (unit: coord) by which the stationary probes of a
+    /// factory sharing a tile are spread apart in the reported position
+    /// (the claimed tile is unchanged), if 0: no separation
+    pub probe_separation: f64,
+
+    /// maximal distance (unit: coord) from which farming probes
+    /// are redirected to reinforce a building of the player whose tile
+    /// is contested by an opponent, if 0: no reinforcement
+    pub probe_reinforce_distance: f64,
+
+    /// if true, attacking probes target the closest opponent building
+    /// instead of the closest opponent tile
+    pub probe_attack_prefer_buildings: bool,
+
+    /// if true, attacking probes are spread over the opponent tiles
+    /// of the attacked region (round-robin) instead of a random one
+    pub probe_attack_spread: bool,
+
     /// amount to pay to produce one probe
     pub probe_price: f64,
 
+    /// time after which a probe expires (sec),
+    /// if 0: probes never expire
+    pub probe_lifespan: f64,
+
+    /// maximal cumulative distance a probe can travel since produced
+    /// by its factory (unit: coord), whatever its policy, beyond it,
+    /// the probe stops and claims the tile, or dies if the tile is owned
+    /// by an opponent, if 0: no maximal distance
+    pub probe_max_range: f64,
+
     /// delay to wait claim a tile, the probe can be manually moved but not claim
     /// another tile during the delay (see Probe `is_claiming` flag for details)
     pub probe_claim_delay: f64,
@@ -80,6 +202,10 @@ pub struct GameConfig {
     /// amount to pay to build a new turret
     pub turret_price: f64,
 
+    /// part of the turret price refunded when scrapping a turret,
+    /// as `refund = turret_price * rate`
+    pub turret_scrap_refund_rate: f64,
+
     /// amount of damage inflicted to probe's hp
     pub turret_damage: u32,
 
@@ -89,6 +215,18 @@ pub struct GameConfig {
     /// scope of the turret (unit: coord)
     pub turret_scope: f64,
 
+    /// which probe in scope the turret fires at in priority
+    /// (see `TurretTargetPriority`)
+    pub turret_target_priority: TurretTargetPriority,
+
+    /// radius around the probe fired at, in which the other
+    /// opponent probes suffer the splash damage (unit: coord),
+    /// if 0: no splash damage
+    pub turret_splash_radius: f64,
+
+    /// amount of damage inflicted to the probes hit by the splash
+    pub turret_splash_damage: u32,
+
     /// Costs of possessing one turret (computed in the player's income)
     pub turret_maintenance_costs: f64,
 
@@ -96,9 +234,62 @@ pub struct GameConfig {
     /// as `income = occupation * rate`
     pub income_rate: f64,
 
+    /// minimal occupation of a tile for it to generate income
+    /// (doesn't affect the occupation recorded in the stats)
+    pub income_occupation_min: u32,
+
+    /// costs of holding territory, subtracted from the income
+    /// as `upkeep = total_occupation * rate`, if 0: no upkeep
+    pub occupation_upkeep_rate: f64,
+
+    /// money given to the first player to destroy an opponent building,
+    /// if 0: no bonus
+    pub first_blood_bonus: f64,
+
+    /// money given for each building destroyed by a player multiplied by
+    /// the number of consecutive previous kills (without losing a building),
+    /// as `bounty = (streak - 1) * kill_streak_bounty`, if 0: no bounty
+    pub kill_streak_bounty: f64,
+
+    /// maximal number of events emitted in one frame (i.e. simulation step,
+    /// see `tick_rate`), events with the lowest priority are dropped
+    /// (see `GameEvent::get_priority`) and replaced by a single
+    /// truncation event, if 0: no limit
+    pub max_events_per_frame: usize,
+
+    /// duration of a simulation step (sec), when specified, the game
+    /// is run by fixed steps, independently of the `dt` given to `run`
+    /// (the remaining time is carried over to the next call),
+    /// if 0: one step of `dt` per call to `run`
+    pub tick_rate: f64,
+
+    /// if true, the factories and probes of all the players are advanced
+    /// in parallel (probe production and movement, see `Player::advance`),
+    /// then the rest of the frame (claims, turret fire, income) is resolved
+    /// player by player, the result doesn't depend on the number of threads
+    pub parallel: bool,
+
+    /// duration of the game (sec), once reached the game ends and
+    /// the player with the highest score wins (see `Game::get_scores`),
+    /// if 0: no time limit
+    pub time_limit: f64,
+
+    /// weights of the players' score (see `Game::get_scores`)
+    pub score_weights: ScoreWeights,
+
+    /// minimal occupation of the tile of a building for it to be kept,
+    /// below it the building is at-risk and dies at the next check (every sec)
+    /// if it hasn't been reinforced, if 0: no upkeep
+    pub building_upkeep_occupation: u32,
+
     /// probability that a tile with maximum occupation lose 2 occupation
     pub deprecate_rate: f64,
 
+    /// probability that a tile with maximum occupation increases by 1
+    /// the occupation of each of its neighbours (unowned or owned by
+    /// the same player), if 0: no spread
+    pub occupation_spread_rate: f64,
+
     /// how much the probe explosion intensity of claiming
     /// is increased
     pub tech_probe_explosion_intensity_increase: u32,
@@ -118,6 +309,12 @@ pub struct GameConfig {
     /// price of probe hp tech
     pub tech_probe_hp_price: f64,
 
+    /// how much the probe speed is increased (coordinate/sec)
+    pub tech_probe_speed_increase: f64,
+
+    /// price of probe speed tech
+    pub tech_probe_speed_price: f64,
+
     /// how much the build probe delay is decreased
     pub tech_factory_build_delay_decrease: f64,
 
@@ -153,4 +350,460 @@ pub struct GameConfig {
 
     /// price of turret maintenance costs tech
     pub tech_turret_maintenance_costs_price: f64,
+
+    /// how much the turret damage is increased
+    pub tech_turret_damage_increase: u32,
+
+    /// price of turret damage tech
+    pub tech_turret_damage_price: f64,
+
+    /// part of the tech price refunded when refunding a tech,
+    /// as `refund = tech_price * rate`
+    pub tech_refund_rate: f64,
+
+    /// factions the players can be part of (see `player_factions`),
+    /// if empty: all players can acquire all techs at their base price
+    pub factions: Vec<FactionConfig>,
+
+    /// index (in `factions`) of the faction of each player,
+    /// in the order of the players ids, players without
+    /// a (valid) faction can acquire all techs at their base price
+    pub player_factions: Vec<usize>,
+
+    /// overrides of the config for some players (by id),
+    /// the other players use the base config (see `PartialConfig`)
+    pub per_player_overrides: HashMap<u128, PartialConfig>,
+
+    /// groups of mutually exclusive techs: a player can only acquire
+    /// one tech of each group (see `Techs::default_exclusion_groups`)
+    pub tech_exclusion_groups: Vec<HashSet<Techs>>,
+}
+
+impl GameConfig {
+    /// Check that the config values are consistent \
+    /// Return an error describing the first invalid value
+    pub fn validate(&self) -> Result<(), String> {
+        if self.dim.x <= 0 || self.dim.y <= 0 {
+            return Err(format!(
+                "Invalid dim: must be positive (got {:?})",
+                self.dim
+            ));
+        }
+        if self.initial_n_probes > self.factory_max_probe {
+            return Err(format!(
+                "Invalid initial_n_probes: must be smaller than factory_max_probe ({} > {})",
+                self.initial_n_probes, self.factory_max_probe
+            ));
+        }
+        if self.max_occupation == 0 {
+            return Err(String::from("Invalid max_occupation: must be positive"));
+        }
+        if self.building_occupation_min > self.max_occupation {
+            return Err(format!(
+                "Invalid building_occupation_min: must be smaller than max_occupation ({} > {})",
+                self.building_occupation_min, self.max_occupation
+            ));
+        }
+        if self.building_upkeep_occupation > self.max_occupation {
+            return Err(format!(
+                "Invalid building_upkeep_occupation: must be smaller than max_occupation ({} > {})",
+                self.building_upkeep_occupation, self.max_occupation
+            ));
+        }
+        if !self.probe_speed.is_finite() || self.probe_speed <= 0.0 {
+            return Err(format!(
+                "Invalid probe_speed: must be positive and finite (got {})",
+                self.probe_speed
+            ));
+        }
+        let delays = [
+            ("factory_build_probe_delay", self.factory_build_probe_delay),
+            ("probe_lifespan", self.probe_lifespan),
+            ("probe_hp_regen_per_sec", self.probe_hp_regen_per_sec),
+            ("probe_max_range", self.probe_max_range),
+            ("vision_radius", self.vision_radius),
+            ("probe_claim_delay", self.probe_claim_delay),
+            ("turret_fire_delay", self.turret_fire_delay),
+            ("tick_rate", self.tick_rate),
+            ("time_limit", self.time_limit),
+        ];
+        for (name, delay) in delays {
+            if !delay.is_finite() || delay < 0.0 {
+                return Err(format!(
+                    "Invalid {}: must be non-negative and finite (got {})",
+                    name, delay
+                ));
+            }
+        }
+        let prices = [
+            ("factory_price", self.factory_price),
+            ("factory_expansion_cost", self.factory_expansion_cost),
+            ("probe_price", self.probe_price),
+            ("turret_price", self.turret_price),
+        ];
+        for (name, price) in prices {
+            if !price.is_finite() || price < 0.0 {
+                return Err(format!(
+                    "Invalid {}: must be non-negative and finite (got {})",
+                    name, price
+                ));
+            }
+        }
+        if !(0.0..=1.0).contains(&self.turret_scrap_refund_rate) {
+            return Err(format!(
+                "Invalid turret_scrap_refund_rate: must be between 0 and 1 (got {})",
+                self.turret_scrap_refund_rate
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.tech_refund_rate) {
+            return Err(format!(
+                "Invalid tech_refund_rate: must be between 0 and 1 (got {})",
+                self.tech_refund_rate
+            ));
+        }
+        for coord in self.obstacles.iter() {
+            if coord.x < 0 || coord.y < 0 || coord.x >= self.dim.x || coord.y >= self.dim.y {
+                return Err(format!("Invalid obstacles: out of the map ({:?})", coord));
+            }
+        }
+        for (coord, _) in self.bonus_tiles.iter() {
+            if coord.x < 0 || coord.y < 0 || coord.x >= self.dim.x || coord.y >= self.dim.y {
+                return Err(format!("Invalid bonus_tiles: out of the map ({:?})", coord));
+            }
+        }
+        if self.symmetric_layout {
+            self.validate_layout_symmetry()?;
+        }
+        for faction in self.player_factions.iter() {
+            if *faction >= self.factions.len() {
+                return Err(format!(
+                    "Invalid player_factions: unknown faction {}",
+                    faction
+                ));
+            }
+        }
+        for overrides in self.per_player_overrides.values() {
+            if let Some(probe_speed) = overrides.probe_speed {
+                if !probe_speed.is_finite() || probe_speed <= 0.0 {
+                    return Err(format!(
+                        "Invalid per_player_overrides: probe_speed must be positive and finite (got {})",
+                        probe_speed
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the reflections of the coordinate that a symmetric
+    /// layout must contain (see `symmetric_layout`)
+    fn get_layout_reflections(&self, coord: &Coord) -> Vec<Coord> {
+        let mx = self.dim.x - 1 - coord.x;
+        let my = self.dim.y - 1 - coord.y;
+        match self.n_player {
+            4 => vec![
+                Coord::new(mx, coord.y),
+                Coord::new(coord.x, my),
+                Coord::new(mx, my),
+            ],
+            _ => vec![Coord::new(mx, my)],
+        }
+    }
+
+    /// Return an error naming the first obstacle or bonus tile
+    /// without symmetric counterpart (see `symmetric_layout`)
+    fn validate_layout_symmetry(&self) -> Result<(), String> {
+        for coord in self.obstacles.iter() {
+            for reflection in self.get_layout_reflections(coord) {
+                if !self.obstacles.contains(&reflection) {
+                    return Err(format!(
+                        "Invalid obstacles: asymmetric layout, no counterpart of {:?} at {:?}",
+                        coord, reflection
+                    ));
+                }
+            }
+        }
+        for (coord, bonus) in self.bonus_tiles.iter() {
+            for reflection in self.get_layout_reflections(coord) {
+                if !self
+                    .bonus_tiles
+                    .iter()
+                    .any(|(c, b)| *c == reflection && b == bonus)
+                {
+                    return Err(format!(
+                        "Invalid bonus_tiles: asymmetric layout, no counterpart of {:?} at {:?}",
+                        coord, reflection
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the maximal base probe speed among the players,
+    /// taking the overrides into account
+    pub fn get_max_probe_speed(&self) -> f64 {
+        self.per_player_overrides
+            .values()
+            .filter_map(|overrides| overrides.probe_speed)
+            .fold(self.probe_speed, f64::max)
+    }
+}
+
+/// Config values overriding the ones of `GameConfig` for a player,
+/// if None: use the value of `GameConfig`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PartialConfig {
+    /// initial money of the player
+    pub initial_money: Option<f64>,
+
+    /// income per occupation of the player
+    pub income_rate: Option<f64>,
+
+    /// speed of the player's probes (unit: coord/sec)
+    pub probe_speed: Option<f64>,
+}
+
+/// Weights of the values combined in the score of a player,
+/// as `score = sum(weight * value)`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ScoreWeights {
+    /// weight of the total occupation of the player's tiles
+    pub occupation: f64,
+
+    /// weight of the player's money
+    pub money: f64,
+
+    /// weight of the number of buildings (factories & turrets)
+    pub buildings: f64,
+}
+
+/// Faction of players, restricting the techs they can acquire
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FactionConfig {
+    /// techs the players of the faction can acquire
+    pub techs: Vec<Techs>,
+
+    /// factor applied to the price of the techs,
+    /// as `price = tech_price * factor`
+    pub tech_price_factor: f64,
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// Return the config of a small 2 players game (20x20 map),
+    /// without any of the optional mechanics, to be adapted by each test
+    pub fn test_config() -> GameConfig {
+        GameConfig {
+            dim: Coord { x: 20, y: 20 },
+            obstacles: Vec::new(),
+            bonus_tiles: Vec::new(),
+            vision_radius: 0.0,
+            n_player: 2,
+            symmetric_spawns: false,
+            symmetric_layout: false,
+            initial_money: 100.0,
+            initial_n_probes: 0,
+            base_income: 0.0,
+            align_income_ticks: false,
+            building_occupation_min: 0,
+            building_surcharge: 0.0,
+            building_surcharge_distance: 0,
+            factory_price: 10.0,
+            factory_expansion_size: 2,
+            factory_expansion_cost: 0.0,
+            factory_maintenance_costs: 0.0,
+            factory_max_probe: 3,
+            max_probes_total: 0,
+            factory_build_probe_delay: 1.0,
+            max_occupation: 10,
+            probe_speed: 1.0,
+            probe_hp: 2,
+            probe_hp_regen_per_sec: 0.0,
+            probe_claim_intensity: 1,
+            probe_explosion_intensity: 3,
+            probe_explosion_reinforce: false,
+            probe_idle_without_target: false,
+            probe_farm_prefer_own: false,
+            adjacency: Adjacency::VonNeumann,
+            farm_capture_max_enemy_occupation: 3,
+            probe_avoid_turrets: false,
+            probe_picket_intensity_factor: 1.0,
+            probe_separation: 0.0,
+            probe_reinforce_distance: 0.0,
+            probe_attack_prefer_buildings: false,
+            probe_attack_spread: false,
+            probe_price: 1.0,
+            probe_lifespan: 0.0,
+            probe_max_range: 0.0,
+            probe_claim_delay: 0.5,
+            probe_maintenance_costs: 0.0,
+            turret_price: 5.0,
+            turret_scrap_refund_rate: 0.0,
+            turret_damage: 1,
+            turret_fire_delay: 1.0,
+            turret_scope: 3.0,
+            turret_target_priority: TurretTargetPriority::First,
+            turret_splash_radius: 0.0,
+            turret_splash_damage: 0,
+            turret_maintenance_costs: 0.0,
+            income_rate: 0.0,
+            income_occupation_min: 0,
+            occupation_upkeep_rate: 0.0,
+            first_blood_bonus: 0.0,
+            kill_streak_bounty: 0.0,
+            max_events_per_frame: 0,
+            tick_rate: 0.0,
+            parallel: false,
+            time_limit: 0.0,
+            score_weights: ScoreWeights {
+                occupation: 1.0,
+                money: 0.0,
+                buildings: 0.0,
+            },
+            building_upkeep_occupation: 0,
+            deprecate_rate: 0.0,
+            occupation_spread_rate: 0.0,
+            tech_probe_explosion_intensity_increase: 2,
+            tech_probe_explosion_intensity_price: 10.0,
+            tech_probe_claim_intensity_increase: 1,
+            tech_probe_claim_intensity_price: 10.0,
+            tech_probe_hp_increase: 2,
+            tech_probe_hp_price: 10.0,
+            tech_probe_speed_increase: 1.0,
+            tech_probe_speed_price: 10.0,
+            tech_factory_build_delay_decrease: 0.5,
+            tech_factory_build_delay_price: 10.0,
+            tech_factory_probe_price_decrease: 0.5,
+            tech_factory_probe_price_price: 10.0,
+            tech_factory_max_probe_increase: 2,
+            tech_factory_max_probe_price: 10.0,
+            tech_turret_scope_increase: 1.0,
+            tech_turret_scope_price: 10.0,
+            tech_turret_fire_delay_decrease: 0.5,
+            tech_turret_fire_delay_price: 10.0,
+            tech_turret_maintenance_costs_decrease: 0.5,
+            tech_turret_maintenance_costs_price: 10.0,
+            tech_turret_damage_increase: 1,
+            tech_turret_damage_price: 10.0,
+            tech_refund_rate: 0.5,
+            factions: Vec::new(),
+            player_factions: Vec::new(),
+            per_player_overrides: HashMap::new(),
+            tech_exclusion_groups: Techs::default_exclusion_groups(),
+        }
+    }
+
+    /// Assert that the config is invalid, because of the field `name`
+    fn assert_invalid(config: &GameConfig, name: &str) {
+        match config.validate() {
+            Err(msg) => assert!(msg.starts_with(&format!("Invalid {}", name)), "{}", msg),
+            Ok(_) => panic!("{} should be invalid", name),
+        }
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        assert!(test_config().validate().is_ok());
+        let cases: Vec<(&str, fn(&mut GameConfig))> = vec![
+            ("dim", |c| c.dim.x = 0),
+            ("initial_n_probes", |c| c.initial_n_probes = 4),
+            ("max_occupation", |c| c.max_occupation = 0),
+            ("building_occupation_min", |c| {
+                c.building_occupation_min = 11
+            }),
+            ("building_upkeep_occupation", |c| {
+                c.building_upkeep_occupation = 11
+            }),
+            ("probe_speed", |c| c.probe_speed = 0.0),
+            ("factory_build_probe_delay", |c| {
+                c.factory_build_probe_delay = -1.0
+            }),
+            ("turret_fire_delay", |c| c.turret_fire_delay = -1.0),
+            ("time_limit", |c| c.time_limit = -1.0),
+            ("probe_price", |c| c.probe_price = -1.0),
+            ("turret_price", |c| c.turret_price = -1.0),
+            ("turret_scrap_refund_rate", |c| {
+                c.turret_scrap_refund_rate = 1.5
+            }),
+            ("tech_refund_rate", |c| c.tech_refund_rate = -0.5),
+            ("obstacles", |c| c.obstacles = vec![Coord::new(20, 0)]),
+            ("bonus_tiles", |c| {
+                c.bonus_tiles = vec![(Coord::new(-1, 0), 2.0)]
+            }),
+            ("player_factions", |c| c.player_factions = vec![0]),
+        ];
+        for (name, invalidate) in cases {
+            let mut config = test_config();
+            invalidate(&mut config);
+            assert_invalid(&config, name);
+        }
+    }
+
+    #[test]
+    fn non_finite_values_are_rejected() {
+        let cases: Vec<(&str, fn(&mut GameConfig))> = vec![
+            ("probe_speed", |c| c.probe_speed = f64::NAN),
+            ("probe_speed", |c| c.probe_speed = f64::INFINITY),
+            ("probe_claim_delay", |c| c.probe_claim_delay = f64::NAN),
+            ("tick_rate", |c| c.tick_rate = f64::INFINITY),
+            ("factory_price", |c| c.factory_price = f64::NAN),
+            ("factory_expansion_cost", |c| {
+                c.factory_expansion_cost = f64::INFINITY
+            }),
+            ("turret_scrap_refund_rate", |c| {
+                c.turret_scrap_refund_rate = f64::NAN
+            }),
+            ("tech_refund_rate", |c| c.tech_refund_rate = f64::NAN),
+            ("per_player_overrides", |c| {
+                let overrides = PartialConfig {
+                    initial_money: None,
+                    income_rate: None,
+                    probe_speed: Some(f64::NAN),
+                };
+                c.per_player_overrides.insert(1, overrides);
+            }),
+        ];
+        for (name, invalidate) in cases {
+            let mut config = test_config();
+            invalidate(&mut config);
+            assert_invalid(&config, name);
+        }
+    }
+
+    #[test]
+    fn symmetric_layout_is_validated() {
+        let mut config = test_config();
+        config.symmetric_layout = true;
+        config.obstacles = vec![Coord::new(3, 4), Coord::new(16, 15)];
+        config.bonus_tiles = vec![(Coord::new(0, 0), 2.0), (Coord::new(19, 19), 2.0)];
+        assert!(config.validate().is_ok());
+
+        config.n_player = 4;
+        config
+            .obstacles
+            .extend([Coord::new(16, 4), Coord::new(3, 15)]);
+        config
+            .bonus_tiles
+            .extend([(Coord::new(19, 0), 2.0), (Coord::new(0, 19), 2.0)]);
+        assert!(config.validate().is_ok());
+
+        // skewed by one tile
+        config.obstacles[3] = Coord::new(3, 14);
+        let err = config.validate().unwrap_err();
+        assert!(err.starts_with("Invalid obstacles: asymmetric layout"));
+        assert!(err.contains(&format!("{:?}", Coord::new(3, 4))));
+
+        // different bonus
+        config.obstacles[3] = Coord::new(3, 15);
+        config.bonus_tiles[3].1 = 3.0;
+        let err = config.validate().unwrap_err();
+        assert!(err.starts_with("Invalid bonus_tiles: asymmetric layout"));
+
+        // not required by default
+        config.symmetric_layout = false;
+        assert!(config.validate().is_ok());
+    }
 }
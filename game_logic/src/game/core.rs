@@ -1,8 +1,10 @@
 use std::collections::HashMap;
 
+use serde::{Deserialize, Serialize};
+
 use super::*;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Point {
     pub x: f64,
     pub y: f64,
@@ -52,7 +54,7 @@ impl Clone for Point {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Coord {
     pub x: i32,
     pub y: i32,
@@ -80,8 +82,22 @@ impl Clone for Coord {
 
 pub struct FrameContext<'a> {
     pub dt: f64,
+    /// Global income schedule signal
+    /// (see `GameConfig.align_income_ticks`)
+    pub is_income_tick: bool,
     pub config: &'a GameConfig,
     pub map: &'a mut Map,
+    /// Total number of probes in the game (all players included),
+    /// probes created during the frame are counted
+    /// (see `GameConfig.max_probes_total`)
+    pub n_probes: usize,
+    /// Spatial index of the probes, as at the start of the frame
+    pub probe_grid: ProbeGrid,
+    /// Events that occured during the frame
+    pub events: Vec<GameEvent>,
+    /// If the factories and probes were already advanced during the frame,
+    /// in parallel (see `GameConfig.parallel`, `Player::advance`)
+    pub is_advanced: bool,
 }
 
 pub fn generate_unique_id() -> u128 {
@@ -99,6 +115,7 @@ pub fn generate_unique_id() -> u128 {
 
 /// Delayer
 /// Designed to be called each frame (see `wait()`)
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Delayer {
     delay: f64,
     counter: f64,
@@ -128,6 +145,17 @@ impl Delayer {
         self.counter = 0.0;
     }
 
+    /// Return the remaining time to wait until the delay
+    /// is reached (unit: sec)
+    pub fn get_remaining(&self) -> f64 {
+        (self.delay - self.counter).max(0.0)
+    }
+
+    /// Return the delay to wait (unit: sec)
+    pub fn get_delay(&self) -> f64 {
+        self.delay
+    }
+
     /// Set the delay to wait (unit: sec)
     pub fn set_delay(&mut self, delay: f64) {
         self.delay = delay;
@@ -194,6 +222,7 @@ where
 
 /// State wrapper \
 /// Used to gradually build state
+#[derive(Clone, Serialize, Deserialize)]
 pub struct StateHandler<T: State> {
     state: T,
     /// Indicates if a state was built in
@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Coord, Techs};
+
+/// Notable events that occured during a frame,
+/// intended to be displayed by the client
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum GameEvent {
+    /// The income of the player just became negative
+    IncomeNegative { player_id: u128, income: f64 },
+    /// The player destroyed the first building of the game
+    FirstBlood { player_id: u128, bonus: f64 },
+    /// The player destroyed a building while on a streak of kills
+    KillStreak {
+        player_id: u128,
+        streak: u32,
+        bounty: f64,
+    },
+    /// The probe exploded at the coordinate
+    ProbeExploded {
+        player_id: u128,
+        probe_id: u128,
+        coord: Coord,
+    },
+    /// The building of the player was conquered
+    BuildingConquered { player_id: u128, building_id: u128 },
+    /// The player acquired the tech
+    TechAcquired { player_id: u128, tech: Techs },
+    /// The player was defeated (lost all its factories)
+    PlayerDefeated { player_id: u128 },
+    /// Some events of the frame were dropped
+    /// (see `GameConfig.max_events_per_frame`)
+    EventsTruncated { n_dropped: usize },
+}
+
+impl GameEvent {
+    /// Return the priority of the event, when the number of events
+    /// of a frame is limited, the events of higher priority are kept: \
+    /// 1: kills related events \
+    /// 0: routine events (e.g. income)
+    pub fn get_priority(&self) -> u32 {
+        match self {
+            GameEvent::FirstBlood { .. } | GameEvent::KillStreak { .. } => 1,
+            GameEvent::BuildingConquered { .. } | GameEvent::PlayerDefeated { .. } => 1,
+            GameEvent::IncomeNegative { .. } => 0,
+            GameEvent::ProbeExploded { .. } | GameEvent::TechAcquired { .. } => 0,
+            GameEvent::EventsTruncated { .. } => 0,
+        }
+    }
+}
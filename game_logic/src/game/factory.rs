@@ -1,38 +1,68 @@
-use std::slice::IterMut;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::slice::{Iter, IterMut};
 
 use log;
+use serde::{Deserialize, Serialize};
 
-use super::core::{state_vec_insert, Coord, FrameContext, State};
+use super::core::{state_vec_insert, Coord, FrameContext, Point, State};
 use super::player::Player;
 use super::probe::{Probe, ProbeDeathCause, ProbeState};
-use super::{core, geometry, Delayer, GameConfig, Identifiable, StateHandler, Techs};
+use super::{
+    core, geometry, Delayer, GameConfig, GameEvent, Identifiable, Map, StateHandler, Techs,
+};
 
+#[derive(Clone, Serialize, Deserialize)]
 pub enum FactoryPolicy {
     Expand,
     Produce,
     Wait,
+    /// The maximal number of probes in the game is reached
+    /// (see `GameConfig.max_probes_total`)
+    Throttled,
+    /// The production is paused by the player
+    /// (see `Factory::set_paused`)
+    Paused,
 }
 
-#[derive(Clone, Debug)]
+impl FactoryPolicy {
+    /// Create an instance from a string (only the policies that
+    /// can be set by the player: "Produce" and "Pause") \
+    /// Return an error in case the `string` is invalid
+    pub fn from_string(string: &str) -> Result<Self, String> {
+        match string {
+            "Produce" => Ok(FactoryPolicy::Produce),
+            "Pause" => Ok(FactoryPolicy::Paused),
+            _ => Err(format!("Invalid factory policy: {}", string)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum FactoryDeathCause {
     Conquered,
     Scrapped,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 struct FactoryConfig {
     max_probe: u32,
     expansion_size: u32,
     maintenance_costs: f64,
     probe_maintenance_costs: f64,
     tech_max_probe_increase: u32,
+    max_probes_total: u32,
+    probe_separation: f64,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FactoryState {
     pub id: u128,
     /// Only specified once, when the factory dies
     pub death: Option<FactoryDeathCause>,
     pub coord: Option<Coord>,
+    /// If the factory's tile is below the upkeep occupation
+    pub at_risk: Option<bool>,
     pub probes: Vec<ProbeState>,
 }
 
@@ -50,6 +80,7 @@ impl State for FactoryState {
             id: *_metadata,
             death: None,
             coord: None,
+            at_risk: None,
             probes: Vec::new(),
         }
     }
@@ -61,12 +92,16 @@ impl State for FactoryState {
         if let Some(coord) = state.coord {
             self.coord = Some(coord);
         }
+        if let Some(at_risk) = state.at_risk {
+            self.at_risk = Some(at_risk);
+        }
         for probe in state.probes {
             state_vec_insert(&mut self.probes, probe);
         }
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Factory {
     pub id: u128,
     config: FactoryConfig,
@@ -78,8 +113,15 @@ pub struct Factory {
     expand_step: u32,
     /// Delay to wait to produce probe
     delayer_produce: Delayer,
+    /// Indicates that the last produced probe couldn't
+    /// be created, it will be produced again on next frame
+    is_production_pending: bool,
     /// Delay to wait between expand step
     delayer_expand: Delayer,
+    /// If the production is paused by the player
+    is_paused: bool,
+    /// Target of the newly produced probes (if any)
+    rally: Option<Coord>,
 }
 
 impl Factory {
@@ -93,6 +135,8 @@ impl Factory {
                 maintenance_costs: config.factory_maintenance_costs,
                 probe_maintenance_costs: config.probe_maintenance_costs,
                 tech_max_probe_increase: config.tech_factory_max_probe_increase,
+                max_probes_total: config.max_probes_total,
+                probe_separation: config.probe_separation,
             },
             state_handle: StateHandler::new(&id),
             policy: FactoryPolicy::Expand,
@@ -100,21 +144,95 @@ impl Factory {
             probes: Vec::new(),
             expand_step: 0,
             delayer_produce: Delayer::new(config.factory_build_probe_delay),
+            is_production_pending: false,
             delayer_expand: Delayer::new(0.5),
+            is_paused: false,
+            rally: None,
+        }
+    }
+
+    /// Pause or resume the production of probes \
+    /// Note: the expansion phase is not interrupted,
+    /// the factory is paused once it is completed
+    pub fn set_paused(&mut self, is_paused: bool) {
+        self.is_paused = is_paused;
+        match self.policy {
+            FactoryPolicy::Expand => {}
+            _ if is_paused => {
+                self.policy = FactoryPolicy::Paused;
+            }
+            FactoryPolicy::Paused => {
+                self.policy = FactoryPolicy::Produce;
+            }
+            _ => {}
         }
     }
 
+    /// Return if the production of probes is paused
+    /// (see `Factory::set_paused`)
+    pub fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
+    /// Re-run the expansion phase, to claim back the tiles next to
+    /// the factory, then resume the previous production \
+    /// Return an error in case the factory is already expanding
+    pub fn reexpand(&mut self) -> Result<(), String> {
+        if matches!(self.policy, FactoryPolicy::Expand) {
+            return Err(String::from("Factory already expanding"));
+        }
+        self.expand_step = 0;
+        self.delayer_expand.reset();
+        self.policy = FactoryPolicy::Expand;
+        Ok(())
+    }
+
+    /// Set the target of the newly produced probes
+    pub fn set_rally(&mut self, rally: Coord) {
+        self.rally = Some(rally);
+    }
+
+    /// Return the target of the newly produced probes, if any
+    pub fn get_rally(&self) -> Option<&Coord> {
+        self.rally.as_ref()
+    }
+
     /// factory policy getter
     pub fn get_policy(&self) -> &FactoryPolicy {
         &self.policy
     }
 
+    /// Create a Factory instance from its (complete) state,
+    /// the factory is directly in production \
+    /// Return an error in case the state has no coordinate
+    pub fn from_state(
+        config: &GameConfig,
+        player: &Player,
+        state: &FactoryState,
+    ) -> Result<Factory, String> {
+        let pos = match &state.coord {
+            Some(coord) => coord.clone(),
+            None => {
+                return Err(String::from("Missing factory coordinate"));
+            }
+        };
+        let mut factory = Factory::new(config, pos);
+        factory.id = state.id;
+        factory.state_handle = StateHandler::new(&state.id);
+        factory.policy = FactoryPolicy::Produce;
+        for probe_state in state.probes.iter() {
+            factory.attach_probe(Probe::from_state(config, player, probe_state)?);
+        }
+        Ok(factory)
+    }
+
     /// Return complete current factory state
     pub fn get_complete_state(&self) -> FactoryState {
         let mut state = FactoryState {
             id: self.id,
             death: None,
             coord: Some(self.pos.clone()),
+            at_risk: None,
             probes: Vec::with_capacity(self.probes.len()),
         };
         for probe in self.probes.iter() {
@@ -133,6 +251,18 @@ impl Factory {
         self.delayer_produce.set_delay(delay);
     }
 
+    /// Return the build probe delay
+    pub fn get_build_probe_delay(&self) -> f64 {
+        self.delayer_produce.get_delay()
+    }
+
+    /// Notify that the last produced probe couldn't be created
+    /// (ex: not enough money), the factory will try to produce it
+    /// again on next frame, without waiting for the produce delay
+    pub fn retry_production(&mut self) {
+        self.is_production_pending = true;
+    }
+
     /// Return the number of probes currently attached to the factory
     pub fn get_num_probes(&self) -> usize {
         self.probes.len()
@@ -143,6 +273,16 @@ impl Factory {
         self.probes.iter_mut()
     }
 
+    /// Iterator over each probe of factory
+    pub fn iter_probes(&self) -> Iter<'_, Probe> {
+        self.probes.iter()
+    }
+
+    /// Return the probe at the given index, if it exists
+    pub fn get_mut_probe_at(&mut self, idx: usize) -> Option<&mut Probe> {
+        self.probes.get_mut(idx)
+    }
+
     /// Return the probe with the given id, if it exists
     pub fn get_mut_probe_by_id(&mut self, probe_id: u128) -> Option<&mut Probe> {
         self.probes.iter_mut().find(|p| p.id == probe_id)
@@ -153,10 +293,14 @@ impl Factory {
         ProbeState::create_created_state(self.pos.as_point())
     }
 
-    /// Return factory income (costs)
+    /// Return factory income (costs), excluding its probes
     pub fn get_income(&self) -> f64 {
+        -self.config.maintenance_costs
+    }
+
+    /// Return the income (costs) of the probes of the factory
+    pub fn get_probes_income(&self) -> f64 {
         -(self.probes.len() as f64) * self.config.probe_maintenance_costs
-            - self.config.maintenance_costs
     }
 
     /// Return the maximum number of probe the factory can have,
@@ -193,7 +337,10 @@ impl Factory {
         self.expand_step += 1;
         if self.expand_step == self.config.expansion_size + 1 {
             self.expand_step = 0;
-            self.policy = FactoryPolicy::Produce;
+            self.policy = match self.is_paused {
+                true => FactoryPolicy::Paused,
+                false => FactoryPolicy::Produce,
+            };
             return;
         }
         let coords = geometry::square(&self.pos, self.expand_step);
@@ -208,25 +355,102 @@ impl Factory {
     /// when resolving states (thus there is no guarantee that the probe
     /// will effectively be created) \
     /// Switch to Wait policy when `max_probe` reached
-    fn produce(&mut self, player: &Player, ctx: &mut FrameContext) {
+    fn produce(&mut self, player: &Player, dt: f64, n_probes: usize) {
         if self.probes.len() == self.get_max_probe(player) as usize {
             self.policy = FactoryPolicy::Wait;
             return;
         }
-        if self.delayer_produce.wait(ctx.dt) {
+        if self.is_throttled(n_probes) {
+            self.policy = FactoryPolicy::Throttled;
+            return;
+        }
+        if self.is_production_pending || self.delayer_produce.wait(dt) {
+            self.is_production_pending = false;
             let state = self.create_probe_state();
             self.state_handle.get_mut().probes.push(state);
         }
     }
 
     /// Switch to Produce policy when having less than `max_probe`
-    fn wait(&mut self, player: &Player, ctx: &mut FrameContext) {
+    fn wait(&mut self, player: &Player) {
         if self.probes.len() < self.get_max_probe(player) as usize {
             self.policy = FactoryPolicy::Produce;
         }
     }
 
+    /// Return if the maximal number of probes in the game is reached,
+    /// given the number of probes in the game
+    pub fn is_throttled(&self, n_probes: usize) -> bool {
+        self.config.max_probes_total > 0 && n_probes >= self.config.max_probes_total as usize
+    }
+
+    /// Switch to Produce policy when the game is below
+    /// its maximal number of probes
+    fn throttle(&mut self, n_probes: usize) {
+        if !self.is_throttled(n_probes) {
+            self.policy = FactoryPolicy::Produce;
+        }
+    }
+
+    /// Spread apart the stationary probes sharing a tile,
+    /// evenly on a circle of radius `probe_separation`
+    /// (see `Probe::set_separation`)
+    fn separate_probes(&mut self) {
+        let mut groups: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+        for (i, probe) in self.probes.iter_mut().enumerate() {
+            if probe.is_moving() {
+                probe.clear_separation();
+                continue;
+            }
+            let coord = probe.get_coord();
+            groups
+                .entry((coord.x, coord.y))
+                .or_insert(Vec::new())
+                .push(i);
+        }
+        for idxs in groups.values() {
+            let n = idxs.len();
+            for (k, idx) in idxs.iter().enumerate() {
+                let mut separation = Point::new(0.0, 0.0);
+                if n > 1 {
+                    let angle = 2.0 * PI * k as f64 / n as f64;
+                    separation = Point::new(angle.cos(), angle.sin());
+                    separation.mul(self.config.probe_separation);
+                }
+                self.probes[*idx].set_separation(separation);
+            }
+        }
+    }
+
     /// run function
+    /// Advance the production of probes of one frame
+    /// (for the expansion, see `run` as it modifies the map)
+    fn advance_production(&mut self, player: &Player, dt: f64, n_probes: usize) {
+        match self.policy {
+            FactoryPolicy::Produce => {
+                self.produce(player, dt, n_probes);
+            }
+            FactoryPolicy::Wait => {
+                self.wait(player);
+            }
+            FactoryPolicy::Throttled => {
+                self.throttle(n_probes);
+            }
+            FactoryPolicy::Expand | FactoryPolicy::Paused => {}
+        }
+    }
+
+    /// Advance the factory of one frame, without modifying the map:
+    /// produce probes and advance its probes (see `Probe::advance`) \
+    /// The rest of the frame is resolved on the next call to `run`
+    /// (see `GameConfig.parallel`)
+    pub fn advance(&mut self, player: &Player, map: &Map, dt: f64, n_probes: usize) {
+        self.advance_production(player, dt, n_probes);
+        for probe in self.probes.iter_mut() {
+            probe.advance(player, map, dt);
+        }
+    }
+
     pub fn run(&mut self, player: &Player, ctx: &mut FrameContext) -> Option<FactoryState> {
         log::debug!(
             "[({:.3}) Factory {:.3}] run...",
@@ -237,12 +461,14 @@ impl Factory {
             FactoryPolicy::Expand => {
                 self.expand(player.id, ctx);
             }
-            FactoryPolicy::Produce => {
-                self.produce(player, ctx);
-            }
-            FactoryPolicy::Wait => {
-                self.wait(player, ctx);
+            _ if !ctx.is_advanced => {
+                self.advance_production(player, ctx.dt, ctx.n_probes);
             }
+            _ => {}
+        }
+
+        if self.config.probe_separation > 0.0 {
+            self.separate_probes();
         }
 
         let mut dead_probe_idxs = Vec::new();
@@ -252,6 +478,13 @@ impl Factory {
                 if state.death.is_some() {
                     dead_probe_idxs.push(i);
                 }
+                if let Some(ProbeDeathCause::Exploded) = state.death {
+                    ctx.events.push(GameEvent::ProbeExploded {
+                        player_id: player.id,
+                        probe_id: probe.id,
+                        coord: probe.get_coord(),
+                    });
+                }
 
                 state_vec_insert(&mut self.state_handle.get_mut().probes, state);
             }
@@ -1,20 +1,27 @@
 use super::{
     core::FrameContext,
     geometry,
-    map::{Map, MapState},
+    map::{Map, MapState, Tile},
     player::{Player, PlayerState},
-    probe::Probe,
+    probe::{Probe, ProbePolicy, ProbeState},
+    protocol::{decode_actions, Action},
     state_vec_insert,
-    turret::TurretDeathCause,
-    Coord, FactoryDeathCause, FactoryState, GameConfig, Identifiable, PlayerDeathCause,
-    PlayerStats, ProbeState, State, StateHandler, Techs,
+    turret::{TurretDeathCause, TurretState},
+    Coord, Delayer, FactionConfig, FactoryDeathCause, FactoryPolicy, FactoryState, GameConfig,
+    GameEvent, Identifiable, PlayerDeathCause, PlayerEffectiveStats, PlayerStats, ProbeGrid, State,
+    StateHandler, Techs,
 };
-use std::{cmp, collections::HashMap};
+use std::{cmp, collections::HashMap, f64::consts};
 
-#[derive(Clone, Debug)]
+use log;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GameState {
     pub map: Option<MapState>,
     pub players: Vec<PlayerState>,
+    pub events: Vec<GameEvent>,
     pub game_ended: bool,
 }
 
@@ -25,6 +32,7 @@ impl State for GameState {
         GameState {
             map: None,
             players: Vec::new(),
+            events: Vec::new(),
             game_ended: false,
         }
     }
@@ -42,9 +50,54 @@ impl State for GameState {
         for player in state.players {
             state_vec_insert(&mut self.players, player);
         }
+        self.events.extend(state.events);
     }
 }
 
+/// Version of the layout of the states (see `ResumePayload`),
+/// to increment on incompatible changes
+pub const STATE_SCHEMA_VERSION: u32 = 1;
+
+/// Id of the anonymous factory holding the visible probes of the
+/// opponents' factories that aren't visible (see `Game::get_state_for_player`)
+pub const HIDDEN_FACTORY_ID: u128 = 0;
+
+/// Snapshot of everything a client needs to resume the game
+/// (ex: on reconnection), see `Game::get_resume_payload`
+#[derive(Clone)]
+pub struct ResumePayload {
+    pub schema_version: u32,
+    /// id of the player resuming the game
+    pub player_id: u128,
+    /// game state as seen by the player, i.e. restricted to its vision
+    /// (see `Game::get_state_for_player`)
+    pub state: GameState,
+    /// effective stats of the player's units
+    pub effective_stats: PlayerEffectiveStats,
+    /// number of frames run
+    pub frame: u64,
+    /// time elapsed since the start of the game (sec)
+    pub time: f64,
+    /// config of the game
+    pub config: GameConfig,
+}
+
+/// Final result of a game, available once the game ended
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameResult {
+    /// id of the winner, `None` in case of a draw
+    /// (all remaining players died on the same frame)
+    pub winner_id: Option<u128>,
+    /// ids of all players, from the first to the last one:
+    /// the winner first, then the players in reverse order of death
+    pub rankings: Vec<u128>,
+    /// number of frames run
+    pub n_frames: u64,
+    /// duration of the game (sec)
+    pub duration: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Game {
     config: GameConfig,
     state_handle: StateHandler<GameState>,
@@ -52,19 +105,122 @@ pub struct Game {
     players: Vec<Player>,
     /// Store player stats gradually, as they die
     player_stats: HashMap<u128, PlayerStats>,
+    /// Number of frames run since the start of the game
+    frame: u64,
+    /// Time elapsed since the start of the game (sec)
+    time: f64,
+    /// Time not yet simulated, when running by fixed steps
+    /// (see `GameConfig.tick_rate`)
+    tick_accumulator: f64,
+    /// Number of events emitted by the previous steps of the current run,
+    /// which are already truncated (see `truncate_events`)
+    n_step_events: usize,
+    /// Delay to wait between two incomes (global schedule)
+    delayer_income: Delayer,
+    /// If a building has already been destroyed by a player
+    /// (see `GameConfig.first_blood_bonus`)
+    is_first_blood_done: bool,
+    /// Number of consecutive buildings destroyed by each player
+    /// without losing a building
+    kill_streaks: HashMap<u128, u32>,
+    /// If the maximal number of probes in the game is reached
+    /// (see `GameConfig.max_probes_total`)
+    is_probe_production_throttled: bool,
+    /// Ids of the dead players, in order of death
+    dead_player_ids: Vec<u128>,
+    /// Result of the game, set once the game ended
+    result: Option<GameResult>,
 }
 
 impl Game {
     pub fn new(player_ids: Vec<u128>, config: GameConfig) -> Self {
-        let mut game = Game {
+        let mut game = Game::new_empty(config);
+        game.create_players(player_ids);
+        game
+    }
+
+    /// Create a game from a (complete) state, as returned by
+    /// `get_complete_state`, only the given players are restored \
+    /// Transient values (deaths, events, stats, delays) are not restored \
+    /// Return an error in case the state is inconsistent
+    pub fn from_state(
+        player_ids: Vec<u128>,
+        config: GameConfig,
+        state: GameState,
+    ) -> Result<Self, String> {
+        let mut game = Game::new_empty(config);
+        if let Some(map_state) = &state.map {
+            game.map.restore_tiles(&map_state.tiles)?;
+        }
+        for (i, id) in player_ids.iter().enumerate() {
+            let player_state = match state.players.iter().find(|p| p.id == *id) {
+                Some(player_state) => player_state,
+                None => {
+                    return Err(format!("Missing state of player {}", id));
+                }
+            };
+            let mut player = Player::from_state(&game.config, player_state, &mut game.map)?;
+            if let Some(faction) = game.get_player_faction(i) {
+                player.set_faction(faction);
+            }
+            game.players.push(player);
+        }
+        Ok(game)
+    }
+
+    /// Return a binary snapshot of the whole game, to be restored with `restore` \
+    /// Unlike the complete state (see `get_complete_state`), the snapshot
+    /// includes the runtime state (delays, stats, random generator,...),
+    /// so that the restored game runs exactly as the original one \
+    /// Return an error in case the game can't be serialized
+    pub fn snapshot(&self) -> Result<Vec<u8>, String> {
+        match bincode::serialize(self) {
+            Ok(bytes) => Ok(bytes),
+            Err(err) => Err(format!("Failed to serialize the game: {}", err)),
+        }
+    }
+
+    /// Create a game from a binary snapshot, as returned by `snapshot`,
+    /// including its config \
+    /// Return an error in case the snapshot is invalid
+    pub fn restore(bytes: &[u8]) -> Result<Self, String> {
+        match bincode::deserialize(bytes) {
+            Ok(game) => Ok(game),
+            Err(err) => Err(format!("Invalid snapshot: {}", err)),
+        }
+    }
+
+    /// Create a game without any player
+    fn new_empty(config: GameConfig) -> Self {
+        Game {
             map: Map::new(&config),
             state_handle: StateHandler::new(&()),
             config: config,
             players: Vec::new(),
             player_stats: HashMap::new(),
-        };
-        game.create_players(player_ids);
-        game
+            frame: 0,
+            time: 0.0,
+            tick_accumulator: 0.0,
+            n_step_events: 0,
+            delayer_income: Delayer::new(1.0),
+            is_first_blood_done: false,
+            kill_streaks: HashMap::new(),
+            is_probe_production_throttled: false,
+            dead_player_ids: Vec::new(),
+            result: None,
+        }
+    }
+
+    /// Return the config of the game
+    pub fn get_config(&self) -> &GameConfig {
+        &self.config
+    }
+
+    /// Return an independent copy of the game, that can be run
+    /// without affecting the original one (ids are preserved, so
+    /// that states of both games can be compared)
+    pub fn fork(&self) -> Game {
+        self.clone()
     }
 
     /// Return complete current game state
@@ -72,6 +228,7 @@ impl Game {
         let mut state = GameState {
             players: Vec::with_capacity(self.players.len()),
             map: Some(self.map.get_complete_state()),
+            events: Vec::new(),
             game_ended: false,
         };
         for player in self.players.iter() {
@@ -80,35 +237,212 @@ impl Game {
         state
     }
 
+    /// Return the complete state of the game as seen by the player
+    /// (see `Map::compute_visibility`): only the visible tiles,
+    /// and the opponents' buildings and probes on visible tiles
+    /// (without their groups), if the vision radius is 0: the complete state \
+    /// Note: the visible probes of the opponents' factories that aren't
+    /// visible are grouped in an anonymous factory, without coordinate
+    /// (see `HIDDEN_FACTORY_ID`) \
+    /// Return an error in case the player is dead
+    pub fn get_state_for_player(&self, player_id: u128) -> Result<GameState, String> {
+        if self.get_player(player_id).is_none() {
+            return Err(String::from("Invalid player (Are you dead ?)"));
+        }
+        if self.config.vision_radius == 0.0 {
+            return Ok(self.get_complete_state());
+        }
+        let visibility = self.map.compute_visibility(player_id);
+        let is_visible = |coord: &Option<Coord>| match coord {
+            Some(coord) => visibility
+                .get(coord.x as usize)
+                .and_then(|col| col.get(coord.y as usize))
+                .map_or(false, |visible| *visible),
+            None => false,
+        };
+
+        let mut state = self.get_complete_state();
+        if let Some(map) = &mut state.map {
+            map.tiles.retain(|tile| is_visible(&tile.coord));
+        }
+        for player in state.players.iter_mut() {
+            if player.id == player_id {
+                continue;
+            }
+            player.groups = None;
+            let mut hidden_probes: Vec<ProbeState> = Vec::new();
+            for factory in player.factories.iter_mut() {
+                factory
+                    .probes
+                    .retain(|probe| is_visible(&probe.pos.as_ref().map(|pos| pos.as_coord())));
+                if !is_visible(&factory.coord) {
+                    hidden_probes.append(&mut factory.probes);
+                }
+            }
+            player
+                .factories
+                .retain(|factory| is_visible(&factory.coord));
+            if !hidden_probes.is_empty() {
+                let mut factory = FactoryState::new(&HIDDEN_FACTORY_ID);
+                factory.probes = hidden_probes;
+                player.factories.push(factory);
+            }
+            player.turrets.retain(|turret| is_visible(&turret.coord));
+        }
+        Ok(state)
+    }
+
+    /// Return the coordinates of the tiles occupied by the building
+    /// (factory or turret), buildings occupy a single tile \
+    /// Return an error in case the building doesn't exist
+    pub fn get_building_footprint(&self, building_id: u128) -> Result<Vec<Coord>, String> {
+        match self.map.get_building_coord(building_id) {
+            Some(coord) => Ok(vec![coord]),
+            None => Err(String::from("Invalid building")),
+        }
+    }
+
+    /// Return a snapshot of the game for the player to resume it \
+    /// Return an error in case the player is dead
+    pub fn get_resume_payload(&self, player_id: u128) -> Result<ResumePayload, String> {
+        let player = match self.get_player(player_id) {
+            Some(player) => player,
+            None => {
+                return Err(String::from("Invalid player (Are you dead ?)"));
+            }
+        };
+        let mut state = self.get_state_for_player(player_id)?;
+        state.game_ended = self.result.is_some();
+        Ok(ResumePayload {
+            schema_version: STATE_SCHEMA_VERSION,
+            player_id: player_id,
+            state: state,
+            effective_stats: player.get_effective_stats(&self.config),
+            frame: self.frame,
+            time: self.time,
+            config: self.config.clone(),
+        })
+    }
+
+    /// Return the occupation of all tiles, indexed as `grid[x][y]`
+    /// (see `Map::get_occupation_grid`)
+    pub fn get_occupation_grid(&self) -> Vec<Vec<u32>> {
+        self.map.get_occupation_grid()
+    }
+
+    /// Return ref of Player with given id, if found
+    fn get_player(&self, id: u128) -> Option<&Player> {
+        self.players.iter().find(|p| p.id == id)
+    }
+
     /// Return mut ref of Player with given id, if found
     fn get_player_mut(&mut self, id: u128) -> Option<&mut Player> {
         self.players.iter_mut().find(|p| p.id == id)
     }
 
-    /// Return suitable start positions for n players
+    /// Return suitable start positions for n players \
+    /// If `symmetric_spawns`, try to get symmetric positions
+    /// (see `get_symmetric_start_positions`), else fall back to an ellipse
+    /// fitting the map dimensions
     fn get_start_positions(&self, n_players: u32) -> Vec<Coord> {
-        let radius = cmp::min(self.config.dim.x, self.config.dim.y) as f64 / 2.0;
-        let margin = radius / 5.0;
-        let mut positions = Vec::with_capacity(n_players as usize);
+        if self.config.symmetric_spawns {
+            if let Some(positions) = self.get_symmetric_start_positions(n_players) {
+                return positions;
+            }
+        }
+        let radius_x = self.config.dim.x as f64 / 2.0;
+        let radius_y = self.config.dim.y as f64 / 2.0;
+        let mut positions: Vec<Coord> = Vec::with_capacity(n_players as usize);
         for i in 0..n_players {
-            let angle = i as f64 / n_players as f64 * 2.0 * 3.141592;
-            let x = (radius - margin) * angle.cos() + radius;
-            let y = (radius - margin) * angle.sin() + radius;
-            positions.push(Coord::new(x as i32, y as i32));
+            let angle = i as f64 / n_players as f64 * 2.0 * consts::PI;
+            let x = 0.8 * radius_x * angle.cos() + radius_x;
+            let y = 0.8 * radius_y * angle.sin() + radius_y;
+            let pos = Coord::new(x as i32, y as i32);
+            let pos = self.get_free_start_position(&pos, &positions);
+            positions.push(pos);
         }
         return positions;
     }
 
+    /// Return the closest coordinate to `pos` that is on the map,
+    /// isn't an obstacle and isn't already taken by another player \
+    /// Return `pos` in case there is none
+    fn get_free_start_position(&self, pos: &Coord, taken: &Vec<Coord>) -> Coord {
+        for coord in geometry::IterVortex::with_bounds(pos, &self.config.dim) {
+            match self.map.get_tile(&coord) {
+                Some(tile) if !tile.is_obstacle && !taken.contains(&coord) => {
+                    return coord;
+                }
+                _ => {}
+            }
+        }
+        log::warn!("No free start position ({:?})", pos);
+        pos.clone()
+    }
+
+    /// Return start positions for 2 or 4 players, obtained by reflecting
+    /// the first one: across the map center for 2 players,
+    /// across both axes for 4 players \
+    /// Return None for other numbers of players, or in case a
+    /// position is out of the map or on an obstacle
+    fn get_symmetric_start_positions(&self, n_players: u32) -> Option<Vec<Coord>> {
+        let radius = cmp::min(self.config.dim.x, self.config.dim.y) as f64 / 2.0;
+        let margin = radius / 5.0;
+        // place the first player on the diagonal for 4 players,
+        // so that its reflections are all distinct
+        let angle: f64 = match n_players {
+            2 => consts::PI,
+            4 => 1.25 * consts::PI,
+            _ => {
+                return None;
+            }
+        };
+        let x = ((radius - margin) * angle.cos() + radius) as i32;
+        let y = ((radius - margin) * angle.sin() + radius) as i32;
+        let mx = self.config.dim.x - 1 - x;
+        let my = self.config.dim.y - 1 - y;
+
+        let positions = match n_players {
+            2 => vec![Coord::new(x, y), Coord::new(mx, my)],
+            _ => vec![
+                Coord::new(x, y),
+                Coord::new(mx, y),
+                Coord::new(mx, my),
+                Coord::new(x, my),
+            ],
+        };
+        for pos in positions.iter() {
+            match self.map.get_tile(pos) {
+                Some(tile) if !tile.is_obstacle => {}
+                _ => {
+                    log::warn!("Invalid symmetric start position ({:?})", pos);
+                    return None;
+                }
+            }
+        }
+        Some(positions)
+    }
+
     /// Create players of the game (update self.players)
     /// Create initial conditions (factory/probes)
     fn create_players(&mut self, player_ids: Vec<u128>) {
         let start_positions = self.get_start_positions(self.config.n_player);
-        for (id, pos) in player_ids.iter().zip(start_positions) {
-            let player = self.create_player(*id, pos);
+        for (i, (id, pos)) in player_ids.iter().zip(start_positions).enumerate() {
+            let mut player = self.create_player(*id, pos);
+            if let Some(faction) = self.get_player_faction(i) {
+                player.set_faction(faction);
+            }
             self.players.push(player);
         }
     }
 
+    /// Return the faction of the i-th player, if any
+    /// (see `GameConfig.player_factions`)
+    fn get_player_faction(&self, idx: usize) -> Option<FactionConfig> {
+        let faction = self.config.player_factions.get(idx)?;
+        self.config.factions.get(*faction).cloned()
+    }
+
     /// Create player \
     /// Create initial conditions (factory/probes)
     fn create_player(&mut self, id: u128, pos: Coord) -> Player {
@@ -130,6 +464,8 @@ impl Game {
             let mut probe = Probe::new(&self.config, &player, pos.as_point());
             if let Some(target) = self.map.get_probe_farm_target(&player, &probe) {
                 probe.set_target_manually(target.as_point());
+            } else {
+                probe.idle();
             }
             let factory = player.factories.last_mut().unwrap();
             factory.attach_probe(probe);
@@ -144,13 +480,39 @@ impl Game {
         player_id: u128,
         death_cause: PlayerDeathCause,
     ) -> Option<PlayerState> {
-        let idx = self.players.iter().position(|p| p.id == player_id);
-        if let Some(idx) = idx {
-            let player = self.players.remove(idx);
-            self.player_stats.insert(player.id, player.get_stats(1.0));
-            return Some(player.die(death_cause));
-        }
-        None
+        let player = self.remove_player(player_id)?;
+        Some(player.die(death_cause))
+    }
+
+    /// Remove a player from the game (if `player_id` is valid),
+    /// along with its buildings, and keep track of its stats \
+    /// Return the removed player
+    fn remove_player(&mut self, player_id: u128) -> Option<Player> {
+        let idx = self.players.iter().position(|p| p.id == player_id)?;
+        let player = self.players.remove(idx);
+        self.map.remove_player_buildings(player.id);
+        self.player_stats.insert(player.id, player.get_stats(1.0));
+        self.dead_player_ids.push(player.id);
+        Some(player)
+    }
+
+    /// Return the result of the game, `None` while the game is running
+    pub fn get_result(&self) -> Option<&GameResult> {
+        self.result.as_ref()
+    }
+
+    /// Return the number of living players \
+    /// Note: dead players are removed at the end of the frame
+    /// they die in (or directly when killed by `kill_player`),
+    /// so a player is only counted if still alive between two frames
+    pub fn get_living_player_count(&self) -> usize {
+        self.players.len()
+    }
+
+    /// Return the ids of the living players
+    /// (see `get_living_player_count` for semantics)
+    pub fn get_living_player_ids(&self) -> Vec<u128> {
+        self.players.iter().map(|p| p.id).collect()
     }
 
     /// Return the players stats (dead players included)
@@ -164,6 +526,111 @@ impl Game {
         stats
     }
 
+    /// Return the effective stats of the player (see `Player::get_effective_stats`)
+    pub fn get_effective_stats(&self, player_id: u128) -> Result<PlayerEffectiveStats, String> {
+        match self.get_player(player_id) {
+            Some(player) => Ok(player.get_effective_stats(&self.config)),
+            None => Err(String::from("Invalid player (Are you dead ?)")),
+        }
+    }
+
+    /// Return the score of each living player, from the highest to
+    /// the lowest (see `GameConfig.score_weights`) \
+    /// Note: the players with the same score are kept in the order
+    /// of the players
+    pub fn get_scores(&self) -> Vec<(u128, f64)> {
+        let weights = &self.config.score_weights;
+        let mut scores: Vec<(u128, f64)> = self
+            .players
+            .iter()
+            .map(|player| {
+                let occupation = self.map.get_player_occupation(player) as f64;
+                let buildings = (player.factories.len() + player.turrets.len()) as f64;
+                let score = weights.occupation * occupation
+                    + weights.money * player.get_money()
+                    + weights.buildings * buildings;
+                (player.id, score)
+            })
+            .collect();
+        scores.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(cmp::Ordering::Equal));
+        scores
+    }
+
+    /// Return the complete state of the player only \
+    /// Return an error in case the player is dead
+    pub fn get_player_state(&self, player_id: u128) -> Result<PlayerState, String> {
+        match self.get_player(player_id) {
+            Some(player) => Ok(player.get_complete_state()),
+            None => Err(String::from("Invalid player (Are you dead ?)")),
+        }
+    }
+
+    /// Return the tiles the player can build on (see `Map::get_buildable_tiles`)
+    pub fn get_buildable_tiles(&self, player_id: u128) -> Result<Vec<Coord>, String> {
+        match self.get_player(player_id) {
+            Some(player) => Ok(self.map.get_buildable_tiles(player)),
+            None => Err(String::from("Invalid player (Are you dead ?)")),
+        }
+    }
+
+    /// Return the tiles the player can expand to (see `Map::get_expansion_frontier`)
+    pub fn get_expansion_frontier(&self, player_id: u128) -> Result<Vec<Coord>, String> {
+        match self.get_player(player_id) {
+            Some(player) => Ok(self.map.get_expansion_frontier(player)),
+            None => Err(String::from("Invalid player (Are you dead ?)")),
+        }
+    }
+
+    /// Return a human-readable summary of the game, intended for logs:
+    /// players' money/occupation/buildings/probes, current leader
+    /// (largest occupation), elapsed frames/time and end status
+    pub fn describe(&self) -> String {
+        let mut desc = format!(
+            "Game (frame: {}, time: {:.2}s, ended: {})\n",
+            self.frame,
+            self.time,
+            self.result.is_some()
+        );
+
+        let mut leader: Option<(u128, u32)> = None;
+        let mut lines = Vec::with_capacity(self.players.len());
+        for player in self.players.iter() {
+            let occupation = self.map.get_player_occupation(player);
+            if leader.map_or(true, |(_, occ)| occupation > occ) {
+                leader = Some((player.id, occupation));
+            }
+            lines.push(format!(
+                "  player {}: money: {:.2}, occupation: {}, factories: {}, turrets: {}, probes: {}\n",
+                player.id,
+                player.get_money(),
+                occupation,
+                player.factories.len(),
+                player.turrets.len(),
+                player.get_num_probes(),
+            ));
+        }
+
+        // dead players are not in self.players anymore
+        let mut dead_ids: Vec<&u128> = self
+            .player_stats
+            .keys()
+            .filter(|id| self.get_player(**id).is_none())
+            .collect();
+        dead_ids.sort();
+        for id in dead_ids {
+            lines.push(format!("  player {}: defeated\n", id));
+        }
+
+        match leader {
+            Some((id, _)) => desc.push_str(&format!("  leader: {}\n", id)),
+            None => desc.push_str("  leader: none\n"),
+        }
+        for line in lines {
+            desc.push_str(&line);
+        }
+        desc
+    }
+
     /// Kill all building marked has dead by map
     /// Update corresponding player states
     fn handle_map_dead_building(&mut self, map_state: &MapState) {
@@ -171,7 +638,12 @@ impl Game {
             // collect all death states
             if let Some(player) = self.get_player_mut(*player_id) {
                 let mut state = PlayerState::new(player_id);
+                let mut events = Vec::new();
                 for id in dead_ids.iter() {
+                    events.push(GameEvent::BuildingConquered {
+                        player_id: *player_id,
+                        building_id: *id,
+                    });
                     // try kill factory
                     if let Some(factory_state) =
                         player.kill_factory(*id, FactoryDeathCause::Conquered)
@@ -188,29 +660,275 @@ impl Game {
                     }
                 }
                 state_vec_insert(&mut self.state_handle.get_mut().players, state);
+                self.state_handle.get_mut().events.append(&mut events);
+            }
+        }
+    }
+
+    /// Flag all buildings whose at-risk status changed (see `Map::upkeep_buildings`)
+    /// Update corresponding player states
+    fn handle_map_building_risk(&mut self, map_state: &MapState) {
+        for (player_id, risks) in map_state.get_building_risk().iter() {
+            if let Some(player) = self.get_player_mut(*player_id) {
+                let mut state = PlayerState::new(player_id);
+                for (id, is_at_risk) in risks.iter() {
+                    if player.factories.iter().any(|f| f.id == *id) {
+                        let mut factory_state = FactoryState::new(id);
+                        factory_state.at_risk = Some(*is_at_risk);
+                        state_vec_insert(&mut state.factories, factory_state);
+                    } else if player.turrets.iter().any(|t| t.id == *id) {
+                        let mut turret_state = TurretState::new(id);
+                        turret_state.at_risk = Some(*is_at_risk);
+                        state_vec_insert(&mut state.turrets, turret_state);
+                    }
+                }
+                state_vec_insert(&mut self.state_handle.get_mut().players, state);
+            }
+        }
+    }
+
+    /// Update the kill streaks of the players,
+    /// give the first blood bonus and the streak bounties \
+    /// Update corresponding events
+    fn handle_map_building_kills(&mut self, map_state: &MapState) {
+        // any lost building breaks the streak
+        for player_id in map_state.get_dead_building().keys() {
+            self.kill_streaks.insert(*player_id, 0);
+        }
+
+        for (killer_id, _) in map_state.get_building_kills().iter() {
+            let streak = self.kill_streaks.entry(*killer_id).or_insert(0);
+            *streak += 1;
+            let streak = *streak;
+
+            let mut reward = 0.0;
+            let mut events = Vec::new();
+
+            if !self.is_first_blood_done {
+                self.is_first_blood_done = true;
+                if self.config.first_blood_bonus > 0.0 {
+                    reward += self.config.first_blood_bonus;
+                    events.push(GameEvent::FirstBlood {
+                        player_id: *killer_id,
+                        bonus: self.config.first_blood_bonus,
+                    });
+                }
+            }
+
+            if streak > 1 && self.config.kill_streak_bounty > 0.0 {
+                let bounty = (streak - 1) as f64 * self.config.kill_streak_bounty;
+                reward += bounty;
+                events.push(GameEvent::KillStreak {
+                    player_id: *killer_id,
+                    streak: streak,
+                    bounty: bounty,
+                });
+            }
+
+            if reward == 0.0 {
+                continue;
+            }
+            if let Some(player) = self.get_player_mut(*killer_id) {
+                // NOTE: the money state is flushed on next player's run
+                player.add_money(reward);
+                self.state_handle.get_mut().events.append(&mut events);
+            }
+        }
+    }
+
+    /// Return the total number of probes in the game \
+    /// Log when the maximal number of probes is reached/left
+    fn count_probes(&mut self) -> usize {
+        let n_probes: usize = self.players.iter().map(|p| p.get_num_probes()).sum();
+        if self.config.max_probes_total == 0 {
+            return n_probes;
+        }
+        let is_throttled = n_probes >= self.config.max_probes_total as usize;
+        if is_throttled && !self.is_probe_production_throttled {
+            log::warn!(
+                "Probe production throttled: maximal number of probes reached ({})",
+                n_probes
+            );
+        } else if !is_throttled && self.is_probe_production_throttled {
+            log::info!("Probe production resumed ({} probes)", n_probes);
+        }
+        self.is_probe_production_throttled = is_throttled;
+        n_probes
+    }
+
+    /// Update the tiles guarded by picket probes \
+    /// Note: a picket guards its tile until the frame following its death
+    fn update_pickets(&mut self) {
+        let mut pickets = Vec::new();
+        for player in self.players.iter_mut() {
+            let player_id = player.id;
+            for probe in player.iter_mut_probes() {
+                if probe.is_picket() {
+                    pickets.push((player_id, probe.get_coord()));
+                }
+            }
+        }
+        self.map.set_pickets(pickets);
+    }
+
+    /// Update the scopes of the turrets avoided by the farming probes
+    /// (see `GameConfig.probe_avoid_turrets`)
+    fn update_turret_scopes(&mut self) {
+        let mut turrets = Vec::new();
+        for player in self.players.iter() {
+            for turret in player.turrets.iter() {
+                turrets.push((player.id, turret.get_coord(), turret.get_scope(player)));
             }
         }
+        self.map.set_turret_scopes(turrets);
     }
 
-    /// Check end game condition \
-    /// If reached, update state
+    /// Limit the number of events of the step, keep the events of
+    /// highest priority (in their original order), notify the truncation \
+    /// Note: the events of the previous steps of the run are left untouched,
+    /// the events of the actions are counted in the first step
+    fn truncate_events(&mut self) {
+        let max_events = self.config.max_events_per_frame;
+        let all_events = &mut self.state_handle.get_mut().events;
+        let events = all_events.split_off(self.n_step_events);
+        if max_events == 0 || events.len() <= max_events {
+            all_events.extend(events);
+            self.n_step_events = all_events.len();
+            return;
+        }
+
+        // NOTE: sort is stable -> earlier events first for a same priority
+        let mut idxs: Vec<usize> = (0..events.len()).collect();
+        idxs.sort_by_key(|i| cmp::Reverse(events[*i].get_priority()));
+        let mut kept = idxs[..max_events].to_vec();
+        kept.sort();
+
+        let n_dropped = events.len() - max_events;
+        let mut truncated: Vec<GameEvent> = kept.iter().map(|i| events[*i].clone()).collect();
+        truncated.push(GameEvent::EventsTruncated {
+            n_dropped: n_dropped,
+        });
+        all_events.extend(truncated);
+        self.n_step_events = all_events.len();
+
+        log::warn!("Events truncated: {} events dropped", n_dropped);
+    }
+
+    /// Check end game condition: at most one player alive \
+    /// If reached, update state and set the result of the game
     fn handle_end_game_condition(&mut self) {
-        if self.players.len() == 1 {
-            self.state_handle.get_mut().game_ended = true;
+        if self.result.is_some() || self.get_living_player_count() > 1 {
+            return;
+        }
+        let winner_id = self.players.first().map(|p| p.id);
+
+        let mut rankings: Vec<u128> = winner_id.into_iter().collect();
+        rankings.extend(self.dead_player_ids.iter().rev());
+
+        self.result = Some(GameResult {
+            winner_id: winner_id,
+            rankings: rankings,
+            n_frames: self.frame,
+            duration: self.time,
+        });
+        self.state_handle.get_mut().game_ended = true;
+    }
+
+    /// Check the time limit (see `GameConfig.time_limit`) \
+    /// If reached, update state and set the result of the game:
+    /// the living players ranked by score (see `get_scores`),
+    /// a draw in case of a tie for the first place
+    fn handle_time_limit(&mut self) {
+        if self.result.is_some()
+            || self.config.time_limit <= 0.0
+            || self.time < self.config.time_limit
+        {
+            return;
         }
+        let scores = self.get_scores();
+        let winner_id = match (scores.get(0), scores.get(1)) {
+            (Some((_, first)), Some((_, second))) if first == second => None,
+            (Some((id, _)), _) => Some(*id),
+            _ => None,
+        };
+
+        let mut rankings: Vec<u128> = scores.iter().map(|(id, _)| *id).collect();
+        rankings.extend(self.dead_player_ids.iter().rev());
+
+        self.result = Some(GameResult {
+            winner_id: winner_id,
+            rankings: rankings,
+            n_frames: self.frame,
+            duration: self.time,
+        });
+        self.state_handle.get_mut().game_ended = true;
     }
 
+    /// Run the game for `dt` seconds, by fixed steps if
+    /// `GameConfig.tick_rate` is specified \
+    /// Return the changes of the state, if any
     pub fn run(&mut self, dt: f64) -> Option<GameState> {
+        if self.config.tick_rate > 0.0 {
+            self.tick_accumulator += dt;
+            while self.tick_accumulator >= self.config.tick_rate {
+                self.tick_accumulator -= self.config.tick_rate;
+                self.step(self.config.tick_rate);
+            }
+        } else {
+            self.step(dt);
+        }
+
+        self.n_step_events = 0;
+        self.state_handle.flush(&())
+    }
+
+    /// Run one simulation step of `dt` seconds \
+    /// The changes of the state are accumulated in the state handle
+    fn step(&mut self, dt: f64) {
+        self.frame += 1;
+        self.time += dt;
+
+        // process the players in a stable order (by id), independently
+        // of the order of insertion/removal in the players vec, as the
+        // resolution of the frame depends on it (claims, turret fire)
+        self.players.sort_by_key(|p| p.id);
+
+        let n_probes = self.count_probes();
+        self.update_pickets();
+        if self.config.probe_avoid_turrets {
+            self.update_turret_scopes();
+        }
+
+        // the probes can travel up to one frame of movement
+        // before being looked up in the grid
+        let max_speed =
+            self.config.get_max_probe_speed() + self.config.tech_probe_speed_increase.max(0.0);
+        let max_scope = self.config.turret_scope + self.config.tech_turret_scope_increase.max(0.0);
+        let probe_grid = ProbeGrid::build(&self.players, max_scope, max_speed * dt + 1.0);
+
+        // advance the players independently, the map is only read
+        if self.config.parallel {
+            let map = &self.map;
+            self.players
+                .par_iter_mut()
+                .for_each(|player| player.advance(map, dt, n_probes));
+        }
+
         let mut ctx = FrameContext {
             dt: dt,
+            is_income_tick: self.delayer_income.wait(dt),
             config: &self.config,
             map: &mut self.map,
+            n_probes: n_probes,
+            probe_grid: probe_grid,
+            events: Vec::new(),
+            is_advanced: self.config.parallel,
         };
 
         // extract players for iteration
         let mut players: Vec<Player> = self.players.drain(..).collect();
 
-        let mut dead_player_idxs = Vec::new();
+        let mut dead_player_ids = Vec::new();
 
         for i in 0..players.len() {
             let mut player = players.remove(i);
@@ -219,7 +937,7 @@ impl Game {
             if let Some(state) = state {
                 // remove dead players
                 if state.death.is_some() {
-                    dead_player_idxs.push(i);
+                    dead_player_ids.push(player.id);
                 }
 
                 state_vec_insert(&mut self.state_handle.get_mut().players, state);
@@ -231,29 +949,60 @@ impl Game {
         // put back players
         self.players = players.drain(..).collect();
 
-        // remove all death players (note: in REVERSE order)
+        if !ctx.events.is_empty() {
+            self.state_handle.get_mut().events.append(&mut ctx.events);
+        }
+
+        // remove all death players (by id -> independent of players order)
         // this can be done here as handle_map_dead_building does
         // not provoke player's death (see Player::kill_factory)
-        for idx in dead_player_idxs.iter().rev() {
-            let player = self.players.remove(*idx);
-            self.player_stats.insert(player.id, player.get_stats(1.0));
+        for id in dead_player_ids.iter() {
+            self.remove_player(*id);
+            self.state_handle
+                .get_mut()
+                .events
+                .push(GameEvent::PlayerDefeated { player_id: *id });
         }
 
         self.map.run(dt);
 
         if let Some(map_state) = self.map.state_handle.flush(&()) {
+            self.handle_map_building_risk(&map_state);
             self.handle_map_dead_building(&map_state);
+            self.handle_map_building_kills(&map_state);
             self.state_handle.get_mut().map = Some(map_state);
         }
 
         self.handle_end_game_condition();
-
-        self.state_handle.flush(&())
+        self.handle_time_limit();
+        self.truncate_events();
     }
 }
 
 // Actions block
 impl Game {
+    /// Return the tile at `coord` \
+    /// Return an error in case the coordinate is out of the map
+    ///
+    /// Note: take the map as argument (and not self) to allow
+    /// borrowing the players mutably at the same time
+    fn require_tile<'a>(map: &'a Map, coord: &Coord) -> Result<&'a Tile, String> {
+        match map.get_tile(coord) {
+            Some(tile) => Ok(tile),
+            None => Err(format!("Invalid tile coordinate ({:?})", coord)),
+        }
+    }
+
+    /// Return an error in case none of the `n_ids` given probes
+    /// could be actioned (ex: dead probes), as long as some were given \
+    /// Note: a partial success is not an error
+    fn require_actioned_probes(n_actioned: usize, n_ids: usize) -> Result<(), String> {
+        if n_ids > 0 && n_actioned == 0 {
+            return Err(String::from("Invalid probes (Are they dead ?)"));
+        }
+        Ok(())
+    }
+
     pub fn resign_game(&mut self, player_id: u128) -> Result<(), String> {
         let state = match self.kill_player(player_id, PlayerDeathCause::Resigned) {
             Some(state) => state,
@@ -267,6 +1016,36 @@ impl Game {
         Ok(())
     }
 
+    /// Abandon the player without defeating them (ex: on disconnection),
+    /// the player can be resumed later (see `Player::abandon`)
+    pub fn abandon_player(&mut self, player_id: u128) -> Result<(), String> {
+        let player = match self.players.iter_mut().find(|p| p.id == player_id) {
+            Some(player) => player,
+            None => {
+                return Err(String::from("Invalid player (Are you dead ?)"));
+            }
+        };
+        player.abandon(&self.map)
+    }
+
+    /// Resume an abandoned player (see `Player::resume`)
+    pub fn resume_player(&mut self, player_id: u128) -> Result<(), String> {
+        let player = match self.players.iter_mut().find(|p| p.id == player_id) {
+            Some(player) => player,
+            None => {
+                return Err(String::from("Invalid player (Are you dead ?)"));
+            }
+        };
+        player.resume()
+    }
+
+    /// Build a factory for the player at the given coordinate
+    ///
+    /// Note: the tile only needs to pass `Tile::can_build`, even if it
+    /// is contested by an opponent, actions are resolved before the
+    /// next `run`. If the tile is then conquered (occupation dropping
+    /// to 0), the factory dies through the map's dead building path
+    /// (see `handle_map_dead_building`), same as any other building.
     pub fn create_factory(
         &mut self,
         player_id: u128,
@@ -274,12 +1053,7 @@ impl Game {
         coord_y: i32,
     ) -> Result<(), String> {
         let coord = Coord::new(coord_x, coord_y);
-        let tile = match self.map.get_tile(&coord) {
-            Some(tile) => tile,
-            None => {
-                return Err(format!("Tile coordinate is invalid ({:?})", &coord));
-            }
-        };
+        let tile = Game::require_tile(&self.map, &coord)?;
 
         let player = match self.players.iter_mut().find(|p| p.id == player_id) {
             Some(player) => player,
@@ -288,18 +1062,28 @@ impl Game {
             }
         };
 
+        // NOTE: a building is registered on the tile as soon as
+        // it is built, so this also holds for multiple builds
+        // on the same tile in one frame
+        if tile.building_id.is_some() {
+            return Err(String::from("Tile already has a building"));
+        }
+
         if !tile.can_build(player) {
             return Err(String::from("Cannot build on tile"));
         }
 
         // actually build the factory
+        let price = player.get_factory_price(&coord, &self.map);
         if !player.build_factory(coord, &mut self.map, &self.config) {
-            return Err(format!("Not enough money (<{})", self.config.factory_price));
+            return Err(format!("Not enough money (<{})", price));
         }
 
         Ok(())
     }
 
+    /// Build a turret for the player at the given coordinate
+    /// (see `create_factory` for contested tiles)
     pub fn create_turret(
         &mut self,
         player_id: u128,
@@ -307,12 +1091,7 @@ impl Game {
         coord_y: i32,
     ) -> Result<(), String> {
         let coord = Coord::new(coord_x, coord_y);
-        let tile = match self.map.get_tile(&coord) {
-            Some(tile) => tile,
-            None => {
-                return Err(format!("Tile coordinate is invalid ({:?})", &coord));
-            }
-        };
+        let tile = Game::require_tile(&self.map, &coord)?;
 
         let player = match self.players.iter_mut().find(|p| p.id == player_id) {
             Some(player) => player,
@@ -321,13 +1100,21 @@ impl Game {
             }
         };
 
+        // NOTE: a building is registered on the tile as soon as
+        // it is built, so this also holds for multiple builds
+        // on the same tile in one frame
+        if tile.building_id.is_some() {
+            return Err(String::from("Tile already has a building"));
+        }
+
         if !tile.can_build(player) {
             return Err(String::from("Cannot build on tile"));
         }
 
         // actually build the turret
+        let price = player.get_turret_price(&coord, &self.map);
         if !player.build_turret(coord, &mut self.map, &self.config) {
-            return Err(format!("Not enough money (<{})", self.config.turret_price));
+            return Err(format!("Not enough money (<{})", price));
         }
 
         Ok(())
@@ -341,12 +1128,7 @@ impl Game {
         target_y: i32,
     ) -> Result<(), String> {
         let target = Coord::new(target_x, target_y);
-        let tile = match self.map.get_tile(&target) {
-            Some(tile) => tile,
-            None => {
-                return Err(format!("Move target is invalid ({:?})", &target));
-            }
-        };
+        let tile = Game::require_tile(&self.map, &target)?;
 
         let player = match self.players.iter_mut().find(|p| p.id == player_id) {
             Some(player) => player,
@@ -359,10 +1141,12 @@ impl Game {
             return Err(format!("Move target is invalid ({:?})", &target));
         }
 
-        for id in ids {
-            player.set_probe_target(id, target.as_point());
-        }
-        Ok(())
+        let n_ids = ids.len();
+        let n_actioned = ids
+            .into_iter()
+            .filter(|id| player.set_probe_target(*id, target.as_point()))
+            .count();
+        Game::require_actioned_probes(n_actioned, n_ids)
     }
 
     pub fn explode_probes(&mut self, player_id: u128, ids: Vec<u128>) -> Result<(), String> {
@@ -373,11 +1157,12 @@ impl Game {
             }
         };
 
-        for id in ids {
-            player.explode_probe(id, &mut self.map);
-        }
-
-        Ok(())
+        let n_ids = ids.len();
+        let n_actioned = ids
+            .into_iter()
+            .filter(|id| player.explode_probe(*id, &mut self.map))
+            .count();
+        Game::require_actioned_probes(n_actioned, n_ids)
     }
 
     pub fn probes_attack(&mut self, player_id: u128, ids: Vec<u128>) -> Result<(), String> {
@@ -388,24 +1173,1734 @@ impl Game {
             }
         };
 
-        for id in ids {
-            player.probe_attack(id, &mut self.map);
-        }
+        let n_ids = ids.len();
+        let n_actioned = ids
+            .into_iter()
+            .filter(|id| player.probe_attack(*id, &mut self.map))
+            .count();
+        Game::require_actioned_probes(n_actioned, n_ids)
+    }
 
-        Ok(())
+    /// Create a new group of probes for the player (see `Player::create_group`) \
+    /// Return the id of the group
+    pub fn create_group(&mut self, player_id: u128, name: String) -> Result<u128, String> {
+        let player = match self.players.iter_mut().find(|p| p.id == player_id) {
+            Some(player) => player,
+            None => {
+                return Err(String::from("Invalid player (Are you dead ?)"));
+            }
+        };
+        Ok(player.create_group(name))
     }
 
-    pub fn acquire_tech(&mut self, player_id: u128, tech: &str) -> Result<(), String> {
+    /// Add the probes to the group of the player (see `Player::assign_probes`)
+    pub fn assign_probes(
+        &mut self,
+        player_id: u128,
+        group_id: u128,
+        ids: Vec<u128>,
+    ) -> Result<(), String> {
         let player = match self.players.iter_mut().find(|p| p.id == player_id) {
             Some(player) => player,
             None => {
                 return Err(String::from("Invalid player (Are you dead ?)"));
             }
         };
+        let n_ids = ids.len();
+        let n_assigned = player.assign_probes(group_id, ids)?;
+        Game::require_actioned_probes(n_assigned, n_ids)
+    }
 
-        let tech = Techs::from_string(tech)?;
-        player.acquire_tech(tech)?;
+    /// Return the ids of the probes of the group of the player
+    fn get_group_probe_ids(&self, player_id: u128, group_id: u128) -> Result<Vec<u128>, String> {
+        let player = match self.players.iter().find(|p| p.id == player_id) {
+            Some(player) => player,
+            None => {
+                return Err(String::from("Invalid player (Are you dead ?)"));
+            }
+        };
+        player.get_group_probe_ids(group_id)
+    }
 
-        Ok(())
+    /// Move the probes of the group (see `Game::move_probes`)
+    pub fn move_group(
+        &mut self,
+        player_id: u128,
+        group_id: u128,
+        target_x: i32,
+        target_y: i32,
+    ) -> Result<(), String> {
+        let ids = self.get_group_probe_ids(player_id, group_id)?;
+        self.move_probes(player_id, ids, target_x, target_y)
+    }
+
+    /// Explode the probes of the group (see `Game::explode_probes`)
+    pub fn explode_group(&mut self, player_id: u128, group_id: u128) -> Result<(), String> {
+        let ids = self.get_group_probe_ids(player_id, group_id)?;
+        self.explode_probes(player_id, ids)
+    }
+
+    /// Make the probes of the group attack (see `Game::probes_attack`)
+    pub fn group_attack(&mut self, player_id: u128, group_id: u128) -> Result<(), String> {
+        let ids = self.get_group_probe_ids(player_id, group_id)?;
+        self.probes_attack(player_id, ids)
+    }
+
+    pub fn hold_probes(&mut self, player_id: u128, ids: Vec<u128>) -> Result<(), String> {
+        let player = match self.players.iter_mut().find(|p| p.id == player_id) {
+            Some(player) => player,
+            None => {
+                return Err(String::from("Invalid player (Are you dead ?)"));
+            }
+        };
+
+        let n_ids = ids.len();
+        let n_actioned = ids.into_iter().filter(|id| player.hold_probe(*id)).count();
+        Game::require_actioned_probes(n_actioned, n_ids)
+    }
+
+    /// Stop the probes (see `Player::stop_probes`)
+    pub fn stop_probes(&mut self, player_id: u128, ids: Vec<u128>) -> Result<(), String> {
+        let player = match self.players.iter_mut().find(|p| p.id == player_id) {
+            Some(player) => player,
+            None => {
+                return Err(String::from("Invalid player (Are you dead ?)"));
+            }
+        };
+
+        let n_actioned = player.stop_probes(&ids);
+        Game::require_actioned_probes(n_actioned, ids.len())
+    }
+
+    /// Make the probes guard their tile against opponents claims,
+    /// only the probes on a tile of the player can be pickets
+    pub fn picket_probes(&mut self, player_id: u128, ids: Vec<u128>) -> Result<(), String> {
+        let player = match self.players.iter_mut().find(|p| p.id == player_id) {
+            Some(player) => player,
+            None => {
+                return Err(String::from("Invalid player (Are you dead ?)"));
+            }
+        };
+
+        let n_ids = ids.len();
+        let n_actioned = ids
+            .into_iter()
+            .filter(|id| player.set_probe_picket(*id, &self.map))
+            .count();
+        Game::require_actioned_probes(n_actioned, n_ids)
+    }
+
+    /// Set the region where the probes search their farm targets in priority,
+    /// if the radius is 0: clear the region \
+    /// Note: the probes fall back on their usual targets when
+    /// the region has no valid target (ex: out of the map)
+    pub fn farm_region_probes(
+        &mut self,
+        player_id: u128,
+        ids: Vec<u128>,
+        center_x: i32,
+        center_y: i32,
+        radius: u32,
+    ) -> Result<(), String> {
+        let center = Coord::new(center_x, center_y);
+
+        let player = match self.players.iter_mut().find(|p| p.id == player_id) {
+            Some(player) => player,
+            None => {
+                return Err(String::from("Invalid player (Are you dead ?)"));
+            }
+        };
+
+        let n_ids = ids.len();
+        let n_actioned = ids
+            .into_iter()
+            .filter(|id| player.set_probe_farm_region(*id, center.clone(), radius, &mut self.map))
+            .count();
+        Game::require_actioned_probes(n_actioned, n_ids)
+    }
+
+    /// Make the probes patrol between the points `a` and `b`,
+    /// none of which can be on an opponent tile
+    pub fn patrol_probes(
+        &mut self,
+        player_id: u128,
+        ids: Vec<u128>,
+        a_x: i32,
+        a_y: i32,
+        b_x: i32,
+        b_y: i32,
+    ) -> Result<(), String> {
+        let a = Coord::new(a_x, a_y);
+        let b = Coord::new(b_x, b_y);
+        let tile_a = Game::require_tile(&self.map, &a)?;
+        let tile_b = Game::require_tile(&self.map, &b)?;
+
+        let player = match self.players.iter_mut().find(|p| p.id == player_id) {
+            Some(player) => player,
+            None => {
+                return Err(String::from("Invalid player (Are you dead ?)"));
+            }
+        };
+
+        if tile_a.is_owned_by_opponent_of(player.id) || tile_b.is_owned_by_opponent_of(player.id) {
+            return Err(format!("Patrol target is invalid ({:?}, {:?})", &a, &b));
+        }
+
+        let n_ids = ids.len();
+        let n_actioned = ids
+            .into_iter()
+            .filter(|id| player.set_probe_patrol(*id, a.as_point(), b.as_point()))
+            .count();
+        Game::require_actioned_probes(n_actioned, n_ids)
+    }
+
+    /// Make the probes siege the target, which has to be an opponent tile
+    /// (see `ProbePolicy::Siege`)
+    pub fn siege_probes(
+        &mut self,
+        player_id: u128,
+        ids: Vec<u128>,
+        target_x: i32,
+        target_y: i32,
+    ) -> Result<(), String> {
+        let target = Coord::new(target_x, target_y);
+        let tile = Game::require_tile(&self.map, &target)?;
+
+        let player = match self.players.iter_mut().find(|p| p.id == player_id) {
+            Some(player) => player,
+            None => {
+                return Err(String::from("Invalid player (Are you dead ?)"));
+            }
+        };
+
+        if !tile.is_owned_by_opponent_of(player.id) {
+            return Err(format!("Siege target is invalid ({:?})", &target));
+        }
+
+        let n_ids = ids.len();
+        let n_actioned = ids
+            .into_iter()
+            .filter(|id| player.set_probe_siege(*id, target.as_point()))
+            .count();
+        Game::require_actioned_probes(n_actioned, n_ids)
+    }
+
+    pub fn set_default_probe_policy(
+        &mut self,
+        player_id: u128,
+        policy: &str,
+    ) -> Result<(), String> {
+        let player = match self.players.iter_mut().find(|p| p.id == player_id) {
+            Some(player) => player,
+            None => {
+                return Err(String::from("Invalid player (Are you dead ?)"));
+            }
+        };
+
+        let policy = ProbePolicy::from_string(policy)?;
+        player.set_default_probe_policy(policy)?;
+
+        Ok(())
+    }
+
+    /// Set the target of the probes produced by a factory of the player
+    pub fn set_factory_rally(
+        &mut self,
+        player_id: u128,
+        factory_id: u128,
+        target_x: i32,
+        target_y: i32,
+    ) -> Result<(), String> {
+        let target = Coord::new(target_x, target_y);
+        let tile = Game::require_tile(&self.map, &target)?;
+
+        let player = match self.players.iter_mut().find(|p| p.id == player_id) {
+            Some(player) => player,
+            None => {
+                return Err(String::from("Invalid player (Are you dead ?)"));
+            }
+        };
+
+        if tile.is_owned_by_opponent_of(player.id) {
+            return Err(format!("Rally target is invalid ({:?})", &target));
+        }
+
+        if !player.set_factory_rally(factory_id, target) {
+            return Err(String::from("Invalid factory"));
+        }
+        Ok(())
+    }
+
+    /// Re-run the expansion of a factory of the player
+    /// (see `Player::reexpand_factory`)
+    pub fn reexpand_factory(&mut self, player_id: u128, factory_id: u128) -> Result<(), String> {
+        let player = match self.players.iter_mut().find(|p| p.id == player_id) {
+            Some(player) => player,
+            None => {
+                return Err(String::from("Invalid player (Are you dead ?)"));
+            }
+        };
+        player.reexpand_factory(factory_id)
+    }
+
+    /// Pause or resume the probe production of a factory of the player
+    /// (policy: "Produce" or "Pause")
+    pub fn set_factory_policy(
+        &mut self,
+        player_id: u128,
+        factory_id: u128,
+        policy: &str,
+    ) -> Result<(), String> {
+        let player = match self.players.iter_mut().find(|p| p.id == player_id) {
+            Some(player) => player,
+            None => {
+                return Err(String::from("Invalid player (Are you dead ?)"));
+            }
+        };
+
+        let policy = FactoryPolicy::from_string(policy)?;
+        player.set_factory_policy(factory_id, policy)?;
+
+        Ok(())
+    }
+
+    pub fn acquire_tech(&mut self, player_id: u128, tech: &str) -> Result<(), String> {
+        let player = match self.players.iter_mut().find(|p| p.id == player_id) {
+            Some(player) => player,
+            None => {
+                return Err(String::from("Invalid player (Are you dead ?)"));
+            }
+        };
+
+        let tech = Techs::from_string(tech)?;
+        player.acquire_tech(tech.clone())?;
+        self.state_handle
+            .get_mut()
+            .events
+            .push(GameEvent::TechAcquired {
+                player_id: player_id,
+                tech: tech,
+            });
+
+        Ok(())
+    }
+
+    /// Refund a technology of the player (see `Player::refund_tech`)
+    pub fn refund_tech(&mut self, player_id: u128, tech: &str) -> Result<(), String> {
+        let player = match self.players.iter_mut().find(|p| p.id == player_id) {
+            Some(player) => player,
+            None => {
+                return Err(String::from("Invalid player (Are you dead ?)"));
+            }
+        };
+
+        let tech = Techs::from_string(tech)?;
+        player.refund_tech(tech)
+    }
+
+    /// Make the turret fire in priority at the probes close to the target,
+    /// as long as the target is within the turret's scope
+    pub fn focus_turret(
+        &mut self,
+        player_id: u128,
+        turret_id: u128,
+        target_x: i32,
+        target_y: i32,
+    ) -> Result<(), String> {
+        let target = Coord::new(target_x, target_y);
+        Game::require_tile(&self.map, &target)?;
+
+        let player = match self.players.iter_mut().find(|p| p.id == player_id) {
+            Some(player) => player,
+            None => {
+                return Err(String::from("Invalid player (Are you dead ?)"));
+            }
+        };
+
+        if !player.set_turret_focus(turret_id, target) {
+            return Err(String::from("Invalid turret"));
+        }
+        Ok(())
+    }
+
+    /// Scrap a turret of the player, part of its price is refunded
+    /// (see `GameConfig.turret_scrap_refund_rate`)
+    pub fn scrap_turret(&mut self, player_id: u128, turret_id: u128) -> Result<(), String> {
+        let player = match self.players.iter_mut().find(|p| p.id == player_id) {
+            Some(player) => player,
+            None => {
+                return Err(String::from("Invalid player (Are you dead ?)"));
+            }
+        };
+
+        let turret_state = match player.scrap_turret(turret_id) {
+            Some(state) => state,
+            None => {
+                return Err(String::from("Invalid turret"));
+            }
+        };
+        self.map.remove_building(player_id, turret_id);
+
+        // insert player state into current state
+        let mut state = PlayerState::new(&player_id);
+        state.turrets.push(turret_state);
+        state_vec_insert(&mut self.state_handle.get_mut().players, state);
+        Ok(())
+    }
+
+    /// Scrap a factory of the player \
+    /// Note: scrapping the last factory defeats the player
+    /// (see `Player::handle_lose_condition`)
+    pub fn scrap_factory(&mut self, player_id: u128, factory_id: u128) -> Result<(), String> {
+        let player = match self.players.iter_mut().find(|p| p.id == player_id) {
+            Some(player) => player,
+            None => {
+                return Err(String::from("Invalid player (Are you dead ?)"));
+            }
+        };
+
+        let factory_state = match player.kill_factory(factory_id, FactoryDeathCause::Scrapped) {
+            Some(state) => state,
+            None => {
+                return Err(String::from("Invalid factory"));
+            }
+        };
+        self.map.remove_building(player_id, factory_id);
+
+        // insert player state into current state
+        let mut state = PlayerState::new(&player_id);
+        state.factories.push(factory_state);
+        state_vec_insert(&mut self.state_handle.get_mut().players, state);
+        Ok(())
+    }
+
+    /// Perform the action
+    pub fn apply_action(&mut self, action: &Action) -> Result<(), String> {
+        match action {
+            Action::ResignGame { player_id } => self.resign_game(*player_id),
+            Action::BuildFactory { player_id, coord } => {
+                self.create_factory(*player_id, coord.x, coord.y)
+            }
+            Action::BuildTurret { player_id, coord } => {
+                self.create_turret(*player_id, coord.x, coord.y)
+            }
+            Action::MoveProbes {
+                player_id,
+                ids,
+                target,
+            } => self.move_probes(*player_id, ids.clone(), target.x, target.y),
+            Action::ExplodeProbes { player_id, ids } => {
+                self.explode_probes(*player_id, ids.clone())
+            }
+            Action::ProbesAttack { player_id, ids } => self.probes_attack(*player_id, ids.clone()),
+            Action::HoldProbes { player_id, ids } => self.hold_probes(*player_id, ids.clone()),
+            Action::AcquireTech { player_id, tech } => self.acquire_tech(*player_id, tech),
+            Action::SetDefaultProbePolicy { player_id, policy } => {
+                self.set_default_probe_policy(*player_id, policy)
+            }
+        }
+    }
+
+    /// Perform a batch of binary encoded actions (see `protocol`) \
+    /// The batch is parsed beforehand: if it is malformed, no action is performed \
+    /// Otherwise all actions are performed in order, even if some of them fail,
+    /// in which case return an error listing the failed actions
+    pub fn apply_actions_bytes(&mut self, buf: &[u8]) -> Result<(), String> {
+        let actions = decode_actions(buf)?;
+        let mut errors = Vec::new();
+        for (idx, action) in actions.iter().enumerate() {
+            if let Err(msg) = self.apply_action(action) {
+                errors.push(format!("action {}: {}", idx, msg));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::tests::test_config;
+    use crate::game::{PartialConfig, ProbeState, ScoreWeights};
+    use std::collections::HashSet;
+
+    fn new_game(config: GameConfig) -> Game {
+        Game::new(vec![1, 2], config)
+    }
+
+    /// Return the (complete) states of the probes of the player
+    fn get_probes(game: &Game, player_id: u128) -> Vec<ProbeState> {
+        game.get_player(player_id)
+            .unwrap()
+            .get_complete_state()
+            .factories
+            .into_iter()
+            .flat_map(|f| f.probes)
+            .collect()
+    }
+
+    fn get_probe_ids(game: &Game, player_id: u128) -> Vec<u128> {
+        get_probes(game, player_id).iter().map(|p| p.id).collect()
+    }
+
+    fn is_invalid_tile<T>(result: Result<T, String>) -> bool {
+        match result {
+            Err(msg) => msg.starts_with("Invalid tile coordinate"),
+            Ok(_) => false,
+        }
+    }
+
+    #[test]
+    fn actions_reject_off_map_coords() {
+        let mut game = new_game(test_config());
+        let money = game.get_player(1).unwrap().get_money();
+
+        assert!(is_invalid_tile(game.create_factory(1, -1, 0)));
+        assert!(is_invalid_tile(game.create_turret(1, 0, 20)));
+        assert!(is_invalid_tile(game.move_probes(1, vec![], 20, 20)));
+        assert!(is_invalid_tile(game.patrol_probes(1, vec![], 0, 0, 0, -5)));
+        assert!(is_invalid_tile(game.siege_probes(1, vec![], 100, 0)));
+        assert!(is_invalid_tile(game.set_factory_rally(1, 0, -1, -1)));
+        assert!(is_invalid_tile(game.focus_turret(1, 0, 0, 21)));
+        // the coordinate is checked first, whatever the player
+        assert!(is_invalid_tile(game.create_factory(3, -1, 0)));
+
+        assert_eq!(game.get_player(1).unwrap().get_money(), money);
+    }
+
+    #[test]
+    fn describe_reports_players_and_end() {
+        let mut config = test_config();
+        config.time_limit = 1.0;
+        let mut game = new_game(config);
+
+        let desc = game.describe();
+        assert!(desc.contains("ended: false"));
+        assert!(desc.contains("player 1: money: 100.00"));
+        assert!(desc.contains("player 2: money: 100.00"));
+
+        // both players are alive, but the time limit is reached
+        game.run(1.0);
+        assert_eq!(game.get_living_player_count(), 2);
+        assert!(game.describe().contains("ended: true"));
+    }
+
+    #[test]
+    fn full_vision_state_is_complete_state() {
+        let mut game = new_game(test_config());
+        game.run(1.0);
+        let state = game.get_state_for_player(1).unwrap();
+        assert_eq!(
+            format!("{:?}", state),
+            format!("{:?}", game.get_complete_state())
+        );
+    }
+
+    #[test]
+    fn limited_vision_state_is_strict_subset() {
+        let mut config = test_config();
+        config.vision_radius = 3.0;
+        let mut game = new_game(config);
+        for id in [1, 2] {
+            game.create_group(id, String::from("group")).unwrap();
+        }
+        game.run(1.0);
+        let complete = game.get_complete_state();
+        let state = game.get_state_for_player(1).unwrap();
+
+        let complete_tiles = complete.map.unwrap().tiles;
+        let tiles = state.map.unwrap().tiles;
+        assert!(!tiles.is_empty());
+        assert!(tiles.len() < complete_tiles.len());
+        for tile in tiles.iter() {
+            assert!(complete_tiles.iter().any(|t| t.id == tile.id));
+        }
+        // the opponent's start (other side of the map) isn't visible
+        let opponent = state.players.iter().find(|p| p.id == 2).unwrap();
+        assert!(opponent.factories.is_empty());
+        assert!(complete
+            .players
+            .iter()
+            .any(|p| p.id == 2 && !p.factories.is_empty()));
+        // only the player's own groups are included
+        assert!(opponent.groups.is_none());
+        assert!(complete
+            .players
+            .iter()
+            .any(|p| p.id == 2 && p.groups.is_some()));
+        let player = state.players.iter().find(|p| p.id == 1).unwrap();
+        assert_eq!(player.groups.as_ref().map(|g| g.len()), Some(1));
+    }
+
+    #[test]
+    fn hidden_factory_id_is_never_revealed() {
+        let mut config = test_config();
+        config.vision_radius = 3.0;
+        config.initial_n_probes = 1;
+        config.factory_max_probe = 1;
+        let mut game = new_game(config.clone());
+        let factory_id = game.get_player(2).unwrap().factories[0].id;
+        // neutral tile next to the territory of the player
+        let pos = game.get_player(1).unwrap().factories[0].pos.clone();
+        let target = Coord::new(pos.x - config.factory_expansion_size as i32 - 3, pos.y);
+        let ids = get_probe_ids(&game, 2);
+        game.move_probes(2, ids.clone(), target.x, target.y)
+            .unwrap();
+
+        let mut is_probe_seen = false;
+        for _ in 0..300 {
+            game.run(0.1);
+            let state = game.get_state_for_player(1).unwrap();
+            let opponent = state.players.iter().find(|p| p.id == 2).unwrap();
+            assert!(opponent.factories.iter().all(|f| f.id != factory_id));
+            let bytes = bincode::serialize(&state).unwrap();
+            assert!(!bytes
+                .windows(16)
+                .any(|w| w == factory_id.to_le_bytes().as_slice()));
+
+            if let Some(factory) = opponent.factories.first() {
+                assert_eq!(factory.id, HIDDEN_FACTORY_ID);
+                assert!(factory.coord.is_none());
+                assert!(factory.probes.iter().all(|p| ids.contains(&p.id)));
+                is_probe_seen = true;
+            }
+        }
+        assert!(is_probe_seen);
+    }
+
+    #[test]
+    fn held_probes_stay_put_until_retasked() {
+        let mut config = test_config();
+        config.initial_n_probes = 1;
+        config.factory_max_probe = 1;
+        let mut game = new_game(config);
+        for id in [1, 2] {
+            let ids = get_probe_ids(&game, id);
+            game.hold_probes(id, ids).unwrap();
+        }
+        // let the factories finish their expansion
+        for _ in 0..20 {
+            game.run(0.1);
+        }
+        let probes = get_probes(&game, 1);
+        let grid = game.get_occupation_grid();
+
+        for _ in 0..100 {
+            game.run(0.1);
+        }
+        let held = get_probes(&game, 1);
+        assert_eq!(held.len(), 1);
+        assert_eq!(held[0].pos, probes[0].pos);
+        assert!(matches!(held[0].policy, Some(ProbePolicy::Hold)));
+        assert_eq!(game.get_occupation_grid(), grid);
+
+        let pos = probes[0].pos.as_ref().unwrap().as_coord();
+        game.move_probes(1, vec![held[0].id], pos.x, pos.y - 1)
+            .unwrap();
+        for _ in 0..20 {
+            game.run(0.1);
+        }
+        assert_ne!(get_probes(&game, 1)[0].pos, probes[0].pos);
+
+        // stopping is holding
+        game.stop_probes(1, vec![held[0].id]).unwrap();
+        game.run(0.1);
+        let stopped = get_probes(&game, 1);
+        assert!(matches!(stopped[0].policy, Some(ProbePolicy::Hold)));
+        assert!(game.stop_probes(1, vec![0]).is_err());
+    }
+
+    #[test]
+    fn building_on_contested_tile_dies_cleanly() {
+        let mut game = new_game(test_config());
+        let coord = Coord::new(10, 10);
+        game.map.claim_tile(1, &coord, 1);
+        game.create_factory(1, coord.x, coord.y).unwrap();
+        let factory_id = game.map.get_tile(&coord).unwrap().building_id.unwrap();
+        // building twice on the tile fails with a clear error
+        assert_eq!(
+            game.create_turret(1, coord.x, coord.y),
+            Err(String::from("Tile already has a building"))
+        );
+
+        // conquered on the same frame
+        game.map.claim_tile(2, &coord, 1);
+        let state = game.run(0.1).unwrap();
+
+        let tile = game.map.get_tile(&coord).unwrap();
+        assert_eq!(tile.owner_id, None);
+        assert_eq!(tile.building_id, None);
+        assert!(game.get_building_footprint(factory_id).is_err());
+        let player = game.get_player(1).unwrap();
+        assert!(player.factories.iter().all(|f| f.id != factory_id));
+        assert!(state.events.iter().any(|e| matches!(
+            e,
+            GameEvent::BuildingConquered { building_id, .. } if *building_id == factory_id
+        )));
+
+        // the tile can be claimed and built on again
+        game.map.claim_tile(1, &coord, 1);
+        assert!(game.create_factory(1, coord.x, coord.y).is_ok());
+    }
+
+    #[test]
+    fn forked_game_runs_independently() {
+        let mut game = new_game(test_config());
+        for _ in 0..5 {
+            game.run(0.1);
+        }
+        let mut fork = game.fork();
+        for _ in 0..5 {
+            game.run(0.1);
+            fork.run(0.1);
+        }
+        // same ids, same evolution
+        assert_eq!(
+            format!("{:?}", fork.get_complete_state()),
+            format!("{:?}", game.get_complete_state())
+        );
+
+        fork.create_factory(1, 17, 10).unwrap();
+        game.run(0.1);
+        fork.run(0.1);
+        let player = game.get_player(1).unwrap();
+        let forked_player = fork.get_player(1).unwrap();
+        assert_eq!(player.factories.len(), 1);
+        assert_eq!(forked_player.factories.len(), 2);
+        assert_eq!(
+            forked_player.get_money(),
+            player.get_money() - fork.config.factory_price
+        );
+        // the opponent is unaffected
+        assert_eq!(
+            format!("{:?}", fork.get_player(2).unwrap().get_complete_state()),
+            format!("{:?}", game.get_player(2).unwrap().get_complete_state())
+        );
+    }
+
+    #[test]
+    fn second_build_on_tile_fails_without_orphan() {
+        let mut game = new_game(test_config());
+        // owned tile, next to the start position
+        let coord = Coord::new(17, 10);
+        let money = game.get_player(1).unwrap().get_money();
+
+        game.create_factory(1, coord.x, coord.y).unwrap();
+        assert!(game.create_factory(1, coord.x, coord.y).is_err());
+        assert!(game.create_turret(1, coord.x, coord.y).is_err());
+
+        let player = game.get_player(1).unwrap();
+        assert_eq!(player.factories.len(), 2);
+        assert!(player.turrets.is_empty());
+        assert_eq!(player.get_money(), money - game.config.factory_price);
+        let building_id = game.map.get_tile(&coord).unwrap().building_id;
+        assert_eq!(building_id, Some(player.factories[1].id));
+    }
+
+    #[test]
+    fn binary_actions_are_applied() {
+        let mut game = new_game(test_config());
+        let money = game.get_player(1).unwrap().get_money();
+        let buf = crate::game::protocol::encode_actions(&vec![
+            Action::BuildFactory {
+                player_id: 1,
+                coord: Coord::new(17, 10),
+            },
+            // opponent tile
+            Action::BuildTurret {
+                player_id: 1,
+                coord: Coord::new(3, 10),
+            },
+            Action::AcquireTech {
+                player_id: 2,
+                tech: String::from("PROBE_HP"),
+            },
+        ]);
+
+        let err = game.apply_actions_bytes(&buf).unwrap_err();
+        assert_eq!(err, "action 1: Cannot build on tile");
+        let player = game.get_player(1).unwrap();
+        assert_eq!(player.factories.len(), 2);
+        assert_eq!(player.get_money(), money - game.config.factory_price);
+        assert!(game.get_player(2).unwrap().has_tech(&Techs::PROBE_HP));
+
+        // malformed: no action is performed
+        assert!(game.apply_actions_bytes(&buf[..buf.len() - 1]).is_err());
+        assert_eq!(game.get_player(1).unwrap().factories.len(), 2);
+    }
+
+    /// Serialize the state, with the ids of the entities
+    /// not in `known_ids` (i.e. created with random ids) replaced by 0
+    fn serialize_without_new_ids(mut state: GameState, known_ids: &HashSet<u128>) -> Vec<u8> {
+        let anonymize = |id: &mut u128| {
+            if !known_ids.contains(id) {
+                *id = 0;
+            }
+        };
+        for player in state.players.iter_mut() {
+            for factory in player.factories.iter_mut() {
+                anonymize(&mut factory.id);
+                factory.probes.iter_mut().for_each(|p| anonymize(&mut p.id));
+            }
+            for turret in player.turrets.iter_mut() {
+                anonymize(&mut turret.id);
+                if let Some(id) = turret.shot_id.as_mut() {
+                    anonymize(id);
+                }
+                turret.splash_ids.iter_mut().for_each(anonymize);
+            }
+        }
+        bincode::serialize(&state).unwrap()
+    }
+
+    #[test]
+    fn binary_actions_match_method_calls() {
+        let mut config = test_config();
+        config.initial_n_probes = 2;
+        let mut game = new_game(config);
+        let known_ids: HashSet<u128> = game
+            .get_complete_state()
+            .players
+            .iter()
+            .flat_map(|p| p.factories.iter())
+            .flat_map(|f| f.probes.iter().map(|p| p.id).chain([f.id]))
+            .collect();
+        let mut expected = game.fork();
+
+        let buf = crate::game::protocol::encode_actions(&vec![
+            Action::BuildFactory {
+                player_id: 1,
+                coord: Coord::new(17, 10),
+            },
+            Action::BuildTurret {
+                player_id: 1,
+                coord: Coord::new(17, 11),
+            },
+            Action::AcquireTech {
+                player_id: 2,
+                tech: String::from("PROBE_HP"),
+            },
+        ]);
+        game.apply_actions_bytes(&buf).unwrap();
+        expected.create_factory(1, 17, 10).unwrap();
+        expected.create_turret(1, 17, 11).unwrap();
+        expected.acquire_tech(2, "PROBE_HP").unwrap();
+
+        for _ in 0..20 {
+            assert_eq!(
+                serialize_without_new_ids(game.get_complete_state(), &known_ids),
+                serialize_without_new_ids(expected.get_complete_state(), &known_ids)
+            );
+            game.run(0.1);
+            expected.run(0.1);
+        }
+    }
+
+    #[test]
+    fn living_players_decrease_on_deaths() {
+        let mut config = test_config();
+        config.n_player = 3;
+        let mut game = Game::new(vec![1, 2, 3], config);
+        game.run(0.1);
+        assert_eq!(game.get_living_player_count(), 3);
+
+        game.resign_game(3).unwrap();
+        assert_eq!(game.get_living_player_count(), 2);
+        assert_eq!(game.get_living_player_ids(), vec![1, 2]);
+        game.run(0.1);
+        assert_eq!(game.get_living_player_count(), 2);
+
+        // conquer the only factory of player 2
+        let coord = game.get_player(2).unwrap().factories[0].pos.clone();
+        game.map.claim_tile(1, &coord, u32::MAX);
+        // the factory dies on this frame, the player on the next one
+        game.run(0.1);
+        assert_eq!(game.get_living_player_count(), 2);
+        game.run(0.1);
+        assert_eq!(game.get_living_player_count(), 1);
+        assert_eq!(game.get_living_player_ids(), vec![1]);
+        assert!(game.resign_game(2).is_err());
+    }
+
+    #[test]
+    fn first_blood_once_and_streak_escalates() {
+        let mut config = test_config();
+        config.probe_price = 0.0;
+        config.first_blood_bonus = 10.0;
+        config.kill_streak_bounty = 3.0;
+        let mut game = new_game(config);
+        let coords = [Coord::new(3, 10), Coord::new(2, 11), Coord::new(2, 9)];
+        for coord in coords.iter() {
+            game.create_turret(2, coord.x, coord.y).unwrap();
+        }
+        game.run(0.1);
+        let money = game.get_player(1).unwrap().get_money();
+
+        let mut rewards = Vec::new();
+        for coord in coords.iter() {
+            game.map.claim_tile(1, coord, u32::MAX);
+            let state = game.run(0.1).unwrap();
+            for event in state.events.iter() {
+                match event {
+                    GameEvent::FirstBlood { player_id, bonus } => {
+                        rewards.push(("first blood", *player_id, *bonus));
+                    }
+                    GameEvent::KillStreak {
+                        player_id, bounty, ..
+                    } => {
+                        rewards.push(("streak", *player_id, *bounty));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        assert_eq!(
+            rewards,
+            vec![
+                ("first blood", 1, 10.0),
+                ("streak", 1, 3.0),
+                ("streak", 1, 6.0)
+            ]
+        );
+        assert_eq!(game.get_player(1).unwrap().get_money(), money + 19.0);
+    }
+
+    #[test]
+    fn build_turret_errors() {
+        let mut config = test_config();
+        config.turret_price = 60.0;
+        let mut game = new_game(config);
+        assert!(is_invalid_tile(game.create_turret(2, -1, 10)));
+        assert_eq!(
+            game.create_turret(3, 3, 10),
+            Err(String::from("Invalid player (Are you dead ?)"))
+        );
+        assert_eq!(
+            game.create_turret(2, 2, 10),
+            Err(String::from("Tile already has a building"))
+        );
+        assert_eq!(
+            game.create_turret(2, 17, 10),
+            Err(String::from("Cannot build on tile"))
+        );
+        assert_eq!(game.create_turret(2, 3, 10), Ok(()));
+        assert_eq!(
+            game.create_turret(2, 2, 11),
+            Err(String::from("Not enough money (<60)"))
+        );
+    }
+
+    #[test]
+    fn acquire_tech_keeps_invalid_name_error() {
+        let mut game = new_game(test_config());
+        assert_eq!(
+            game.acquire_tech(1, "PROBE_ARMOR"),
+            Err(String::from("Invalid tech name: PROBE_ARMOR"))
+        );
+        assert_eq!(game.acquire_tech(1, "TURRET_DAMAGE"), Ok(()));
+    }
+
+    #[test]
+    fn global_probe_cap_throttles_then_resumes() {
+        let mut config = test_config();
+        config.max_probes_total = 2;
+        config.probe_lifespan = 4.0;
+        let mut game = new_game(config);
+        let mut probe_ids = HashSet::new();
+        let mut is_throttled = false;
+        for _ in 0..120 {
+            game.run(0.1);
+            let n_probes: usize = game.players.iter().map(|p| p.get_num_probes()).sum();
+            assert!(n_probes <= 2);
+            is_throttled |= game.players.iter().all(|p| {
+                p.factories
+                    .iter()
+                    .all(|f| matches!(f.get_policy(), FactoryPolicy::Throttled))
+            });
+            for player_id in [1, 2] {
+                probe_ids.extend(get_probe_ids(&game, player_id));
+            }
+        }
+        assert!(is_throttled);
+        // production resumed once the first probes expired
+        assert!(probe_ids.len() > 2);
+    }
+
+    #[test]
+    fn scrapping_last_factory_defeats_player() {
+        let mut game = new_game(test_config());
+        let factory_id = game.get_player(2).unwrap().factories[0].id;
+        assert_eq!(
+            game.scrap_factory(1, factory_id),
+            Err(String::from("Invalid factory"))
+        );
+        assert_eq!(
+            game.scrap_factory(2, 0),
+            Err(String::from("Invalid factory"))
+        );
+
+        assert_eq!(game.scrap_factory(2, factory_id), Ok(()));
+        assert!(game
+            .map
+            .get_tile(&Coord::new(2, 10))
+            .unwrap()
+            .building_id
+            .is_none());
+        game.run(0.1);
+        game.run(0.1);
+        assert!(game.get_player(2).is_none());
+        assert_eq!(game.get_result().unwrap().winner_id, Some(1));
+    }
+
+    #[test]
+    fn scrapped_turret_is_refunded_and_frees_tile() {
+        let mut config = test_config();
+        config.turret_price = 10.0;
+        config.turret_scrap_refund_rate = 0.5;
+        let mut game = new_game(config);
+        game.create_turret(2, 3, 10).unwrap();
+        let turret_id = game.get_player(2).unwrap().turrets[0].id;
+        assert_eq!(game.get_player(2).unwrap().get_money(), 90.0);
+        assert_eq!(
+            game.scrap_turret(1, turret_id),
+            Err(String::from("Invalid turret"))
+        );
+
+        assert_eq!(game.scrap_turret(2, turret_id), Ok(()));
+        assert_eq!(game.get_player(2).unwrap().get_money(), 95.0);
+        assert!(game.get_player(2).unwrap().turrets.is_empty());
+        assert!(game
+            .map
+            .get_tile(&Coord::new(3, 10))
+            .unwrap()
+            .building_id
+            .is_none());
+        assert_eq!(game.create_turret(2, 3, 10), Ok(()));
+    }
+
+    #[test]
+    fn stats_peaks_match_recorded_series() {
+        let mut game = new_game(test_config());
+        for _ in 0..60 {
+            game.run(0.1);
+        }
+        game.create_turret(2, 3, 10).unwrap();
+        for _ in 0..60 {
+            game.run(0.1);
+        }
+
+        let stats = game.get_players_stats();
+        for player_id in [1, 2] {
+            let stats = &stats[&player_id];
+            assert!(!stats.time.is_empty());
+            let peak_money = stats.money.iter().cloned().fold(0.0, f64::max);
+            assert_eq!(stats.peak_money, peak_money);
+            assert_eq!(
+                stats.peak_occupation,
+                *stats.occupation.iter().max().unwrap()
+            );
+            assert_eq!(stats.peak_probes, *stats.probes.iter().max().unwrap());
+            assert!(stats.peak_probes > 0);
+            assert!(stats.probes_produced as usize >= stats.peak_probes);
+            assert_eq!(stats.buildings_lost, 0);
+        }
+        // the initial factory isn't built by the player
+        assert_eq!(stats[&1].buildings_built, 0);
+        assert_eq!(stats[&2].buildings_built, 1);
+    }
+
+    #[test]
+    fn building_conquered_on_build_frame_is_destroyed() {
+        let mut game = new_game(test_config());
+        let coord = Coord::new(10, 10);
+        game.map.claim_tile(1, &coord, 1);
+        // the action resolves first, then the tile is conquered
+        game.apply_action(&Action::BuildTurret {
+            player_id: 1,
+            coord: coord.clone(),
+        })
+        .unwrap();
+        let turret_id = game.map.get_tile(&coord).unwrap().building_id.unwrap();
+        // conquered to zero, then claimed by the opponent
+        game.map.claim_tile(2, &coord, 1);
+        game.map.claim_tile(2, &coord, 1);
+        game.run(0.1).unwrap();
+
+        // the building isn't left on the opponent's tile
+        let tile = game.map.get_tile(&coord).unwrap();
+        assert!(tile.is_owned_by(2));
+        assert_eq!(tile.building_id, None);
+        let player = game.get_player(1).unwrap();
+        assert!(player.turrets.iter().all(|t| t.id != turret_id));
+        assert!(game.get_player(2).unwrap().turrets.is_empty());
+    }
+
+    #[test]
+    fn game_from_state_matches_state() {
+        let mut game = new_game(test_config());
+        for _ in 0..40 {
+            game.run(0.1);
+        }
+        game.create_turret(2, 3, 10).unwrap();
+        let state = game.get_complete_state();
+
+        let restored = Game::from_state(vec![1, 2], test_config(), state.clone()).unwrap();
+        assert_eq!(
+            bincode::serialize(&restored.get_complete_state()).unwrap(),
+            bincode::serialize(&state).unwrap()
+        );
+        assert_eq!(get_probe_ids(&restored, 1), get_probe_ids(&game, 1));
+
+        let result = Game::from_state(vec![1, 3], test_config(), state);
+        assert_eq!(
+            result.err(),
+            Some(String::from("Missing state of player 3"))
+        );
+    }
+
+    #[test]
+    fn events_are_truncated_per_step() {
+        let mut config = test_config();
+        config.max_events_per_frame = 1;
+        let mut game = new_game(config);
+        // two steps of the same run, each emitting two events
+        for ids in [[1, 2], [3, 4]] {
+            for id in ids {
+                game.state_handle
+                    .get_mut()
+                    .events
+                    .push(GameEvent::PlayerDefeated { player_id: id });
+            }
+            game.truncate_events();
+        }
+        let events = &game.state_handle.get().events;
+        assert_eq!(events.len(), 4);
+        assert!(matches!(
+            events[0],
+            GameEvent::PlayerDefeated { player_id: 1 }
+        ));
+        assert!(matches!(
+            events[1],
+            GameEvent::EventsTruncated { n_dropped: 1 }
+        ));
+        assert!(matches!(
+            events[2],
+            GameEvent::PlayerDefeated { player_id: 3 }
+        ));
+        assert!(matches!(
+            events[3],
+            GameEvent::EventsTruncated { n_dropped: 1 }
+        ));
+    }
+
+    #[test]
+    fn picket_guards_tile_until_killed() {
+        let mut config = test_config();
+        config.initial_n_probes = 1;
+        config.probe_picket_intensity_factor = 0.0;
+        let mut game = new_game(config);
+        let probe_id = get_probe_ids(&game, 1)[0];
+        game.picket_probes(1, vec![probe_id]).unwrap();
+        game.run(0.1);
+        let probe = get_probes(&game, 1).remove(0);
+        let coord = probe.pos.unwrap().as_coord();
+
+        let occupation = game.map.get_tile(&coord).unwrap().occupation;
+        game.map.claim_tile(2, &coord, 1);
+        assert_eq!(game.map.get_tile(&coord).unwrap().occupation, occupation);
+
+        // the picket is killed: the tile isn't guarded anymore
+        let player = game.players.iter_mut().find(|p| p.id == 1).unwrap();
+        player
+            .get_mut_probe_by_id(probe_id)
+            .unwrap()
+            .inflict_damage(u32::MAX);
+        game.run(0.1);
+        game.run(0.1);
+        assert!(get_probes(&game, 1).iter().all(|p| p.id != probe_id));
+        let occupation = game.map.get_tile(&coord).unwrap().occupation;
+        game.map.claim_tile(2, &coord, 1);
+        assert_eq!(
+            game.map.get_tile(&coord).unwrap().occupation,
+            occupation - 1
+        );
+    }
+
+    #[test]
+    fn middle_player_death_keeps_survivors() {
+        let mut config = test_config();
+        config.n_player = 3;
+        let mut game = Game::new(vec![1, 2, 3], config);
+        let pos = game.get_player(2).unwrap().factories[0].pos.clone();
+        game.map.claim_tile(1, &pos, u32::MAX);
+        game.run(0.1);
+        game.run(0.1);
+
+        let mut ids: Vec<u128> = game.players.iter().map(|p| p.id).collect();
+        ids.sort();
+        assert_eq!(ids, vec![1, 3]);
+        assert_eq!(game.dead_player_ids, vec![2]);
+        assert!(game.get_result().is_none());
+    }
+
+    #[test]
+    fn elimination_result_has_winner() {
+        let mut game = new_game(test_config());
+        game.run(0.1);
+        assert!(game.get_result().is_none());
+        game.resign_game(2).unwrap();
+        game.run(0.1);
+
+        let result = game.get_result().unwrap();
+        assert_eq!(result.winner_id, Some(1));
+        assert_eq!(result.rankings, vec![1, 2]);
+        assert_eq!(result.n_frames, 2);
+    }
+
+    #[test]
+    fn time_limit_result_ranks_by_score() {
+        let mut config = test_config();
+        config.time_limit = 0.3;
+        let mut game = new_game(config);
+        game.map.claim_tile(2, &Coord::new(10, 10), 5);
+        for _ in 0..3 {
+            assert!(game.get_result().is_none());
+            game.run(0.1);
+        }
+
+        let result = game.get_result().unwrap();
+        assert_eq!(result.winner_id, Some(2));
+        assert_eq!(result.rankings, vec![2, 1]);
+        assert!(result.duration >= 0.3);
+    }
+
+    #[test]
+    fn time_limit_result_with_tied_or_leading_scores() {
+        let mut config = test_config();
+        config.time_limit = 0.3;
+        config.score_weights = ScoreWeights {
+            occupation: 0.0,
+            money: 0.0,
+            buildings: 1.0,
+        };
+        for lead in [false, true] {
+            let mut game = new_game(config.clone());
+            if lead {
+                game.map.claim_tile(2, &Coord::new(10, 10), 5);
+                game.create_turret(2, 10, 10).unwrap();
+            }
+            let scores = game.get_scores();
+            assert_eq!(scores.len(), 2);
+            for _ in 0..3 {
+                game.run(0.1);
+            }
+
+            let result = game.get_result().unwrap();
+            match lead {
+                true => {
+                    assert_eq!(scores, vec![(2, 2.0), (1, 1.0)]);
+                    assert_eq!(result.winner_id, Some(2));
+                    assert_eq!(result.rankings, vec![2, 1]);
+                }
+                false => {
+                    assert_eq!(scores[0].1, scores[1].1);
+                    assert_eq!(result.winner_id, None);
+                    let mut rankings = result.rankings.clone();
+                    rankings.sort();
+                    assert_eq!(rankings, vec![1, 2]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn simultaneous_deaths_result_in_draw() {
+        let mut game = new_game(test_config());
+        for player_id in [1, 2] {
+            let pos = game.get_player(player_id).unwrap().factories[0].pos.clone();
+            let opponent_id = 3 - player_id;
+            game.map.claim_tile(opponent_id, &pos, u32::MAX);
+        }
+        game.run(0.1);
+        game.run(0.1);
+
+        let result = game.get_result().unwrap();
+        assert_eq!(result.winner_id, None);
+        let mut rankings = result.rankings.clone();
+        rankings.sort();
+        assert_eq!(rankings, vec![1, 2]);
+    }
+
+    #[test]
+    fn players_order_does_not_affect_run() {
+        let mut config = test_config();
+        // overlapping expansions: contested claims on each frame
+        config.factory_expansion_size = 8;
+        config.factory_max_probe = 0;
+        let mut game = new_game(config);
+        let mut reversed = game.fork();
+        reversed.players.reverse();
+        for _ in 0..60 {
+            game.run(0.1);
+            reversed.run(0.1);
+        }
+        assert_eq!(
+            bincode::serialize(&game.get_complete_state()).unwrap(),
+            bincode::serialize(&reversed.get_complete_state()).unwrap()
+        );
+    }
+
+    #[test]
+    fn resume_payload_contains_player_state() {
+        let mut config = test_config();
+        config.vision_radius = 4.0;
+        let mut game = new_game(config);
+        game.create_turret(1, 17, 10).unwrap();
+        game.acquire_tech(1, "PROBE_HP").unwrap();
+        game.run(0.1);
+
+        let payload = game.get_resume_payload(1).unwrap();
+        assert_eq!(payload.player_id, 1);
+        assert_eq!(payload.schema_version, STATE_SCHEMA_VERSION);
+        assert_eq!(payload.frame, 1);
+        let player = game.get_player(1).unwrap();
+        let state = payload.state.players.iter().find(|p| p.id == 1).unwrap();
+        assert_eq!(state.money, Some(player.get_money()));
+        assert_eq!(state.techs, vec![Techs::PROBE_HP]);
+        assert_eq!(state.factories.len(), 1);
+        assert_eq!(state.turrets.len(), 1);
+        assert_eq!(payload.effective_stats.probe_hp, 4);
+        assert_eq!(
+            bincode::serialize(&payload.config).unwrap(),
+            bincode::serialize(&game.config).unwrap()
+        );
+
+        // the map is restricted to the player's vision
+        let visible = game.get_state_for_player(1).unwrap();
+        let n_tiles = |state: &GameState| state.map.as_ref().unwrap().tiles.len();
+        assert_eq!(n_tiles(&payload.state), n_tiles(&visible));
+        assert!(n_tiles(&payload.state) < n_tiles(&game.get_complete_state()));
+        assert!(payload
+            .state
+            .players
+            .iter()
+            .all(|p| p.id == 1 || p.factories.is_empty()));
+    }
+
+    #[test]
+    fn fixed_steps_are_independent_of_dt() {
+        let mut config = test_config();
+        config.tick_rate = 0.1;
+        // no probes: the run is deterministic
+        config.factory_max_probe = 0;
+        let mut game = new_game(config);
+        let mut halves = game.fork();
+        for _ in 0..30 {
+            game.run(0.1);
+            halves.run(0.05);
+            halves.run(0.05);
+        }
+        assert_eq!(game.frame, 30);
+        assert_eq!(halves.frame, 30);
+        assert_eq!(
+            bincode::serialize(&game.get_complete_state()).unwrap(),
+            bincode::serialize(&halves.get_complete_state()).unwrap()
+        );
+    }
+
+    #[test]
+    fn paused_factory_expands_then_stops_producing() {
+        let mut game = new_game(test_config());
+        let factory_id = game.get_player(1).unwrap().factories[0].id;
+        game.set_factory_policy(1, factory_id, "Pause").unwrap();
+        game.run(0.1);
+        let policy = game.get_player(1).unwrap().factories[0].get_policy();
+        assert!(matches!(policy, FactoryPolicy::Expand));
+
+        // the expansion completes, then the factory stays paused
+        for _ in 0..50 {
+            game.run(0.1);
+        }
+        let policy = game.get_player(1).unwrap().factories[0].get_policy();
+        assert!(matches!(policy, FactoryPolicy::Paused));
+        assert!(get_probes(&game, 1).is_empty());
+        assert!(!get_probes(&game, 2).is_empty());
+
+        game.set_factory_policy(1, factory_id, "Produce").unwrap();
+        for _ in 0..20 {
+            game.run(0.1);
+        }
+        assert!(!get_probes(&game, 1).is_empty());
+    }
+
+    #[test]
+    fn building_footprints_are_their_tile() {
+        let mut game = new_game(test_config());
+        game.create_turret(2, 3, 10).unwrap();
+        let player = game.get_player(2).unwrap();
+        let factory_id = player.factories[0].id;
+        let turret_id = player.turrets[0].id;
+
+        assert_eq!(
+            game.get_building_footprint(factory_id),
+            Ok(vec![Coord::new(2, 10)])
+        );
+        assert_eq!(
+            game.get_building_footprint(turret_id),
+            Ok(vec![Coord::new(3, 10)])
+        );
+        assert_eq!(
+            game.get_building_footprint(0),
+            Err(String::from("Invalid building"))
+        );
+    }
+
+    #[test]
+    fn hold_and_picket_require_own_probes() {
+        let mut config = test_config();
+        config.initial_n_probes = 2;
+        let mut game = new_game(config);
+        let ids = get_probe_ids(&game, 1);
+        let error = Err(String::from("Invalid probes (Are they dead ?)"));
+
+        // unknown and opponent's probes
+        let foreign_ids = get_probe_ids(&game, 2);
+        assert_eq!(game.hold_probes(1, vec![0]), error);
+        assert_eq!(game.hold_probes(1, foreign_ids.clone()), error);
+        assert_eq!(game.picket_probes(1, vec![0]), error);
+        assert_eq!(game.picket_probes(1, foreign_ids), error);
+
+        // partial success
+        assert_eq!(game.hold_probes(1, vec![ids[0], 0]), Ok(()));
+        assert_eq!(game.picket_probes(1, vec![0, ids[1]]), Ok(()));
+        assert_eq!(game.hold_probes(1, Vec::new()), Ok(()));
+    }
+
+    #[test]
+    fn symmetric_spawns_are_mirrored() {
+        let mut config = test_config();
+        config.symmetric_spawns = true;
+        let game = new_game(config.clone());
+        let (dx, dy) = (config.dim.x - 1, config.dim.y - 1);
+
+        // point-symmetry for 2 players
+        let positions = game.get_start_positions(2);
+        let mirrored = positions[1].clone();
+        assert_eq!(positions.len(), 2);
+        assert_eq!(
+            positions[1],
+            Coord::new(dx - positions[0].x, dy - positions[0].y)
+        );
+
+        // symmetry across both axes for 4 players
+        let positions = game.get_start_positions(4);
+        let (x, y) = (positions[0].x, positions[0].y);
+        assert_eq!(
+            positions,
+            vec![
+                Coord::new(x, y),
+                Coord::new(dx - x, y),
+                Coord::new(dx - x, dy - y),
+                Coord::new(x, dy - y),
+            ]
+        );
+        let distinct: HashSet<(i32, i32)> = positions.iter().map(|c| (c.x, c.y)).collect();
+        assert_eq!(distinct.len(), 4);
+
+        // other numbers of players: circle
+        let circle = new_game(test_config()).get_start_positions(3);
+        assert_eq!(game.get_start_positions(3), circle);
+
+        // mirrored position on an obstacle: circle
+        config.obstacles = vec![mirrored];
+        let game = new_game(config.clone());
+        config.symmetric_spawns = false;
+        let circle = new_game(config).get_start_positions(2);
+        assert_eq!(game.get_start_positions(2), circle);
+    }
+
+    #[test]
+    fn overrides_only_apply_to_their_player() {
+        let mut config = test_config();
+        config.per_player_overrides.insert(
+            1,
+            PartialConfig {
+                initial_money: Some(2.0 * config.initial_money),
+                income_rate: None,
+                probe_speed: None,
+            },
+        );
+        let game = new_game(config.clone());
+        let state = game.get_complete_state();
+        let get_money = |id: u128| {
+            state
+                .players
+                .iter()
+                .find(|p| p.id == id)
+                .unwrap()
+                .money
+                .unwrap()
+        };
+        assert_eq!(get_money(1), 2.0 * config.initial_money);
+        assert_eq!(get_money(2), config.initial_money);
+    }
+
+    #[test]
+    fn tech_acquired_event_on_purchase_frame() {
+        let mut game = new_game(test_config());
+        game.run(0.1);
+
+        let is_tech_event = |state: &Option<GameState>| {
+            state.as_ref().map_or(false, |s| {
+                s.events.iter().any(|e| {
+                    matches!(
+                        e,
+                        GameEvent::TechAcquired {
+                            player_id: 1,
+                            tech: Techs::PROBE_HP
+                        }
+                    )
+                })
+            })
+        };
+        game.acquire_tech(1, "PROBE_HP").unwrap();
+        assert!(is_tech_event(&game.run(0.1)));
+        assert!(!is_tech_event(&game.run(0.1)));
+    }
+
+    #[test]
+    fn restored_snapshot_runs_as_original() {
+        let mut config = test_config();
+        // no probe production: the ids of new probes are random
+        config.initial_n_probes = 3;
+        config.max_probes_total = 6;
+        config.deprecate_rate = 0.5;
+        config.occupation_spread_rate = 0.2;
+        config.tick_rate = 0.1;
+        let mut game = new_game(config);
+        game.create_turret(1, 17, 10).unwrap();
+        for _ in 0..30 {
+            game.run(0.15);
+        }
+
+        let mut restored = Game::restore(&game.snapshot().unwrap()).unwrap();
+        for _ in 0..100 {
+            let state = game.run(0.15);
+            let restored_state = restored.run(0.15);
+            assert_eq!(
+                bincode::serialize(&state).unwrap(),
+                bincode::serialize(&restored_state).unwrap()
+            );
+        }
+        assert_eq!(
+            bincode::serialize(&game.get_complete_state()).unwrap(),
+            bincode::serialize(&restored.get_complete_state()).unwrap()
+        );
+        assert_eq!(restored.frame, game.frame);
+        assert_eq!(restored.time, game.time);
+    }
+
+    #[test]
+    fn invalid_snapshot_is_rejected() {
+        let game = new_game(test_config());
+        let bytes = game.snapshot().unwrap();
+        assert!(Game::restore(&bytes[..bytes.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn abandoned_player_stops_spending_but_keeps_occupation() {
+        let mut game = new_game(test_config());
+        let get_occupation =
+            |game: &Game, id: u128| game.map.get_player_occupation(game.get_player(id).unwrap());
+        let occupation = get_occupation(&game, 1);
+        assert_eq!(game.abandon_player(1), Ok(()));
+        assert!(game.abandon_player(1).is_err());
+        for _ in 0..50 {
+            game.run(0.1);
+        }
+        assert_eq!(game.get_player(1).unwrap().get_money(), 100.0);
+        assert!(game.get_player(2).unwrap().get_money() < 100.0);
+        assert!(get_occupation(&game, 1) >= occupation);
+        assert_eq!(game.get_player(1).unwrap().factories.len(), 1);
+
+        assert_eq!(game.resume_player(1), Ok(()));
+        assert!(game.resume_player(1).is_err());
+        for _ in 0..50 {
+            game.run(0.1);
+        }
+        assert!(game.get_player(1).unwrap().get_money() < 100.0);
+    }
+
+    #[test]
+    fn start_positions_are_distinct_and_in_bounds() {
+        for (dim, n_players) in [(Coord::new(100, 20), 10), (Coord::new(12, 12), 40)] {
+            let mut config = test_config();
+            config.dim = dim.clone();
+            config.obstacles = vec![Coord::new(1, 1)];
+            let game = new_game(config);
+            let positions = game.get_start_positions(n_players);
+            assert_eq!(positions.len(), n_players as usize);
+            let distinct: HashSet<(i32, i32)> = positions.iter().map(|c| (c.x, c.y)).collect();
+            assert_eq!(distinct.len(), n_players as usize);
+            for pos in positions.iter() {
+                let tile = game.map.get_tile(pos).unwrap();
+                assert!(!tile.is_obstacle);
+            }
+        }
+    }
+
+    #[test]
+    fn reexpanded_factory_reclaims_deprecated_tiles() {
+        let mut config = test_config();
+        // no probe can be bought: only the factory claims tiles
+        config.initial_money = 0.0;
+        let mut game = new_game(config.clone());
+        for _ in 0..20 {
+            game.run(0.1);
+        }
+        let factory = &game.get_player(1).unwrap().factories[0];
+        let (factory_id, pos) = (factory.id, factory.pos.clone());
+
+        // the expansion claims each diamond of radius 1..=size in turn
+        let size = config.factory_expansion_size;
+        let coords: Vec<Coord> = geometry::square(&pos, size)
+            .into_iter()
+            .filter(|c| game.map.get_tile(c).is_some())
+            .collect();
+        for coord in coords.iter() {
+            game.map.claim_tile(2, coord, 1);
+        }
+        let get_occupations = |game: &Game| -> Vec<u32> {
+            coords
+                .iter()
+                .map(|c| game.map.get_tile(c).unwrap().occupation)
+                .collect()
+        };
+        let deprecated = get_occupations(&game);
+
+        assert_eq!(game.reexpand_factory(1, factory_id), Ok(()));
+        assert!(game.reexpand_factory(1, factory_id).is_err());
+        for _ in 0..20 {
+            game.run(0.1);
+        }
+        let occupations = get_occupations(&game);
+        for (i, coord) in coords.iter().enumerate() {
+            let n_claims = (1..=size)
+                .filter(|step| geometry::square(&pos, *step).contains(coord))
+                .count() as u32;
+            let expected = u32::min(deprecated[i] + 2 * n_claims, config.max_occupation);
+            assert_eq!(occupations[i], expected);
+        }
+        for coord in coords.iter() {
+            assert!(game.map.get_tile(coord).unwrap().is_owned_by(1));
+        }
+    }
+
+    /// Run the game for `n_frames` frames of 0.1 sec, in a pool of
+    /// `n_threads` threads \
+    /// Return the serialized state of each frame
+    fn run_in_pool(game: &mut Game, n_threads: usize, n_frames: usize) -> Vec<Vec<u8>> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(n_threads)
+            .build()
+            .unwrap();
+        pool.install(|| {
+            (0..n_frames)
+                .map(|_| bincode::serialize(&game.run(0.1)).unwrap())
+                .collect()
+        })
+    }
+
+    #[test]
+    fn parallel_run_is_independent_of_threads() {
+        let mut config = test_config();
+        config.parallel = true;
+        config.n_player = 4;
+        // no probe production: the ids of new probes are random
+        config.initial_n_probes = 3;
+        config.max_probes_total = 12;
+        config.deprecate_rate = 0.5;
+        config.occupation_spread_rate = 0.2;
+        let mut game = Game::new(vec![1, 2, 3, 4], config);
+        for id in [1, 3] {
+            let pos = game.get_player(id).unwrap().factories[0].pos.clone();
+            game.create_turret(id, pos.x, pos.y + 1).unwrap();
+        }
+        let grid = game.get_occupation_grid();
+        let mut forked = game.fork();
+
+        let states = run_in_pool(&mut game, 1, 150);
+        assert_eq!(states, run_in_pool(&mut forked, 4, 150));
+        assert_eq!(
+            bincode::serialize(&game.get_complete_state()).unwrap(),
+            bincode::serialize(&forked.get_complete_state()).unwrap()
+        );
+        // the probes farmed
+        assert_ne!(game.get_occupation_grid(), grid);
+    }
+
+    #[test]
+    fn parallel_production_respects_max_probes_total() {
+        let mut config = test_config();
+        config.parallel = true;
+        config.n_player = 4;
+        config.max_probes_total = 5;
+        let mut game = Game::new(vec![1, 2, 3, 4], config);
+        for _ in 0..100 {
+            game.run(0.1);
+            assert!(game.count_probes() <= 5);
+        }
+        assert_eq!(game.count_probes(), 5);
+    }
+
+    /// Return a game of 8 players with 50 factories each
+    fn create_large_game(parallel: bool) -> Game {
+        let mut config = test_config();
+        config.dim = Coord::new(100, 100);
+        config.n_player = 8;
+        config.initial_money = 1e6;
+        config.parallel = parallel;
+        let mut game = Game::new((1..=8).collect(), config);
+        for player in game.players.iter_mut() {
+            let pos = player.factories[0].pos.clone();
+            let coords: Vec<Coord> = geometry::iter_vortex(&pos)
+                .filter(|c| {
+                    game.map
+                        .get_tile(c)
+                        .map_or(false, |t| t.building_id.is_none())
+                })
+                .take(49)
+                .collect();
+            for coord in coords {
+                game.map
+                    .claim_tile(player.id, &coord, game.config.max_occupation);
+                player.create_factory(coord, &mut game.map, &game.config);
+            }
+        }
+        game
+    }
+
+    /// Benchmark of the parallel run, to run with:
+    /// `cargo test --release bench_parallel_run -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_parallel_run() {
+        for parallel in [false, true] {
+            let mut game = create_large_game(parallel);
+            let start = std::time::Instant::now();
+            for _ in 0..200 {
+                game.run(0.1);
+            }
+            println!(
+                "parallel: {}, probes: {}, duration: {:?}",
+                parallel,
+                game.count_probes(),
+                start.elapsed()
+            );
+        }
     }
 }
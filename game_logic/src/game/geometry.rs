@@ -1,4 +1,4 @@
-use super::core::Coord;
+use super::core::{Coord, Point};
 
 /// Return the coordinates from `distance` of the origin,
 /// without the origin, in a square shape:
@@ -61,6 +61,30 @@ pub fn square(origin: &Coord, distance: u32) -> Vec<Coord> {
     return coords;
 }
 
+/// Return the coordinates from `distance` of the origin,
+/// without the origin, in a box shape (diagonals included):
+/// ```
+/// distance: 1 & 2
+///                 * * * * *
+///     * * *       * * * * *
+///     *   *       * *   * *
+///     * * *       * * * * *
+///                 * * * * *
+/// ```
+pub fn box_without_origin(origin: &Coord, distance: u32) -> Vec<Coord> {
+    let mut coords: Vec<Coord> = Vec::new();
+    let distance = distance as i32;
+    for y in -distance..=distance {
+        for x in -distance..=distance {
+            if x != 0 || y != 0 {
+                coords.push(Coord::new(origin.x + x, origin.y + y));
+            }
+        }
+    }
+
+    return coords;
+}
+
 /// Return the coordinates at `distance` of the origin,
 /// in a square shape:
 /// ```
@@ -97,6 +121,56 @@ pub fn ring(origin: &Coord, distance: u32) -> Vec<Coord> {
     return coords;
 }
 
+/// Return the coordinates at `distance` of the origin (see `ring`),
+/// only keeping the ones in the bounds of a map of dimension `dim`
+pub fn ring_clamped(origin: &Coord, distance: u32, dim: &Coord) -> Vec<Coord> {
+    let mut coords: Vec<Coord> = Vec::new();
+    let distance = distance as i32;
+    let is_in_bounds = |x: i32| x >= 0 && x < dim.x;
+
+    let min_y = i32::max(origin.y - distance, 0);
+    let max_y = i32::min(origin.y + distance, dim.y - 1);
+    for y in min_y..=max_y {
+        let dx = distance - (y - origin.y).abs();
+        if is_in_bounds(origin.x - dx) {
+            coords.push(Coord::new(origin.x - dx, y));
+        }
+        if dx != 0 && is_in_bounds(origin.x + dx) {
+            coords.push(Coord::new(origin.x + dx, y));
+        }
+    }
+
+    return coords;
+}
+
+/// Return the coordinates whose center is within `radius`
+/// (euclidean distance) of the origin's center, in a disk shape:
+/// ```
+/// radius: 1.0 & 1.5 & 2.5
+///                                 * * *
+///                   * * *       * * * * *
+///       *           * * *       * * * * *
+///     * * *         * * *       * * * * *
+///       *                         * * *
+/// ```
+pub fn disk(origin: &Coord, radius: f64) -> Vec<Coord> {
+    let mut coords: Vec<Coord> = Vec::new();
+    if radius < 0.0 {
+        return coords;
+    }
+    let distance = radius.floor() as i32;
+    let radius_2 = radius.powi(2);
+    for y in -distance..=distance {
+        for x in -distance..=distance {
+            if (x * x + y * y) as f64 <= radius_2 {
+                coords.push(Coord::new(origin.x + x, origin.y + y));
+            }
+        }
+    }
+
+    return coords;
+}
+
 /// Return an iterator that yield the coordinates around
 /// the origin (first coordinate yielded) from the successive
 /// rings (with distance 1, 2, 3, ...), never stops. \
@@ -111,11 +185,22 @@ pub fn iter_vortex<'a>(origin: &'a Coord) -> IterVortex {
     IterVortex::new(origin)
 }
 
+/// Return an iterator that yield the coordinates around
+/// the origin (see `iter_vortex`), only keeping the ones
+/// in the bounds of a map of dimension `dim` \
+/// Stops once all the coordinates of the map have been yielded
+pub fn iter_vortex_clamped<'a>(origin: &'a Coord, dim: &Coord) -> IterVortex<'a> {
+    IterVortex::with_bounds(origin, dim)
+}
+
 pub struct IterVortex<'a> {
     origin: &'a Coord,
     distance: u32,
     ring: Vec<Coord>,
     idx: usize,
+    /// Dimension of the map and distance of the farthest
+    /// coordinate of the map, if the iterator is bounded
+    bounds: Option<(Coord, u32)>,
 }
 
 impl<'a> IterVortex<'a> {
@@ -125,6 +210,21 @@ impl<'a> IterVortex<'a> {
             distance: 0,
             ring: vec![origin.clone()],
             idx: 0,
+            bounds: None,
+        }
+    }
+
+    /// Create a bounded iterator, that only yields the coordinates
+    /// in the bounds of a map of dimension `dim`
+    pub fn with_bounds(origin: &'a Coord, dim: &Coord) -> Self {
+        let dx = i32::max((origin.x).abs(), (dim.x - 1 - origin.x).abs());
+        let dy = i32::max((origin.y).abs(), (dim.y - 1 - origin.y).abs());
+        IterVortex {
+            origin: origin,
+            distance: 0,
+            ring: ring_clamped(origin, 0, dim),
+            idx: 0,
+            bounds: Some((dim.clone(), (dx + dy) as u32)),
         }
     }
 }
@@ -132,17 +232,72 @@ impl<'a> IterVortex<'a> {
 impl<'a> Iterator for IterVortex<'a> {
     type Item = Coord;
     fn next(&mut self) -> Option<Self::Item> {
-        match self.ring.get(self.idx) {
-            Some(coord) => {
+        loop {
+            if let Some(coord) = self.ring.get(self.idx) {
                 self.idx += 1;
                 return Some(coord.clone());
             }
-            None => {
-                self.distance += 1;
-                self.ring = ring(self.origin, self.distance);
-                self.idx = 1;
-                return Some(self.ring[0].clone());
-            }
+            self.distance += 1;
+            self.idx = 0;
+            self.ring = match &self.bounds {
+                None => ring(self.origin, self.distance),
+                Some((_, max_distance)) if self.distance > *max_distance => {
+                    return None;
+                }
+                Some((dim, _)) => ring_clamped(self.origin, self.distance, dim),
+            };
+        }
+    }
+}
+
+/// Return the distance between the point and the segment `[a, b]`
+pub fn distance_to_segment(point: &Point, a: &Point, b: &Point) -> f64 {
+    let dx = b.x - a.x;
+    let dy = b.y - a.y;
+    let norm = dx * dx + dy * dy;
+    // position of the projection of the point on the segment
+    let t = match norm == 0.0 {
+        true => 0.0,
+        false => (((point.x - a.x) * dx + (point.y - a.y) * dy) / norm).clamp(0.0, 1.0),
+    };
+    Point::new(a.x + t * dx - point.x, a.y + t * dy - point.y).norm()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disk_counts() {
+        let origin = Coord::new(5, 5);
+        assert_eq!(disk(&origin, 0.0).len(), 1);
+        assert_eq!(disk(&origin, 1.0).len(), 5);
+        assert_eq!(disk(&origin, 2.5).len(), 21);
+        assert!(disk(&origin, -1.0).is_empty());
+
+        // exactly the coords within the radius
+        let coords = disk(&origin, 2.5);
+        for coord in square(&origin, 5) {
+            let dx = (coord.x - origin.x) as f64;
+            let dy = (coord.y - origin.y) as f64;
+            let is_in = dx * dx + dy * dy <= 2.5 * 2.5;
+            assert_eq!(coords.contains(&coord), is_in);
+        }
+    }
+
+    #[test]
+    fn bounded_vortex_yields_whole_map_once() {
+        let dim = Coord::new(4, 3);
+        for (x, y) in [(0, 0), (1, 1), (3, 2)] {
+            let origin = Coord::new(x, y);
+            let mut coords: Vec<(i32, i32)> = IterVortex::with_bounds(&origin, &dim)
+                .map(|c| (c.x, c.y))
+                .collect();
+            assert_eq!(coords[0], (x, y));
+            coords.sort();
+            let expected: Vec<(i32, i32)> =
+                (0..4).flat_map(|x| (0..3).map(move |y| (x, y))).collect();
+            assert_eq!(coords, expected);
         }
     }
 }
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use super::{Player, Point};
+
+/// Probe registered in the grid
+#[derive(Clone, Debug)]
+pub struct GridProbe {
+    pub player_id: u128,
+    pub probe_id: u128,
+    /// Index of the player in the players (sorted by id)
+    pub player_idx: usize,
+    /// Index of the factory of the probe in the player's factories
+    pub factory_idx: usize,
+    /// Index of the probe in the factory's probes
+    /// (see `Player::get_mut_probe_at`)
+    pub probe_idx: usize,
+}
+
+/// Spatial index of the probes of all players,
+/// bucketed by square cells of `cell_size` coordinates
+///
+/// Note: built at the start of the frame (see `ProbeGrid::build`),
+/// the probes may then move by up to `margin` coordinates,
+/// and the probes created during the frame are not indexed
+pub struct ProbeGrid {
+    cell_size: f64,
+    /// Maximal distance a probe can travel during the frame
+    margin: f64,
+    cells: HashMap<(i32, i32), Vec<GridProbe>>,
+}
+
+impl ProbeGrid {
+    pub fn new(cell_size: f64, margin: f64) -> Self {
+        ProbeGrid {
+            cell_size: cell_size.max(1.0),
+            margin: margin,
+            cells: HashMap::new(),
+        }
+    }
+
+    /// Create a grid indexing all the probes of the players \
+    /// Note: the players must be sorted by id
+    pub fn build(players: &Vec<Player>, cell_size: f64, margin: f64) -> Self {
+        let mut grid = ProbeGrid::new(cell_size, margin);
+        for (player_idx, player) in players.iter().enumerate() {
+            for (factory_idx, factory) in player.factories.iter().enumerate() {
+                for (probe_idx, probe) in factory.iter_probes().enumerate() {
+                    grid.insert(
+                        GridProbe {
+                            player_id: player.id,
+                            probe_id: probe.id,
+                            player_idx: player_idx,
+                            factory_idx: factory_idx,
+                            probe_idx: probe_idx,
+                        },
+                        &probe.pos,
+                    );
+                }
+            }
+        }
+        grid
+    }
+
+    /// Return the cell containing the point `(x, y)`
+    fn get_cell(&self, x: f64, y: f64) -> (i32, i32) {
+        (
+            (x / self.cell_size).floor() as i32,
+            (y / self.cell_size).floor() as i32,
+        )
+    }
+
+    pub fn insert(&mut self, probe: GridProbe, pos: &Point) {
+        let cell = self.get_cell(pos.x, pos.y);
+        self.cells.entry(cell).or_insert(Vec::new()).push(probe);
+    }
+
+    /// Return the probes that can be within `radius` of `pos`
+    /// (taking the margin into account), sorted in the iteration
+    /// order of the players (by id) and of their probes
+    pub fn query(&self, pos: &Point, radius: f64) -> Vec<&GridProbe> {
+        let radius = radius + self.margin;
+        let (x_min, y_min) = self.get_cell(pos.x - radius, pos.y - radius);
+        let (x_max, y_max) = self.get_cell(pos.x + radius, pos.y + radius);
+
+        let mut probes = Vec::new();
+        for x in x_min..=x_max {
+            for y in y_min..=y_max {
+                if let Some(cell) = self.cells.get(&(x, y)) {
+                    probes.extend(cell.iter());
+                }
+            }
+        }
+        probes.sort_by_key(|p| (p.player_idx, p.factory_idx, p.probe_idx));
+        probes
+    }
+}
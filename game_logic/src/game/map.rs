@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use super::{
     core, core::Coord, geometry, player::Player, probe::Probe, random, state_vec_insert, Delayer,
@@ -6,19 +6,65 @@ use super::{
 };
 
 use log;
+use serde::{Deserialize, Serialize};
 
+/// Which tiles are adjacent to a tile, when checking
+/// if a tile is connected to the territory of a player
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Adjacency {
+    /// The 8 surrounding tiles (diagonals included)
+    Moore,
+    /// The 4 orthogonal tiles (see `geometry::square_without_origin`)
+    VonNeumann,
+}
+
+impl Adjacency {
+    /// Create an instance from a string \
+    /// Return an error in case the `string` is invalid
+    pub fn from_string(string: &str) -> Result<Self, String> {
+        match string {
+            "Moore" => Ok(Adjacency::Moore),
+            "VonNeumann" => Ok(Adjacency::VonNeumann),
+            _ => Err(format!("Invalid adjacency: {}", string)),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 struct MapConfig {
     pub dim: Coord,
     pub max_occupation: u32,
     pub deprecate_rate: f64,
+    pub spread_rate: f64,
+    pub attack_prefer_buildings: bool,
+    pub income_occupation_min: u32,
+    pub attack_spread: bool,
+    pub farm_prefer_own: bool,
+    pub adjacency: Adjacency,
+    pub farm_capture_max_enemy_occupation: u32,
+    pub picket_intensity_factor: f64,
+    pub building_upkeep_occupation: u32,
+    /// If the threatened buildings should be recorded
+    /// (see `Map::take_threatened_buildings`)
+    pub record_threats: bool,
+    pub vision_radius: f64,
+    pub avoid_turrets: bool,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MapState {
     pub tiles: Vec<TileState>,
     /// store state of dead factories
     /// Internal to rust implementation
     dead_building: HashMap<u128, Vec<u128>>,
+    /// store buildings whose at-risk status changed
+    /// `{player id: [(building id, is at risk)]}` \
+    /// Internal to rust implementation
+    building_risk: HashMap<u128, Vec<(u128, bool)>>,
+    /// store buildings destroyed by a player
+    /// `[(killer id, building owner id)]` \
+    /// Internal to rust implementation
+    building_kills: Vec<(u128, u128)>,
 }
 
 impl State for MapState {
@@ -28,10 +74,12 @@ impl State for MapState {
         MapState {
             tiles: Vec::new(),
             dead_building: HashMap::new(),
+            building_risk: HashMap::new(),
+            building_kills: Vec::new(),
         }
     }
 
-    fn merge(&mut self, state: Self) {
+    fn merge(&mut self, mut state: Self) {
         for tile in state.tiles.iter() {
             state_vec_insert(&mut self.tiles, tile.clone());
         }
@@ -43,6 +91,16 @@ impl State for MapState {
                 self.dead_building.insert(owner, buildings);
             }
         }
+
+        self.building_kills.append(&mut state.building_kills);
+
+        for (owner, mut risks) in state.building_risk {
+            if let Some(current) = self.building_risk.get_mut(&owner) {
+                current.append(&mut risks);
+            } else {
+                self.building_risk.insert(owner, risks);
+            }
+        }
     }
 }
 
@@ -51,8 +109,19 @@ impl MapState {
     pub fn get_dead_building(&self) -> &HashMap<u128, Vec<u128>> {
         &self.dead_building
     }
+
+    /// Return `building_kills` attribute
+    pub fn get_building_kills(&self) -> &Vec<(u128, u128)> {
+        &self.building_kills
+    }
+
+    /// Return `building_risk` attribute
+    pub fn get_building_risk(&self) -> &HashMap<u128, Vec<(u128, bool)>> {
+        &self.building_risk
+    }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Map {
     config: MapConfig,
     pub state_handle: StateHandler<MapState>,
@@ -61,7 +130,31 @@ pub struct Map {
     /// -> fast iteration trough map buidings \
     /// `{player id: {building_id: building_coord}}`
     buildings: HashMap<u128, HashMap<u128, Coord>>,
+    /// Buildings whose tile is below `building_upkeep_occupation`
+    /// (see `Map::upkeep_buildings`)
+    buildings_at_risk: HashSet<u128>,
+    /// Coordinates of the buildings whose tile has been
+    /// contested by an opponent since the last check of their owner
+    /// `{player id: [building coord]}`
+    threatened_buildings: HashMap<u128, Vec<Coord>>,
+    /// Coordinates of the tiles guarded by a picket probe
+    picket_coords: Vec<Coord>,
+    /// (owner id, coordinate, scope) of all turrets,
+    /// only set if `avoid_turrets` (see `Map::set_turret_scopes`)
+    turret_scopes: Vec<(u128, Coord, f64)>,
+    /// Total occupation of the owned tiles of each player,
+    /// kept up to date on each tile change (see `update_occupations`)
+    /// `{player id: occupation}`
+    occupations: HashMap<u128, u32>,
+    /// Coordinates of the bonus tiles (see `GameConfig.bonus_tiles`)
+    bonus_coords: Vec<Coord>,
     delayer_deprecate: Delayer,
+    /// Number of attack targets given, used to spread
+    /// the attacking probes (see `MapConfig.attack_spread`)
+    attack_round_robin: usize,
+    /// Generator of all the random decisions of the game
+    /// (farm/attack targets, deprecation, spreading)
+    rng: random::GameRng,
 }
 
 impl Map {
@@ -76,19 +169,87 @@ impl Map {
             }
             tiles.push(col);
         }
+        for coord in config.obstacles.iter() {
+            if let Some(tile) = tiles
+                .get_mut(coord.x as usize)
+                .and_then(|col| col.get_mut(coord.y as usize))
+            {
+                tile.is_obstacle = true;
+            }
+        }
+        for (coord, bonus) in config.bonus_tiles.iter() {
+            if let Some(tile) = tiles
+                .get_mut(coord.x as usize)
+                .and_then(|col| col.get_mut(coord.y as usize))
+            {
+                tile.bonus = *bonus;
+            }
+        }
         return Map {
             config: MapConfig {
                 dim: dim,
                 max_occupation: config.max_occupation,
                 deprecate_rate: config.deprecate_rate,
+                spread_rate: config.occupation_spread_rate,
+                attack_prefer_buildings: config.probe_attack_prefer_buildings,
+                income_occupation_min: config.income_occupation_min,
+                attack_spread: config.probe_attack_spread,
+                farm_prefer_own: config.probe_farm_prefer_own,
+                adjacency: config.adjacency.clone(),
+                farm_capture_max_enemy_occupation: config.farm_capture_max_enemy_occupation,
+                picket_intensity_factor: config.probe_picket_intensity_factor,
+                building_upkeep_occupation: config.building_upkeep_occupation,
+                record_threats: config.probe_reinforce_distance > 0.0,
+                vision_radius: config.vision_radius,
+                avoid_turrets: config.probe_avoid_turrets,
             },
             state_handle: StateHandler::new(&()),
             tiles: tiles,
             buildings: HashMap::new(),
+            buildings_at_risk: HashSet::new(),
+            threatened_buildings: HashMap::new(),
+            picket_coords: Vec::new(),
+            turret_scopes: Vec::new(),
+            occupations: HashMap::new(),
+            bonus_coords: config
+                .bonus_tiles
+                .iter()
+                .map(|(coord, _)| coord.clone())
+                .collect(),
             delayer_deprecate: Delayer::new(1.0),
+            attack_round_robin: 0,
+            rng: random::new_rng(),
         };
     }
 
+    /// Restore the tiles from their (complete) states \
+    /// Return an error in case a state has no or an invalid coordinate
+    pub fn restore_tiles(&mut self, states: &Vec<TileState>) -> Result<(), String> {
+        for state in states.iter() {
+            let coord = match &state.coord {
+                Some(coord) => coord,
+                None => {
+                    return Err(String::from("Missing tile coordinate"));
+                }
+            };
+            let tile = match self.get_mut_tile(coord) {
+                Some(tile) => tile,
+                None => {
+                    return Err(format!("Invalid tile coordinate ({:?})", coord));
+                }
+            };
+            let before = (tile.owner_id, tile.occupation);
+            tile.id = state.id;
+            if let Some(occupation) = state.occupation {
+                tile.occupation = occupation;
+            }
+            tile.owner_id = state.owner_id;
+            let after = (tile.owner_id, tile.occupation);
+            Map::update_occupations(&mut self.occupations, before, after);
+        }
+        Ok(())
+    }
+
     /// Return a reference to tile if it exists
     pub fn get_tile(&self, coord: &Coord) -> Option<&Tile> {
         if !coord.is_positive() {
@@ -107,12 +268,106 @@ impl Map {
             .get_mut(coord.y as usize)
     }
 
+    /// Update the total occupation of the players for a tile
+    /// going from `before` to `after` (as `(owner id, occupation)`)
+    ///
+    /// Note: take the occupations as argument (and not self) to allow
+    /// borrowing the tiles mutably at the same time
+    fn update_occupations(
+        occupations: &mut HashMap<u128, u32>,
+        before: (Option<u128>, u32),
+        after: (Option<u128>, u32),
+    ) {
+        if before == after {
+            return;
+        }
+        if let (Some(owner_id), occupation) = before {
+            if let Some(total) = occupations.get_mut(&owner_id) {
+                *total = total.saturating_sub(occupation);
+            }
+        }
+        if let (Some(owner_id), occupation) = after {
+            *occupations.entry(owner_id).or_insert(0) += occupation;
+        }
+    }
+
+    /// Return the coordinate of the building, if it exists
+    pub fn get_building_coord(&self, building_id: u128) -> Option<Coord> {
+        self.buildings
+            .values()
+            .find_map(|buildings| buildings.get(&building_id))
+            .cloned()
+    }
+
     /// Return the total occupation of all owned tiles of player
     pub fn get_player_occupation(&self, player: &Player) -> u32 {
+        match self.occupations.get(&player.id) {
+            Some(occupation) => *occupation,
+            None => 0,
+        }
+    }
+
+    /// Return the visibility of the tiles for the player, indexed as the tiles,
+    /// the tiles within `vision_radius` of an owned tile are visible
+    /// (if the vision radius is 0, all tiles are visible)
+    pub fn compute_visibility(&self, player_id: u128) -> Vec<Vec<bool>> {
+        let dim = &self.config.dim;
+        if self.config.vision_radius == 0.0 {
+            return vec![vec![true; dim.y as usize]; dim.x as usize];
+        }
+        let mut visibility = vec![vec![false; dim.y as usize]; dim.x as usize];
+        let radius = self.config.vision_radius;
+        let span = radius as i32;
+        for col in self.tiles.iter() {
+            for tile in col.iter() {
+                if !tile.is_owned_by(player_id) {
+                    continue;
+                }
+                for dx in -span..=span {
+                    for dy in -span..=span {
+                        if ((dx * dx + dy * dy) as f64) > radius * radius {
+                            continue;
+                        }
+                        let x = tile.coord.x + dx;
+                        let y = tile.coord.y + dy;
+                        if x < 0 || y < 0 || x >= dim.x || y >= dim.y {
+                            continue;
+                        }
+                        visibility[x as usize][y as usize] = true;
+                    }
+                }
+            }
+        }
+        visibility
+    }
+
+    /// Return the total bonus of the owned bonus tiles of player
+    /// (with a non-zero occupation)
+    pub fn get_player_bonus_income(&self, player: &Player) -> f64 {
+        let mut bonus = 0.0;
+        for coord in self.bonus_coords.iter() {
+            if let Some(tile) = self.get_tile(coord) {
+                if tile.is_owned_by(player.id) && tile.occupation > 0 {
+                    bonus += tile.bonus;
+                }
+            }
+        }
+        bonus
+    }
+
+    /// Return the total occupation of all owned tiles of player
+    /// that generate income, i.e. with an occupation of at least
+    /// `income_occupation_min`
+    pub fn get_player_income_occupation(&self, player: &Player) -> u32 {
+        if self.config.income_occupation_min == 0 {
+            return self.get_player_occupation(player);
+        }
         let mut occupation = 0;
         for col in self.tiles.iter() {
             for tile in col.iter() {
-                if tile.is_owned_by(player.id) {
+                if tile.is_owned_by(player.id)
+                    && tile.occupation >= self.config.income_occupation_min
+                {
                     occupation += tile.occupation;
                 }
             }
@@ -126,6 +381,8 @@ impl Map {
         let mut state = MapState {
             tiles: Vec::with_capacity(n_tiles as usize),
             dead_building: HashMap::new(),
+            building_risk: HashMap::new(),
+            building_kills: Vec::new(),
         };
         for col in self.tiles.iter() {
             for tile in col.iter() {
@@ -135,6 +392,14 @@ impl Map {
         state
     }
 
+    /// Return the occupation of all tiles, indexed as `grid[x][y]`
+    pub fn get_occupation_grid(&self) -> Vec<Vec<u32>> {
+        self.tiles
+            .iter()
+            .map(|col| col.iter().map(|tile| tile.occupation).collect())
+            .collect()
+    }
+
     /// Return the tiles that are neighbour of the `tile` \
     /// Neighbours as defined by `geometry::square_without_origin(tile.coord, distance)`
     pub fn get_neighbour_tiles(&self, tile: &Tile, distance: u32) -> Vec<&Tile> {
@@ -150,8 +415,34 @@ impl Map {
         return neighbours;
     }
 
+    /// Return if one of the tiles adjacent to `tile` is owned by the player
+    /// (see `GameConfig.adjacency`)
+    fn is_adjacent_to_player(&self, tile: &Tile, player_id: u128) -> bool {
+        let coords = match self.config.adjacency {
+            Adjacency::Moore => geometry::box_without_origin(&tile.coord, 1),
+            Adjacency::VonNeumann => geometry::square_without_origin(&tile.coord, 1),
+        };
+        coords.iter().any(|coord| match self.get_tile(coord) {
+            Some(neighbour) => neighbour.is_owned_by(player_id),
+            None => false,
+        })
+    }
+
+    /// Return if there is an opponent tile of the player
+    /// within `distance` of the coordinate
+    pub fn is_close_to_opponent(&self, player_id: u128, coord: &Coord, distance: u32) -> bool {
+        geometry::square_without_origin(coord, distance)
+            .iter()
+            .filter_map(|c| self.get_tile(c))
+            .any(|tile| tile.is_owned_by_opponent_of(player_id))
+    }
+
     /// Return if the given tile can be farmed by a probe of `player`
     fn is_tile_valid_farm_target(&self, tile: &Tile, player: &Player) -> bool {
+        if tile.is_obstacle {
+            return false;
+        }
+
         // check if tile occupation full
         if tile.occupation == self.config.max_occupation {
             return false;
@@ -159,27 +450,77 @@ impl Map {
 
         if !tile.is_owned_by(player.id) {
             // check if tile occupied by an other player
-            if tile.occupation > 3 {
+            if tile.occupation > self.config.farm_capture_max_enemy_occupation {
                 return false;
             } else {
                 // assert that tile is not isolated
-                let neighbours = self.get_neighbour_tiles(tile, 1);
-                for neighbour in neighbours.iter() {
-                    if neighbour.is_owned_by(player.id) {
-                        return true;
-                    }
-                }
-                return false;
+                return self.is_adjacent_to_player(tile, player.id);
             }
         }
         return true;
     }
 
+    /// Return the coordinates of the tiles the player can build on
+    /// (see `Tile::can_build`)
+    pub fn get_buildable_tiles(&self, player: &Player) -> Vec<Coord> {
+        self.tiles
+            .iter()
+            .flat_map(|c| c.iter())
+            .filter(|t| t.can_build(player))
+            .map(|t| t.coord.clone())
+            .collect()
+    }
+
+    /// Return the coordinates of the tiles the player can expand to,
+    /// i.e. the tiles not owned by the player that are valid farm targets
+    /// (next to the player's territory, see `is_tile_valid_farm_target`)
+    pub fn get_expansion_frontier(&self, player: &Player) -> Vec<Coord> {
+        self.tiles
+            .iter()
+            .flat_map(|c| c.iter())
+            .filter(|t| !t.is_owned_by(player.id) && self.is_tile_valid_farm_target(t, player))
+            .map(|t| t.coord.clone())
+            .collect()
+    }
+
+    /// Return if the given tile can be farmed by a probe of `player`,
+    /// and (if `safe_from` is given) can be reached from there
+    /// without crossing the scope of an opponent turret
+    fn is_tile_safe_farm_target(
+        &self,
+        tile: &Tile,
+        player: &Player,
+        safe_from: Option<&Coord>,
+    ) -> bool {
+        self.is_tile_valid_farm_target(tile, player)
+            && safe_from.map_or(true, |from| self.is_path_safe(player.id, from, &tile.coord))
+    }
+
     /// Return a target to farm (own or unoccupied tile)
-    /// in the surroundings of the probe if possible
-    fn get_close_probe_farm_target(&self, player: &Player, coord: &Coord) -> Option<Coord> {
+    /// in the surroundings of the probe if possible \
+    /// If `farm_prefer_own`, own tiles are targeted first \
+    /// If `safe_from` is given, only the safe targets are considered
+    /// (see `is_tile_safe_farm_target`)
+    fn get_close_probe_farm_target(
+        &mut self,
+        player: &Player,
+        coord: &Coord,
+        safe_from: Option<&Coord>,
+    ) -> Option<Coord> {
         let mut coords = geometry::square_without_origin(coord, 3);
-        random::shuffle_vec(&mut coords);
+        random::shuffle_vec(&mut coords, &mut self.rng);
+
+        if self.config.farm_prefer_own {
+            for coord in coords.iter() {
+                if let Some(tile) = self.get_tile(coord) {
+                    if tile.is_owned_by(player.id)
+                        && self.is_tile_safe_farm_target(tile, player, safe_from)
+                    {
+                        return Some(tile.coord.clone());
+                    }
+                }
+            }
+        }
 
         for coord in coords.iter() {
             // get tile on coord
@@ -190,66 +531,174 @@ impl Map {
                 }
             };
 
-            if self.is_tile_valid_farm_target(tile, player) {
+            if self.is_tile_safe_farm_target(tile, player, safe_from) {
                 return Some(tile.coord.clone());
             }
         }
         None
     }
 
+    /// Return a target to farm (own or unoccupied tile)
+    /// within `radius` of the center of the region, if any \
+    /// If `safe_from` is given, only the safe targets are considered
+    /// (see `is_tile_safe_farm_target`)
+    pub fn get_region_probe_farm_target(
+        &mut self,
+        player: &Player,
+        center: &Coord,
+        radius: u32,
+        safe_from: Option<&Coord>,
+    ) -> Option<Coord> {
+        let mut coords = geometry::square(center, radius);
+        random::shuffle_vec(&mut coords, &mut self.rng);
+
+        for coord in coords.iter() {
+            if let Some(tile) = self.get_tile(coord) {
+                if self.is_tile_safe_farm_target(tile, player, safe_from) {
+                    return Some(tile.coord.clone());
+                }
+            }
+        }
+        None
+    }
+
     /// Return a target for the probe to farm (own or unoccupied tile)
-    /// either in surroundings of the probe or next to a player's factory
-    pub fn get_probe_farm_target(&self, player: &Player, probe: &Probe) -> Option<Coord> {
-        // first look next to the probe itself
-        if let Some(target) = self.get_close_probe_farm_target(player, &probe.get_coord()) {
+    /// either in surroundings of the probe or next to a player's factory \
+    /// If `avoid_turrets`, the safe targets are preferred
+    /// (see `is_tile_safe_farm_target`)
+    pub fn get_probe_farm_target(&mut self, player: &Player, probe: &Probe) -> Option<Coord> {
+        if self.config.avoid_turrets && !self.turret_scopes.is_empty() {
+            let from = probe.get_coord();
+            if let Some(target) = self.search_probe_farm_target(player, probe, Some(&from)) {
+                return Some(target);
+            }
+        }
+        self.search_probe_farm_target(player, probe, None)
+    }
+
+    /// Return a target for the probe to farm (own or unoccupied tile),
+    /// only the safe ones if `safe_from` is given (see `get_probe_farm_target`)
+    fn search_probe_farm_target(
+        &mut self,
+        player: &Player,
+        probe: &Probe,
+        safe_from: Option<&Coord>,
+    ) -> Option<Coord> {
+        // first look in the farm region of the probe (if any)
+        if let Some((center, radius)) = probe.get_farm_region() {
+            if let Some(target) =
+                self.get_region_probe_farm_target(player, center, *radius, safe_from)
+            {
+                return Some(target);
+            }
+        }
+
+        // then look next to the probe itself
+        if let Some(target) =
+            self.get_close_probe_farm_target(player, &probe.get_coord(), safe_from)
+        {
             return Some(target);
         };
 
         // then look next to the factories
         // NOTE: do not use player.factories as it is empty
         // see factories.drain in Player.run
-        if let Some(buildings) = self.buildings.get(&player.id) {
-            for coord in buildings.values() {
-                if let Some(target) = self.get_close_probe_farm_target(player, &coord) {
-                    return Some(target);
+        // sorted, so that the target doesn't depend on the hashing
+        // of the buildings (which differs in a restored game)
+        let mut coords: Vec<Coord> = match self.buildings.get(&player.id) {
+            Some(buildings) => buildings.values().cloned().collect(),
+            None => Vec::new(),
+        };
+        coords.sort_by_key(|c| (c.x, c.y));
+        for coord in coords.iter() {
+            if let Some(target) = self.get_close_probe_farm_target(player, coord, safe_from) {
+                return Some(target);
+            }
+        }
+
+        None
+    }
+
+    /// Return the coordinate of the closest building
+    /// of the opponents of the player, if any \
+    /// The ties are broken by coordinate, independently of the
+    /// iteration order of the buildings
+    fn get_closest_opponent_building(&self, player_id: u128, coord: &Coord) -> Option<Coord> {
+        let mut closest: Option<(&Coord, (i32, i32, i32))> = None;
+        for (owner_id, buildings) in self.buildings.iter() {
+            if *owner_id == player_id {
+                continue;
+            }
+            for building_coord in buildings.values() {
+                let dx = building_coord.x - coord.x;
+                let dy = building_coord.y - coord.y;
+                let dist = (dx * dx + dy * dy, building_coord.x, building_coord.y);
+                if closest.map_or(true, |(_, d)| dist < d) {
+                    closest = Some((building_coord, dist));
                 }
             }
         }
+        closest.map(|(coord, _)| coord.clone())
+    }
 
+    /// Return the coordinate of the closest tile owned by the player, if any
+    pub fn get_closest_owned_tile(&self, player_id: u128, coord: &Coord) -> Option<Coord> {
+        for coord in geometry::IterVortex::with_bounds(coord, &self.config.dim) {
+            if let Some(tile) = self.get_tile(&coord) {
+                if tile.is_owned_by(player_id) {
+                    return Some(coord);
+                }
+            }
+        }
         None
     }
 
     /// Return a target for the probe to attack
-    pub fn get_probe_attack_target(&self, player_id: u128, probe: &Probe) -> Option<Coord> {
-        let mut target_tile: Option<&Tile> = None;
-
-        let mut idx = 0;
+    pub fn get_probe_attack_target(&mut self, player_id: u128, probe: &Probe) -> Option<Coord> {
+        if self.config.attack_prefer_buildings {
+            if let Some(target) = self.get_closest_opponent_building(player_id, &probe.get_coord())
+            {
+                return Some(target);
+            }
+        }
 
-        let max_idx = i32::max(1000, 4 * self.config.dim.x * self.config.dim.y);
+        let mut target_tile: Option<&Tile> = None;
 
-        for coord in geometry::iter_vortex(&probe.get_coord()) {
+        // the iterator stops once the whole map has been visited
+        let origin = probe.get_coord();
+        for coord in geometry::IterVortex::with_bounds(&origin, &self.config.dim) {
             if let Some(tile) = self.get_tile(&coord) {
                 if tile.is_owned_by_opponent_of(player_id) {
                     target_tile = Some(tile);
                     break;
                 }
             }
-            idx += 1;
-            if idx == max_idx {
+        }
+        let target_tile = match target_tile {
+            Some(tile) => tile,
+            None => {
                 log::warn!("Didn't found attack target");
                 return None;
             }
-        }
+        };
         // choose tile in region
-        let mut tiles = self.get_neighbour_tiles(&target_tile.unwrap(), 2);
-        tiles.push(target_tile.unwrap());
-        random::shuffle_vec(&mut tiles);
-        for tile in tiles {
-            if tile.is_owned_by_opponent_of(player_id) {
-                return Some(tile.coord.clone());
-            }
+        let mut tiles = self.get_neighbour_tiles(target_tile, 2);
+        tiles.push(target_tile);
+
+        let mut coords: Vec<Coord> = tiles
+            .iter()
+            .filter(|t| t.is_owned_by_opponent_of(player_id))
+            .map(|t| t.coord.clone())
+            .collect();
+
+        if self.config.attack_spread {
+            let coord = coords.get(self.attack_round_robin % coords.len()).cloned();
+            self.attack_round_robin += 1;
+            return coord;
         }
-        None
+
+        random::shuffle_vec(&mut coords, &mut self.rng);
+        coords.into_iter().next()
     }
 
     /// For each tile, if it meets the conditions,
@@ -266,19 +715,70 @@ impl Map {
             let mut prob = (occ - half) / (self.config.max_occupation as f64 - half);
             prob *= self.config.deprecate_rate;
 
-            if random::random() <= prob {
+            if random::random(&mut self.rng) <= prob {
+                let before = (tile.owner_id, tile.occupation);
                 tile.decr_occupation(2);
+                let after = (tile.owner_id, tile.occupation);
+                Map::update_occupations(&mut self.occupations, before, after);
 
-                let state = TileState::new(&tile);
+                let mut state = TileState::new(&tile);
+                state.change = Some(TileChange::Decayed);
                 state_vec_insert(&mut self.state_handle.get_mut().tiles, state);
             }
         }
     }
 
+    /// For each tile with maximum occupation, increase the occupation
+    /// of its neighbours (unowned or owned by the same player)
+    /// with a certain probability.
+    fn spread_tiles(&mut self) {
+        let mut candidates: Vec<(u128, Coord)> = Vec::new();
+        for tile in self.tiles.iter().flat_map(|c| c.iter()) {
+            if tile.occupation < self.config.max_occupation {
+                continue;
+            }
+            let owner_id = match tile.owner_id {
+                Some(owner_id) => owner_id,
+                None => {
+                    continue;
+                }
+            };
+            for neighbour in self.get_neighbour_tiles(tile, 1) {
+                if neighbour.is_owned_by_opponent_of(owner_id)
+                    || neighbour.occupation == self.config.max_occupation
+                {
+                    continue;
+                }
+                candidates.push((owner_id, neighbour.coord.clone()));
+            }
+        }
+        let mut claims: Vec<(u128, Coord)> = Vec::new();
+        for candidate in candidates {
+            if random::random(&mut self.rng) <= self.config.spread_rate {
+                claims.push(candidate);
+            }
+        }
+
+        for (owner_id, coord) in claims.iter() {
+            // make sure not to spread on a tile taken by an
+            // other player during the spreading
+            if let Some(tile) = self.get_tile(coord) {
+                if tile.is_owned_by_opponent_of(*owner_id) {
+                    continue;
+                }
+            }
+            self.claim_tile(*owner_id, coord, 1);
+        }
+    }
+
     /// Set a building id, this method
-    /// should be called each time a new building is created
+    /// should be called each time a new building is created \
+    /// Fail if the tile doesn't exist or already has a building
     pub fn set_new_building(&mut self, coord: &Coord, id: u128) -> Result<(), ()> {
         if let Some(tile) = self.get_mut_tile(&coord) {
+            if tile.building_id.is_some() {
+                return Err(());
+            }
             tile.building_id = Some(id);
 
             // add building id as attribute
@@ -296,11 +796,51 @@ impl Map {
         Err(())
     }
 
+    /// Remove the building of the player from the map,
+    /// this method should be called when a building is
+    /// removed by its owner (as it doesn't die through `claim_tile`)
+    pub fn remove_building(&mut self, player_id: u128, building_id: u128) {
+        let coord = match self.buildings.get_mut(&player_id) {
+            Some(buildings) => buildings.remove(&building_id),
+            None => None,
+        };
+        self.buildings_at_risk.remove(&building_id);
+        if let Some(coord) = coord {
+            if let Some(tile) = self.get_mut_tile(&coord) {
+                if tile.building_id == Some(building_id) {
+                    tile.building_id = None;
+                }
+            }
+        }
+    }
+
+    /// Remove all the buildings of the player from the map,
+    /// this method should be called when a player dies
+    /// (as its buildings don't die through `claim_tile`)
+    pub fn remove_player_buildings(&mut self, player_id: u128) {
+        self.threatened_buildings.remove(&player_id);
+        let buildings = match self.buildings.remove(&player_id) {
+            Some(buildings) => buildings,
+            None => {
+                return;
+            }
+        };
+        for (id, coord) in buildings.iter() {
+            self.buildings_at_risk.remove(id);
+            if let Some(tile) = self.get_mut_tile(coord) {
+                if tile.building_id == Some(*id) {
+                    tile.building_id = None;
+                }
+            }
+        }
+    }
+
     /// Claim the tile at the coordinate of the probe
     /// with the given intensity \
     /// Store the tile state, potential building death in current state \
-    /// Return if it could be done
+    /// Return if it could be done (obstacles can't be claimed)
     pub fn claim_tile(&mut self, player_id: u128, coord: &Coord, intensity: u32) -> bool {
+        let picket_factor = self.config.picket_intensity_factor;
         let tile = self.get_mut_tile(coord);
         let tile = match tile {
             None => {
@@ -308,18 +848,42 @@ impl Map {
             }
             Some(tile) => tile,
         };
+        if tile.is_obstacle {
+            return false;
+        }
+
+        // reduce the intensity of the opponents on a guarded tile
+        let mut intensity = intensity;
+        if tile.picket_id.is_some()
+            && tile.picket_id == tile.owner_id
+            && !tile.is_owned_by(player_id)
+        {
+            intensity = (intensity as f64 * picket_factor) as u32;
+            if intensity == 0 {
+                return true;
+            }
+        }
 
         let mut deaths: Option<(u128, u128)> = None;
+        let mut threat: Option<u128> = None;
+        let mut change: Option<TileChange> = None;
+        let previous_owner_id = tile.owner_id;
+        let before = (tile.owner_id, tile.occupation);
         match tile.owner_id {
             None => {
                 tile.set_owner(player_id);
                 tile.incr_occupation(intensity);
+                change = Some(TileChange::Claimed);
             }
             Some(owner_id) => {
                 if owner_id == player_id {
                     tile.incr_occupation(intensity);
                 } else {
                     tile.decr_occupation(intensity);
+                    change = Some(TileChange::Contested);
+                    if tile.building_id.is_some() && tile.occupation > 0 {
+                        threat = Some(owner_id);
+                    }
                     if tile.occupation == 0 {
                         // notify building death
                         if let Some(building_id) = tile.building_id {
@@ -327,52 +891,199 @@ impl Map {
                         }
                         tile.owner_id = None;
                         tile.building_id = None;
+                        change = Some(TileChange::Lost);
                     }
                 }
             }
         }
-        let state = TileState::new(&tile);
+        let after = (tile.owner_id, tile.occupation);
+        let mut state = TileState::new(&tile);
+        if change.is_some() {
+            state.change = change;
+            state.previous_owner_id = previous_owner_id;
+        }
+        Map::update_occupations(&mut self.occupations, before, after);
         state_vec_insert(&mut self.state_handle.get_mut().tiles, state);
 
         // add building death to current state
         if let Some((owner, building)) = deaths {
-            // remove building id from instance attribute
-            if let Some(buildings) = self.buildings.get_mut(&owner) {
-                buildings.remove(&building);
-            }
+            self.notify_building_death(owner, building);
+            self.state_handle
+                .get_mut()
+                .building_kills
+                .push((player_id, owner));
+        }
 
-            if let Some(ids) = self.state_handle.get_mut().dead_building.get_mut(&owner) {
-                ids.push(building);
-            } else {
-                self.state_handle
-                    .get_mut()
-                    .dead_building
-                    .insert(owner, vec![building]);
+        if let Some(owner) = threat {
+            if self.config.record_threats {
+                let coords = self.threatened_buildings.entry(owner).or_insert(Vec::new());
+                if !coords.contains(coord) {
+                    coords.push(coord.clone());
+                }
             }
         }
 
         true
     }
 
+    /// Set the tiles guarded by picket probes (see `ProbePolicy::Picket`),
+    /// `pickets` are the (player id, coordinate) of all picket probes \
+    /// The previous pickets are discarded
+    pub fn set_pickets(&mut self, pickets: Vec<(u128, Coord)>) {
+        for coord in self.picket_coords.drain(..).collect::<Vec<Coord>>() {
+            if let Some(tile) = self.get_mut_tile(&coord) {
+                tile.picket_id = None;
+            }
+        }
+        for (player_id, coord) in pickets {
+            if let Some(tile) = self.get_mut_tile(&coord) {
+                tile.picket_id = Some(player_id);
+                self.picket_coords.push(coord);
+            }
+        }
+    }
+
+    /// Set the scopes of the turrets avoided by the farming probes,
+    /// `turrets` are the (owner id, coordinate, scope) of all turrets \
+    /// The previous scopes are discarded
+    pub fn set_turret_scopes(&mut self, turrets: Vec<(u128, Coord, f64)>) {
+        self.turret_scopes = turrets;
+    }
+
+    /// Return if the straight path between the coordinates doesn't
+    /// cross the scope of a turret of an opponent of the player
+    fn is_path_safe(&self, player_id: u128, from: &Coord, to: &Coord) -> bool {
+        let from = from.as_point();
+        let to = to.as_point();
+        self.turret_scopes.iter().all(|(owner_id, coord, scope)| {
+            *owner_id == player_id
+                || geometry::distance_to_segment(&coord.as_point(), &from, &to) > *scope
+        })
+    }
+
+    /// Return the coordinates of the buildings of the player
+    /// that have been contested since the last call
+    pub fn take_threatened_buildings(&mut self, player_id: u128) -> Vec<Coord> {
+        self.threatened_buildings
+            .remove(&player_id)
+            .unwrap_or(Vec::new())
+    }
+
+    /// Remove the building from the instance attributes
+    /// and store its death in current state \
+    /// Note: the building id must already be removed from its tile
+    fn notify_building_death(&mut self, owner: u128, building: u128) {
+        if let Some(buildings) = self.buildings.get_mut(&owner) {
+            buildings.remove(&building);
+        }
+        self.buildings_at_risk.remove(&building);
+
+        if let Some(ids) = self.state_handle.get_mut().dead_building.get_mut(&owner) {
+            ids.push(building);
+        } else {
+            self.state_handle
+                .get_mut()
+                .dead_building
+                .insert(owner, vec![building]);
+        }
+    }
+
+    /// Store the change of at-risk status of the building in current state
+    fn notify_building_risk(&mut self, owner: u128, building: u128, is_at_risk: bool) {
+        let state = self.state_handle.get_mut();
+        if let Some(risks) = state.building_risk.get_mut(&owner) {
+            risks.push((building, is_at_risk));
+        } else {
+            state
+                .building_risk
+                .insert(owner, vec![(building, is_at_risk)]);
+        }
+    }
+
+    /// Check that the tile of each building has at least
+    /// `building_upkeep_occupation` occupation \
+    /// A building below the threshold is first flagged at-risk,
+    /// if it still is at the next check, it dies
+    fn upkeep_buildings(&mut self) {
+        let mut buildings: Vec<(u128, u128, Coord)> = Vec::new();
+        for (owner, owned) in self.buildings.iter() {
+            for (id, coord) in owned.iter() {
+                buildings.push((*owner, *id, coord.clone()));
+            }
+        }
+
+        for (owner, id, coord) in buildings {
+            let occupation = match self.get_tile(&coord) {
+                Some(tile) => tile.occupation,
+                None => {
+                    continue;
+                }
+            };
+            let is_at_risk = self.buildings_at_risk.contains(&id);
+
+            if occupation >= self.config.building_upkeep_occupation {
+                if is_at_risk {
+                    self.buildings_at_risk.remove(&id);
+                    self.notify_building_risk(owner, id, false);
+                }
+            } else if is_at_risk {
+                if let Some(tile) = self.get_mut_tile(&coord) {
+                    tile.building_id = None;
+                }
+                self.notify_building_death(owner, id);
+            } else {
+                self.buildings_at_risk.insert(id);
+                self.notify_building_risk(owner, id, true);
+            }
+        }
+    }
+
     /// run the map
     pub fn run(&mut self, dt: f64) {
         if self.delayer_deprecate.wait(dt) {
             self.deprecate_tiles();
+            if self.config.spread_rate > 0.0 {
+                self.spread_tiles();
+            }
+            if self.config.building_upkeep_occupation > 0 {
+                self.upkeep_buildings();
+            }
         }
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 struct TileConfig {
     max_occupation: u32,
     building_occupation_min: u32,
 }
 
-#[derive(Clone, Debug)]
+/// Kind of change that occured on a tile during the frame
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum TileChange {
+    /// The tile got a new owner
+    Claimed,
+    /// The tile lost its owner (occupation dropped to 0)
+    Lost,
+    /// The occupation was decreased by an opponent of the owner
+    Contested,
+    /// The occupation was decreased by deprecation
+    Decayed,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TileState {
     pub id: u128,
     pub coord: Option<Coord>,
     pub occupation: Option<u32>,
     pub owner_id: Option<u128>,
+    pub change: Option<TileChange>,
+    /// Owner of the tile before the change (if any)
+    pub previous_owner_id: Option<u128>,
+    /// Only specified in the complete state, for obstacles
+    pub is_obstacle: Option<bool>,
+    /// Only specified in the complete state, for bonus tiles
+    pub bonus: Option<f64>,
 }
 
 impl Identifiable for TileState {
@@ -390,6 +1101,10 @@ impl State for TileState {
             coord: None, // only specify coord on map creation
             occupation: Some(_metadata.occupation),
             owner_id: _metadata.owner_id,
+            change: None,
+            previous_owner_id: None,
+            is_obstacle: None,
+            bonus: None,
         }
     }
 
@@ -403,9 +1118,17 @@ impl State for TileState {
         if let Some(owner_id) = state.owner_id {
             self.owner_id = Some(owner_id);
         }
+        if let Some(change) = state.change {
+            self.change = Some(change);
+        }
+        // keep the first previous owner of the frame
+        if self.previous_owner_id.is_none() {
+            self.previous_owner_id = state.previous_owner_id;
+        }
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Tile {
     pub id: u128,
     config: TileConfig,
@@ -414,6 +1137,15 @@ pub struct Tile {
     pub owner_id: Option<u128>,
     /// may be id of: Factory, Turret
     pub building_id: Option<u128>,
+    /// Id of the player whose picket probe guards the tile
+    /// (see `Map::set_pickets`)
+    picket_id: Option<u128>,
+    /// If the tile is an obstacle, that can't be claimed nor
+    /// built on (see `GameConfig.obstacles`)
+    pub is_obstacle: bool,
+    /// Income earned by the owner on each income tick,
+    /// if 0: not a bonus tile (see `GameConfig.bonus_tiles`)
+    pub bonus: f64,
 }
 
 impl Tile {
@@ -428,6 +1160,9 @@ impl Tile {
             occupation: 0,
             owner_id: None,
             building_id: None,
+            picket_id: None,
+            is_obstacle: false,
+            bonus: 0.0,
         };
     }
 
@@ -438,12 +1173,17 @@ impl Tile {
             coord: Some(self.coord.clone()),
             occupation: Some(self.occupation),
             owner_id: self.owner_id,
+            change: None,
+            previous_owner_id: None,
+            is_obstacle: self.is_obstacle.then(|| true),
+            bonus: (self.bonus != 0.0).then(|| self.bonus),
         }
     }
 
     /// Return if the given player can build on tile
     pub fn can_build(&self, player: &Player) -> bool {
-        self.building_id.is_none()
+        !self.is_obstacle
+            && self.building_id.is_none()
             && self.is_owned_by(player.id)
             && self.occupation >= self.config.building_occupation_min
     }
@@ -471,16 +1211,442 @@ impl Tile {
 
     /// Increment tile occupation by `value`
     pub fn incr_occupation(&mut self, value: u32) {
-        self.occupation = u32::min(self.occupation + value, self.config.max_occupation);
+        // saturate -> a large value can't overflow before capping
+        self.occupation = u32::min(
+            self.occupation.saturating_add(value),
+            self.config.max_occupation,
+        );
     }
 
     /// Decrement tile occupation by `value`
     pub fn decr_occupation(&mut self, value: u32) {
-        // don't use the max way -> negative value don't exists on unsigned
-        if self.occupation >= value {
-            self.occupation -= value;
-        } else {
-            self.occupation = 0;
+        // saturate -> negative value don't exists on unsigned
+        self.occupation = self.occupation.saturating_sub(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::tests::test_config;
+    use rand::SeedableRng;
+
+    fn get_tile(map: &Map, x: i32, y: i32) -> &Tile {
+        map.get_tile(&Coord::new(x, y)).unwrap()
+    }
+
+    /// Return a probe of a new player (id: 1) at (x, y)
+    fn create_probe(config: &GameConfig, x: i32, y: i32) -> Probe {
+        let player = Player::new(1, config);
+        Probe::new(config, &player, Coord::new(x, y).as_point())
+    }
+
+    /// Flush the map state, return the state of the tile at (x, y)
+    fn flush_tile_state(map: &mut Map, x: i32, y: i32) -> Option<TileState> {
+        let id = get_tile(map, x, y).id;
+        map.state_handle
+            .flush(&())?
+            .tiles
+            .into_iter()
+            .find(|tile| tile.id == id)
+    }
+
+    #[test]
+    fn maxed_tiles_spread_to_neighbours() {
+        let mut config = test_config();
+        config.occupation_spread_rate = 1.0;
+        let mut map = Map::new(&config);
+        map.claim_tile(1, &Coord::new(5, 5), config.max_occupation);
+        map.claim_tile(2, &Coord::new(6, 5), 3);
+        map.run(1.0);
+
+        for (x, y) in [(4, 5), (5, 4), (5, 6)] {
+            let tile = get_tile(&map, x, y);
+            assert!(tile.is_owned_by(1));
+            assert_eq!(tile.occupation, 1);
+        }
+        // opponent tiles are left untouched
+        let tile = get_tile(&map, 6, 5);
+        assert!(tile.is_owned_by(2));
+        assert_eq!(tile.occupation, 3);
+        // only maxed tiles spread
+        assert_eq!(get_tile(&map, 7, 5).owner_id, None);
+    }
+
+    #[test]
+    fn no_spread_without_spread_rate() {
+        let config = test_config();
+        let mut map = Map::new(&config);
+        map.claim_tile(1, &Coord::new(5, 5), config.max_occupation);
+        for _ in 0..10 {
+            map.run(1.0);
         }
+        assert_eq!(get_tile(&map, 4, 5).owner_id, None);
+        assert_eq!(map.get_occupation_grid()[5][5], config.max_occupation);
+    }
+
+    #[test]
+    fn tile_changes_of_owner_flip() {
+        let config = test_config();
+        let mut map = Map::new(&config);
+        map.claim_tile(2, &Coord::new(5, 5), 2);
+        let state = flush_tile_state(&mut map, 5, 5).unwrap();
+        assert!(matches!(state.change, Some(TileChange::Claimed)));
+        assert_eq!(state.previous_owner_id, None);
+
+        map.claim_tile(1, &Coord::new(5, 5), 1);
+        let state = flush_tile_state(&mut map, 5, 5).unwrap();
+        assert!(matches!(state.change, Some(TileChange::Contested)));
+        assert_eq!(state.owner_id, Some(2));
+
+        // lost then claimed on the same frame
+        map.claim_tile(1, &Coord::new(5, 5), 1);
+        map.claim_tile(1, &Coord::new(5, 5), 1);
+        let state = flush_tile_state(&mut map, 5, 5).unwrap();
+        assert!(matches!(state.change, Some(TileChange::Claimed)));
+        assert_eq!(state.owner_id, Some(1));
+        assert_eq!(state.previous_owner_id, Some(2));
+    }
+
+    #[test]
+    fn tile_lost_to_opponent() {
+        let config = test_config();
+        let mut map = Map::new(&config);
+        map.claim_tile(2, &Coord::new(5, 5), 1);
+        map.state_handle.flush(&());
+        map.claim_tile(1, &Coord::new(5, 5), 1);
+        let state = flush_tile_state(&mut map, 5, 5).unwrap();
+        assert!(matches!(state.change, Some(TileChange::Lost)));
+        assert_eq!(state.previous_owner_id, Some(2));
+        assert_eq!(get_tile(&map, 5, 5).owner_id, None);
+    }
+
+    #[test]
+    fn occupation_grid_matches_map() {
+        let mut config = test_config();
+        config.dim = Coord::new(12, 7);
+        let mut map = Map::new(&config);
+        map.claim_tile(1, &Coord::new(3, 6), config.max_occupation + 5);
+        map.claim_tile(2, &Coord::new(11, 0), 4);
+
+        let grid = map.get_occupation_grid();
+        assert_eq!(grid.len(), 12);
+        assert!(grid.iter().all(|col| col.len() == 7));
+        assert_eq!(grid[3][6], config.max_occupation);
+        assert_eq!(grid[11][0], 4);
+        assert_eq!(grid.iter().flatten().filter(|occ| **occ > 0).count(), 2);
+    }
+
+    #[test]
+    fn attack_target_prefers_buildings() {
+        let mut config = test_config();
+        let probe = create_probe(&config, 5, 5);
+        for prefer_buildings in [false, true] {
+            config.probe_attack_prefer_buildings = prefer_buildings;
+            let mut map = Map::new(&config);
+            map.claim_tile(2, &Coord::new(8, 5), 1);
+            map.claim_tile(2, &Coord::new(15, 15), 1);
+            map.set_new_building(&Coord::new(15, 15), 42).unwrap();
+
+            let target = map.get_probe_attack_target(1, &probe);
+            match prefer_buildings {
+                true => assert_eq!(target, Some(Coord::new(15, 15))),
+                false => assert_eq!(target, Some(Coord::new(8, 5))),
+            }
+        }
+    }
+
+    #[test]
+    fn attack_targets_are_spread() {
+        let mut config = test_config();
+        config.probe_attack_spread = true;
+        let mut map = Map::new(&config);
+        for x in 10..13 {
+            for y in 10..13 {
+                map.claim_tile(2, &Coord::new(x, y), 1);
+            }
+        }
+
+        let mut targets: Vec<Coord> = Vec::new();
+        for _ in 0..4 {
+            let probe = create_probe(&config, 5, 5);
+            let target = map.get_probe_attack_target(1, &probe).unwrap();
+            assert!(map.get_tile(&target).unwrap().is_owned_by(2));
+            assert!(!targets.contains(&target));
+            targets.push(target);
+        }
+    }
+
+    #[test]
+    fn eroded_building_at_risk_then_dies() {
+        let mut config = test_config();
+        config.building_upkeep_occupation = 5;
+        let mut map = Map::new(&config);
+        let coord = Coord::new(5, 5);
+        map.claim_tile(1, &coord, 6);
+        map.set_new_building(&coord, 42).unwrap();
+        map.run(1.0);
+        let state = map.state_handle.flush(&()).unwrap();
+        assert!(state.get_building_risk().is_empty());
+
+        // eroded below the threshold
+        map.claim_tile(2, &coord, 2);
+        map.run(1.0);
+        let state = map.state_handle.flush(&()).unwrap();
+        assert_eq!(state.get_building_risk().get(&1), Some(&vec![(42, true)]));
+        assert!(state.get_dead_building().is_empty());
+
+        // not reinforced
+        map.run(1.0);
+        let state = map.state_handle.flush(&()).unwrap();
+        assert_eq!(state.get_dead_building().get(&1), Some(&vec![42]));
+        assert_eq!(get_tile(&map, 5, 5).building_id, None);
+        assert_eq!(map.get_building_coord(42), None);
+    }
+
+    #[test]
+    fn reinforced_building_is_safe_again() {
+        let mut config = test_config();
+        config.building_upkeep_occupation = 5;
+        let mut map = Map::new(&config);
+        let coord = Coord::new(5, 5);
+        map.claim_tile(1, &coord, 4);
+        map.set_new_building(&coord, 42).unwrap();
+        map.run(1.0);
+        map.state_handle.flush(&());
+
+        map.claim_tile(1, &coord, 2);
+        map.run(1.0);
+        let state = map.state_handle.flush(&()).unwrap();
+        assert_eq!(state.get_building_risk().get(&1), Some(&vec![(42, false)]));
+        assert!(state.get_dead_building().is_empty());
+        assert_eq!(map.get_building_coord(42), Some(coord));
+    }
+
+    #[test]
+    fn occupation_arithmetic_saturates() {
+        let mut config = test_config();
+        config.max_occupation = u32::MAX;
+        let mut tile = Tile::new(&config, Coord::new(0, 0));
+        tile.incr_occupation(u32::MAX - 1);
+        tile.incr_occupation(5);
+        assert_eq!(tile.occupation, u32::MAX);
+        tile.decr_occupation(u32::MAX - 3);
+        assert_eq!(tile.occupation, 3);
+        tile.decr_occupation(5);
+        assert_eq!(tile.occupation, 0);
+
+        let config = test_config();
+        let mut tile = Tile::new(&config, Coord::new(0, 0));
+        tile.incr_occupation(u32::MAX);
+        assert_eq!(tile.occupation, config.max_occupation);
+    }
+
+    #[test]
+    fn claims_with_large_intensity_saturate() {
+        let config = test_config();
+        let mut map = Map::new(&config);
+        let player = Player::new(1, &config);
+        let coord = Coord::new(5, 5);
+        map.claim_tile(1, &coord, u32::MAX);
+        map.claim_tile(1, &coord, u32::MAX);
+        assert_eq!(get_tile(&map, 5, 5).occupation, config.max_occupation);
+        assert_eq!(map.get_player_occupation(&player), config.max_occupation);
+
+        map.claim_tile(2, &coord, u32::MAX);
+        assert_eq!(get_tile(&map, 5, 5).occupation, 0);
+        assert_eq!(get_tile(&map, 5, 5).owner_id, None);
+        assert_eq!(map.get_player_occupation(&player), 0);
+    }
+
+    /// Return the (x, y) of the coordinates, sorted
+    fn sort_coords(coords: Vec<Coord>) -> Vec<(i32, i32)> {
+        let mut coords: Vec<(i32, i32)> = coords.iter().map(|c| (c.x, c.y)).collect();
+        coords.sort();
+        coords
+    }
+
+    #[test]
+    fn expansion_frontier_matches_claimable_neighbours() {
+        let mut config = test_config();
+        config.obstacles = vec![Coord::new(5, 4)];
+        let mut map = Map::new(&config);
+        let player = Player::new(1, &config);
+        map.claim_tile(1, &Coord::new(5, 5), 1);
+        map.claim_tile(1, &Coord::new(6, 5), 1);
+        map.claim_tile(2, &Coord::new(5, 6), 2);
+        // too occupied by the opponent to be captured
+        map.claim_tile(2, &Coord::new(4, 5), 4);
+
+        assert_eq!(
+            sort_coords(map.get_expansion_frontier(&player)),
+            vec![(5, 6), (6, 4), (6, 6), (7, 5)]
+        );
+    }
+
+    /// Return a map where the player (id: 1) owns the maxed 3x3 square
+    /// around (5, 5), except (6, 5) which is decayed
+    fn create_decayed_map(config: &GameConfig) -> Map {
+        let mut map = Map::new(config);
+        for coord in geometry::square(&Coord::new(5, 5), 1) {
+            map.claim_tile(1, &coord, config.max_occupation);
+        }
+        map.get_mut_tile(&Coord::new(6, 5)).unwrap().occupation = 5;
+        map
+    }
+
+    #[test]
+    fn farming_probes_top_up_own_tiles_first() {
+        let mut config = test_config();
+        config.probe_farm_prefer_own = true;
+        let mut map = create_decayed_map(&config);
+        let player = Player::new(1, &config);
+        let probe = create_probe(&config, 5, 5);
+        for _ in 0..20 {
+            let target = map.get_probe_farm_target(&player, &probe).unwrap();
+            assert_eq!((target.x, target.y), (6, 5));
+        }
+    }
+
+    #[test]
+    fn farming_probes_target_any_tile_by_default() {
+        let config = test_config();
+        let mut map = create_decayed_map(&config);
+        let player = Player::new(1, &config);
+        let probe = create_probe(&config, 5, 5);
+        let is_expanding = (0..20).any(|_| {
+            let target = map.get_probe_farm_target(&player, &probe).unwrap();
+            !get_tile(&map, target.x, target.y).is_owned_by(1)
+        });
+        assert!(is_expanding);
+    }
+
+    #[test]
+    fn cached_occupation_matches_tiles() {
+        let mut config = test_config();
+        config.dim = Coord::new(6, 6);
+        config.deprecate_rate = 0.2;
+        config.occupation_spread_rate = 0.2;
+        let players = [Player::new(1, &config), Player::new(2, &config)];
+        for seed in 0..5 {
+            let mut map = Map::new(&config);
+            map.rng = random::GameRng::seed_from_u64(seed);
+            let mut rng = random::GameRng::seed_from_u64(seed);
+            for i in 0..500 {
+                let player = &players[(random::random(&mut rng) * 2.0) as usize];
+                let coord = Coord::new(
+                    (random::random(&mut rng) * 6.0) as i32,
+                    (random::random(&mut rng) * 6.0) as i32,
+                );
+                map.claim_tile(player.id, &coord, (random::random(&mut rng) * 12.0) as u32);
+                if i % 10 == 0 {
+                    map.run(1.0);
+                }
+                for player in players.iter() {
+                    let occupation: u32 = map
+                        .tiles
+                        .iter()
+                        .flatten()
+                        .filter(|t| t.is_owned_by(player.id))
+                        .map(|t| t.occupation)
+                        .sum();
+                    assert_eq!(
+                        map.get_player_occupation(player),
+                        occupation,
+                        "seed: {}",
+                        seed
+                    );
+                }
+            }
+        }
+    }
+
+    /// Return the (owner id, occupation) of all the tiles of the map
+    fn get_occupations(map: &Map) -> Vec<(Option<u128>, u32)> {
+        map.tiles
+            .iter()
+            .flatten()
+            .map(|t| (t.owner_id, t.occupation))
+            .collect()
+    }
+
+    #[test]
+    fn restored_map_resumes_random_stream() {
+        let mut config = test_config();
+        config.deprecate_rate = 0.5;
+        config.occupation_spread_rate = 0.3;
+        let mut map = create_decayed_map(&config);
+        let player = Player::new(1, &config);
+        let probe = create_probe(&config, 5, 5);
+        map.run(3.0);
+
+        let bytes = bincode::serialize(&map).unwrap();
+        let mut restored: Map = bincode::deserialize(&bytes).unwrap();
+        for _ in 0..20 {
+            map.run(1.0);
+            restored.run(1.0);
+            assert_eq!(get_occupations(&restored), get_occupations(&map));
+            assert_eq!(
+                restored.get_probe_farm_target(&player, &probe),
+                map.get_probe_farm_target(&player, &probe)
+            );
+        }
+        assert_eq!(
+            random::random(&mut restored.rng),
+            random::random(&mut map.rng)
+        );
+    }
+
+    #[test]
+    fn attack_target_found_in_far_corner() {
+        let mut config = test_config();
+        config.dim = Coord::new(6, 6);
+        let mut map = Map::new(&config);
+        let probe = create_probe(&config, 0, 0);
+        assert_eq!(map.get_probe_attack_target(1, &probe), None);
+
+        map.claim_tile(2, &Coord::new(5, 5), 2);
+        assert_eq!(
+            map.get_probe_attack_target(1, &probe),
+            Some(Coord::new(5, 5))
+        );
+    }
+
+    #[test]
+    fn diagonal_contact_is_not_adjacent_under_von_neumann() {
+        let mut config = test_config();
+        let player = Player::new(1, &config);
+        for adjacency in [Adjacency::Moore, Adjacency::VonNeumann] {
+            config.adjacency = adjacency.clone();
+            let mut map = Map::new(&config);
+            map.claim_tile(1, &Coord::new(5, 5), 2);
+
+            let orthogonal = get_tile(&map, 6, 5);
+            assert!(map.is_tile_valid_farm_target(orthogonal, &player));
+            let diagonal = get_tile(&map, 6, 6);
+            match adjacency {
+                Adjacency::Moore => assert!(map.is_tile_valid_farm_target(diagonal, &player)),
+                Adjacency::VonNeumann => {
+                    assert!(!map.is_tile_valid_farm_target(diagonal, &player))
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn enemy_tile_capture_threshold() {
+        let mut config = test_config();
+        config.farm_capture_max_enemy_occupation = 4;
+        let player = Player::new(1, &config);
+        let mut map = Map::new(&config);
+        map.claim_tile(1, &Coord::new(5, 5), 2);
+        map.claim_tile(2, &Coord::new(6, 5), 4);
+        map.claim_tile(2, &Coord::new(4, 5), 5);
+        // not connected to the territory of the player
+        map.claim_tile(2, &Coord::new(12, 5), 4);
+
+        assert!(map.is_tile_valid_farm_target(get_tile(&map, 6, 5), &player));
+        assert!(!map.is_tile_valid_farm_target(get_tile(&map, 4, 5), &player));
+        assert!(!map.is_tile_valid_farm_target(get_tile(&map, 12, 5), &player));
     }
 }
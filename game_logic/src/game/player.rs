@@ -1,31 +1,35 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use log;
+use serde::{Deserialize, Serialize};
 
 use crate::game::state_vec_insert;
 
 use super::{
+    core,
     core::State,
     core::NOT_IDENTIFIABLE,
     factory::{Factory, FactoryState},
-    probe::{Probe, ProbeState},
+    probe::{Probe, ProbePolicy, ProbeState},
     turret::{Turret, TurretDeathCause, TurretState},
-    Coord, Delayer, FactoryDeathCause, FactoryPolicy, FrameContext, GameConfig, Identifiable, Map,
-    Point, StateHandler,
+    Coord, Delayer, FactionConfig, FactoryDeathCause, FactoryPolicy, FrameContext, GameConfig,
+    GameEvent, Identifiable, Map, Point, StateHandler,
 };
 
 /// All player technologies
-#[derive(Eq, Hash, PartialEq, Clone, Debug)]
+#[derive(Eq, Hash, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub enum Techs {
     PROBE_EXPLOSION_INTENSITY,
     PROBE_CLAIM_INTENSITY,
     PROBE_HP,
+    PROBE_SPEED,
     FACTORY_BUILD_DELAY,
     FACTORY_PROBE_PRICE,
     FACTORY_MAX_PROBE,
     TURRET_SCOPE,
     TURRET_FIRE_DELAY,
     TURRET_MAINTENANCE_COSTS,
+    TURRET_DAMAGE,
 }
 
 impl Techs {
@@ -36,117 +40,181 @@ impl Techs {
             "PROBE_EXPLOSION_INTENSITY" => Ok(Techs::PROBE_EXPLOSION_INTENSITY),
             "PROBE_CLAIM_INTENSITY" => Ok(Techs::PROBE_CLAIM_INTENSITY),
             "PROBE_HP" => Ok(Techs::PROBE_HP),
+            "PROBE_SPEED" => Ok(Techs::PROBE_SPEED),
             "FACTORY_BUILD_DELAY" => Ok(Techs::FACTORY_BUILD_DELAY),
             "FACTORY_PROBE_PRICE" => Ok(Techs::FACTORY_PROBE_PRICE),
             "FACTORY_MAX_PROBE" => Ok(Techs::FACTORY_MAX_PROBE),
             "TURRET_SCOPE" => Ok(Techs::TURRET_SCOPE),
             "TURRET_FIRE_DELAY" => Ok(Techs::TURRET_FIRE_DELAY),
             "TURRET_MAINTENANCE_COSTS" => Ok(Techs::TURRET_MAINTENANCE_COSTS),
+            "TURRET_DAMAGE" => Ok(Techs::TURRET_DAMAGE),
             _ => Err(format!("Invalid tech name: {}", string)),
         }
     }
 
-    /// Return if the `tech` doesn't conflicts with the `techs`
-    pub fn is_tech_acquirable(techs: &HashSet<Self>, tech: &Self) -> bool {
-        match tech {
-            Techs::PROBE_CLAIM_INTENSITY => {
-                !techs.contains(&Techs::PROBE_EXPLOSION_INTENSITY)
-                    && !techs.contains(&Techs::PROBE_HP)
-            }
-            Techs::PROBE_EXPLOSION_INTENSITY => {
-                !techs.contains(&Techs::PROBE_CLAIM_INTENSITY) && !techs.contains(&Techs::PROBE_HP)
-            }
-            Techs::PROBE_HP => {
-                !techs.contains(&Techs::PROBE_CLAIM_INTENSITY)
-                    && !techs.contains(&Techs::PROBE_EXPLOSION_INTENSITY)
-            }
-            Techs::FACTORY_BUILD_DELAY => {
-                !techs.contains(&Techs::FACTORY_MAX_PROBE)
-                    && !techs.contains(&Techs::FACTORY_PROBE_PRICE)
-            }
-            Techs::FACTORY_MAX_PROBE => {
-                !techs.contains(&Techs::FACTORY_BUILD_DELAY)
-                    && !techs.contains(&Techs::FACTORY_PROBE_PRICE)
-            }
-            Techs::FACTORY_PROBE_PRICE => {
-                !techs.contains(&Techs::FACTORY_MAX_PROBE)
-                    && !techs.contains(&Techs::FACTORY_BUILD_DELAY)
-            }
-            Techs::TURRET_FIRE_DELAY => {
-                !techs.contains(&Techs::TURRET_MAINTENANCE_COSTS)
-                    && !techs.contains(&Techs::TURRET_SCOPE)
-            }
-            Techs::TURRET_MAINTENANCE_COSTS => {
-                !techs.contains(&Techs::TURRET_FIRE_DELAY) && !techs.contains(&Techs::TURRET_SCOPE)
-            }
-            Techs::TURRET_SCOPE => {
-                !techs.contains(&Techs::TURRET_MAINTENANCE_COSTS)
-                    && !techs.contains(&Techs::TURRET_FIRE_DELAY)
-            }
-        }
+    /// Return the default groups of mutually exclusive techs:
+    /// one group for each of the probe, factory and turret techs
+    /// (turret damage is part of the turret techs)
+    pub fn default_exclusion_groups() -> Vec<HashSet<Self>> {
+        vec![
+            HashSet::from([
+                Techs::PROBE_EXPLOSION_INTENSITY,
+                Techs::PROBE_CLAIM_INTENSITY,
+                Techs::PROBE_HP,
+                Techs::PROBE_SPEED,
+            ]),
+            HashSet::from([
+                Techs::FACTORY_BUILD_DELAY,
+                Techs::FACTORY_PROBE_PRICE,
+                Techs::FACTORY_MAX_PROBE,
+            ]),
+            HashSet::from([
+                Techs::TURRET_SCOPE,
+                Techs::TURRET_FIRE_DELAY,
+                Techs::TURRET_MAINTENANCE_COSTS,
+                Techs::TURRET_DAMAGE,
+            ]),
+        ]
     }
 
-    /// Return the price of `tech`
+    /// Return if the `tech` doesn't conflicts with the `techs`,
+    /// that is, if it doesn't share an exclusion group with any of them
+    pub fn is_tech_acquirable(config: &PlayerConfig, techs: &HashSet<Self>, tech: &Self) -> bool {
+        config
+            .tech_exclusion_groups
+            .iter()
+            .filter(|group| group.contains(tech))
+            .all(|group| group.is_disjoint(techs))
+    }
+
+    /// Return the price of `tech`, taking the faction into account
     pub fn get_tech_price(config: &PlayerConfig, tech: &Self) -> f64 {
-        match tech {
+        let price = match tech {
             Techs::PROBE_CLAIM_INTENSITY => config.tech_probe_claim_intensity_price,
             Techs::PROBE_EXPLOSION_INTENSITY => config.tech_probe_explosion_intensity_price,
             Techs::PROBE_HP => config.tech_probe_hp_price,
+            Techs::PROBE_SPEED => config.tech_probe_speed_price,
             Techs::FACTORY_BUILD_DELAY => config.tech_factory_build_delay_price,
             Techs::FACTORY_MAX_PROBE => config.tech_factory_max_probe_price,
             Techs::FACTORY_PROBE_PRICE => config.tech_factory_probe_price_price,
             Techs::TURRET_FIRE_DELAY => config.tech_turret_fire_delay_price,
             Techs::TURRET_MAINTENANCE_COSTS => config.tech_turret_maintenance_costs_price,
             Techs::TURRET_SCOPE => config.tech_turret_scope_price,
+            Techs::TURRET_DAMAGE => config.tech_turret_damage_price,
+        };
+        match &config.faction {
+            Some(faction) => price * faction.tech_price_factor,
+            None => price,
+        }
+    }
+
+    /// Return if the `tech` is part of the techs of the faction
+    /// (always the case without faction)
+    pub fn is_tech_available(config: &PlayerConfig, tech: &Self) -> bool {
+        match &config.faction {
+            Some(faction) => faction.techs.contains(tech),
+            None => true,
         }
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum PlayerDeathCause {
     Defeated,
     Resigned,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct PlayerConfig {
     income_rate: f64,
+    occupation_upkeep_rate: f64,
     base_income: f64,
     probe_price: f64,
     factory_price: f64,
+    factory_expansion_cost: f64,
     factory_build_probe_delay: f64,
     turret_price: f64,
+    turret_scrap_refund_rate: f64,
     turret_fire_delay: f64,
+    building_surcharge: f64,
+    building_surcharge_distance: u32,
+    probe_reinforce_distance: f64,
+    probe_speed: f64,
+    probe_hp: u32,
+    tech_probe_speed_increase: f64,
     tech_factory_probe_price_decrease: f64,
     tech_factory_build_delay_decrease: f64,
     tech_turret_fire_delay_decrease: f64,
     tech_probe_explosion_intensity_price: f64,
     tech_probe_claim_intensity_price: f64,
     tech_probe_hp_price: f64,
+    tech_probe_speed_price: f64,
     tech_factory_build_delay_price: f64,
     tech_factory_probe_price_price: f64,
     tech_factory_max_probe_price: f64,
     tech_turret_scope_price: f64,
     tech_turret_fire_delay_price: f64,
     tech_turret_maintenance_costs_price: f64,
+    tech_turret_damage_price: f64,
+    tech_refund_rate: f64,
+    faction: Option<FactionConfig>,
+    tech_exclusion_groups: Vec<HashSet<Techs>>,
+}
+
+/// Effective values of the player's units,
+/// taking the acquired techs into account
+#[derive(Clone, Debug)]
+pub struct PlayerEffectiveStats {
+    pub probe_speed: f64,
+    pub probe_hp: u32,
+    pub probe_claim_intensity: u32,
+    pub probe_explosion_intensity: u32,
+    pub factory_max_probe: u32,
+    pub factory_build_probe_delay: f64,
+    pub factory_probe_price: f64,
+    pub turret_scope: f64,
+    pub turret_damage: u32,
+    pub turret_fire_delay: f64,
+    pub turret_maintenance_costs: f64,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct PlayerStats {
+    /// Time at which each value was recorded (sec)
+    pub time: Vec<f64>,
     pub money: Vec<f64>,
     pub occupation: Vec<u32>,
     pub factories: Vec<usize>,
     pub turrets: Vec<usize>,
     pub probes: Vec<usize>,
+    /// Summary of the recorded values, to avoid scanning the series
+    pub peak_money: f64,
+    pub peak_occupation: u32,
+    pub peak_probes: usize,
+    /// Summary of the events that occured during the game
+    pub probes_produced: u32,
+    pub probes_lost: u32,
+    pub buildings_built: u32,
+    /// Buildings conquered by an opponent (scrapped ones excluded)
+    pub buildings_lost: u32,
 }
 
 impl PlayerStats {
     pub fn new() -> Self {
         PlayerStats {
+            time: Vec::new(),
             money: Vec::new(),
             occupation: Vec::new(),
             factories: Vec::new(),
             turrets: Vec::new(),
             probes: Vec::new(),
+            peak_money: 0.0,
+            peak_occupation: 0,
+            peak_probes: 0,
+            probes_produced: 0,
+            probes_lost: 0,
+            buildings_built: 0,
+            buildings_lost: 0,
         }
     }
 
@@ -159,22 +227,69 @@ impl PlayerStats {
         turrets: usize,
         probes: usize,
     ) {
+        self.time.push(time);
         self.money.push(money);
         self.occupation.push(occupation);
         self.factories.push(factories);
         self.turrets.push(turrets);
         self.probes.push(probes);
+
+        self.peak_money = f64::max(self.peak_money, money);
+        self.peak_occupation = u32::max(self.peak_occupation, occupation);
+        self.peak_probes = usize::max(self.peak_probes, probes);
     }
 }
 
-#[derive(Clone, Debug)]
+/// Named group of probes of a player, managed by the client
+/// (see `Player::create_group`)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProbeGroup {
+    pub id: u128,
+    pub name: String,
+    pub probe_ids: HashSet<u128>,
+}
+
+/// Detail of the last computed income of the player,
+/// costs are given as negative values \
+/// Note: factory production costs (probe price) are not included
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct IncomeBreakdown {
+    pub base: f64,
+    pub occupation: f64,
+    pub bonus: f64,
+    pub factory_maintenance: f64,
+    pub probe_maintenance: f64,
+    pub turret_maintenance: f64,
+    pub occupation_upkeep: f64,
+}
+
+impl IncomeBreakdown {
+    /// Return the total income
+    pub fn total(&self) -> f64 {
+        self.base
+            + self.occupation
+            + self.bonus
+            + self.factory_maintenance
+            + self.probe_maintenance
+            + self.turret_maintenance
+            + self.occupation_upkeep
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PlayerState {
     pub id: u128,
     /// Only specified once, when the player dies
     pub death: Option<PlayerDeathCause>,
     pub money: Option<f64>,
     pub income: Option<f64>,
+    pub income_breakdown: Option<IncomeBreakdown>,
+    pub default_probe_policy: Option<ProbePolicy>,
+    /// All the groups of the player, specified when any of them changes
+    pub groups: Option<Vec<ProbeGroup>>,
     pub techs: Vec<Techs>,
+    /// Techs refunded since the last state (see `Player::refund_tech`)
+    pub refunded_techs: Vec<Techs>,
     pub factories: Vec<FactoryState>,
     pub turrets: Vec<TurretState>,
 }
@@ -194,7 +309,11 @@ impl State for PlayerState {
             death: None,
             money: None,
             income: None,
+            income_breakdown: None,
+            default_probe_policy: None,
+            groups: None,
             techs: Vec::new(),
+            refunded_techs: Vec::new(),
             factories: Vec::new(),
             turrets: Vec::new(),
         }
@@ -210,6 +329,16 @@ impl State for PlayerState {
         if let Some(income) = state.income {
             self.income = Some(income);
         }
+        if let Some(breakdown) = state.income_breakdown {
+            self.income_breakdown = Some(breakdown);
+        }
+        if let Some(policy) = state.default_probe_policy {
+            self.default_probe_policy = Some(policy);
+        }
+        if let Some(groups) = state.groups {
+            self.groups = Some(groups);
+        }
+        self.refunded_techs.extend(state.refunded_techs);
         for factory in state.factories {
             state_vec_insert(&mut self.factories, factory);
         }
@@ -219,6 +348,7 @@ impl State for PlayerState {
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Player {
     pub id: u128,
     config: PlayerConfig,
@@ -230,40 +360,149 @@ pub struct Player {
     pub turrets: Vec<Turret>,
     /// Delay to wait between two incomes
     delayer_income: Delayer,
+    /// Last computed income
+    last_income: f64,
+    /// Detail of the last computed income
+    income_breakdown: IncomeBreakdown,
+    /// Policy given to newly created probes
+    default_probe_policy: ProbePolicy,
+    /// Ids of the factories paused when the player was abandoned,
+    /// None if the player isn't abandoned (see `Player::abandon`)
+    abandoned_factories: Option<Vec<u128>>,
+    /// Groups of probes, by group id
+    groups: HashMap<u128, ProbeGroup>,
 }
 
 impl Player {
+    /// Create a new Player instance, applying the overrides
+    /// of the player's config (see `GameConfig.per_player_overrides`)
     pub fn new(id: u128, config: &GameConfig) -> Self {
+        let overrides = config.per_player_overrides.get(&id);
+        let initial_money = overrides
+            .and_then(|o| o.initial_money)
+            .unwrap_or(config.initial_money);
+        let income_rate = overrides
+            .and_then(|o| o.income_rate)
+            .unwrap_or(config.income_rate);
+        let probe_speed = overrides
+            .and_then(|o| o.probe_speed)
+            .unwrap_or(config.probe_speed);
         Player {
             id: id,
             config: PlayerConfig {
-                income_rate: config.income_rate,
+                income_rate: income_rate,
+                occupation_upkeep_rate: config.occupation_upkeep_rate,
                 base_income: config.base_income,
                 probe_price: config.probe_price,
                 factory_price: config.factory_price,
+                factory_expansion_cost: config.factory_expansion_cost,
                 factory_build_probe_delay: config.factory_build_probe_delay,
                 turret_price: config.turret_price,
+                turret_scrap_refund_rate: config.turret_scrap_refund_rate,
                 turret_fire_delay: config.turret_fire_delay,
+                building_surcharge: config.building_surcharge,
+                building_surcharge_distance: config.building_surcharge_distance,
+                probe_reinforce_distance: config.probe_reinforce_distance,
+                probe_speed: probe_speed,
+                probe_hp: config.probe_hp,
+                tech_probe_speed_increase: config.tech_probe_speed_increase,
                 tech_factory_probe_price_decrease: config.tech_factory_probe_price_decrease,
                 tech_factory_build_delay_decrease: config.tech_factory_build_delay_decrease,
                 tech_turret_fire_delay_decrease: config.tech_turret_fire_delay_decrease,
                 tech_probe_explosion_intensity_price: config.tech_probe_explosion_intensity_price,
                 tech_probe_claim_intensity_price: config.tech_probe_claim_intensity_price,
                 tech_probe_hp_price: config.tech_probe_hp_price,
+                tech_probe_speed_price: config.tech_probe_speed_price,
                 tech_factory_build_delay_price: config.tech_factory_build_delay_price,
                 tech_factory_probe_price_price: config.tech_factory_probe_price_price,
                 tech_factory_max_probe_price: config.tech_factory_max_probe_price,
                 tech_turret_scope_price: config.tech_turret_scope_price,
                 tech_turret_fire_delay_price: config.tech_turret_fire_delay_price,
                 tech_turret_maintenance_costs_price: config.tech_turret_maintenance_costs_price,
+                tech_turret_damage_price: config.tech_turret_damage_price,
+                tech_refund_rate: config.tech_refund_rate,
+                faction: None,
+                tech_exclusion_groups: config.tech_exclusion_groups.clone(),
             },
             state_handle: StateHandler::new(&id),
             stats: PlayerStats::new(),
             techs: HashSet::new(),
-            money: config.initial_money,
+            money: initial_money,
             factories: Vec::new(),
             turrets: Vec::new(),
             delayer_income: Delayer::new(1.0),
+            last_income: 0.0,
+            income_breakdown: IncomeBreakdown::default(),
+            default_probe_policy: ProbePolicy::Farm,
+            abandoned_factories: None,
+            groups: HashMap::new(),
+        }
+    }
+
+    /// Create a Player instance from its (complete) state,
+    /// register its buildings on the map \
+    /// Return an error in case a building can't be placed on the map
+    pub fn from_state(
+        config: &GameConfig,
+        state: &PlayerState,
+        map: &mut Map,
+    ) -> Result<Player, String> {
+        let mut player = Player::new(state.id, config);
+        if let Some(money) = state.money {
+            player.money = money;
+        }
+        if let Some(policy) = &state.default_probe_policy {
+            player.default_probe_policy = policy.clone();
+        }
+        if let Some(breakdown) = &state.income_breakdown {
+            player.income_breakdown = breakdown.clone();
+        }
+        if let Some(groups) = &state.groups {
+            for group in groups.iter() {
+                player.groups.insert(group.id, group.clone());
+            }
+        }
+        for tech in state.techs.iter() {
+            player.techs.insert(tech.clone());
+        }
+
+        for factory_state in state.factories.iter() {
+            let factory = Factory::from_state(config, &player, factory_state)?;
+            Player::restore_building(player.id, &factory.pos, factory.id, map)?;
+            player.factories.push(factory);
+        }
+        for turret_state in state.turrets.iter() {
+            let turret = Turret::from_state(config, turret_state)?;
+            Player::restore_building(player.id, &turret.get_coord(), turret.id, map)?;
+            player.turrets.push(turret);
+        }
+
+        // apply one-off effects of the techs, then discard the state
+        player.state_handle.get_mut().techs = state.techs.clone();
+        player.handle_new_techs();
+        player.state_handle = StateHandler::new(&player.id);
+
+        Ok(player)
+    }
+
+    /// Register the building on the map \
+    /// Return an error in case the tile isn't owned by the player
+    /// or already has a building
+    fn restore_building(
+        player_id: u128,
+        pos: &Coord,
+        building_id: u128,
+        map: &mut Map,
+    ) -> Result<(), String> {
+        match map.get_tile(pos) {
+            Some(tile) if tile.is_owned_by(player_id) => {}
+            _ => {
+                return Err(format!("Invalid building coordinate ({:?})", pos));
+            }
+        }
+        match map.set_new_building(pos, building_id) {
+            Ok(()) => Ok(()),
+            Err(()) => Err(format!("Tile already has a building ({:?})", pos)),
         }
     }
 
@@ -274,7 +513,11 @@ impl Player {
             death: None,
             money: Some(self.money),
             income: Some(0.0),
+            income_breakdown: Some(self.income_breakdown.clone()),
+            default_probe_policy: Some(self.default_probe_policy.clone()),
+            groups: Some(self.get_groups()),
             techs: Vec::with_capacity(self.techs.len()),
+            refunded_techs: Vec::new(),
             factories: Vec::with_capacity(self.factories.len()),
             turrets: Vec::with_capacity(self.turrets.len()),
         };
@@ -313,16 +556,48 @@ impl Player {
     }
 
     /// Create a new probe, set a target for the probe \
+    /// The probe is sent to the `rally` target of its factory (if any),
+    /// unless it has been taken by an opponent in the meantime \
     /// Return the new probe state
-    fn create_probe(&self, state: &mut ProbeState, ctx: &mut FrameContext) -> Option<Probe> {
+    fn create_probe(
+        &self,
+        state: &mut ProbeState,
+        rally: Option<&Coord>,
+        ctx: &mut FrameContext,
+    ) -> Option<Probe> {
         if let Some(pos) = &state.pos {
             let mut probe = Probe::new(ctx.config, &self, pos.clone());
             // set id
             state.id = probe.id;
+
+            if let ProbePolicy::Attack = self.default_probe_policy {
+                probe.set_attack(self.id, ctx.map);
+                let probe_state = probe.get_complete_state();
+                state.policy = probe_state.policy;
+                state.target = probe_state.target;
+                return Some(probe);
+            }
+
+            if let Some(rally) = rally {
+                if let Some(tile) = ctx.map.get_tile(rally) {
+                    if !tile.is_owned_by_opponent_of(self.id) {
+                        probe.set_target_manually(rally.as_point());
+                        state.target = Some(rally.clone());
+                        return Some(probe);
+                    }
+                }
+            }
+
             // set target
             let target = match ctx.map.get_probe_farm_target(self, &probe) {
                 Some(target) => target,
-                None => pos.as_coord(),
+                None => {
+                    // instead of claiming the factory's own tile
+                    if probe.idle() {
+                        state.policy = Some(ProbePolicy::Idle);
+                    }
+                    pos.as_coord()
+                }
             };
 
             probe.set_target_manually(target.as_point());
@@ -333,18 +608,65 @@ impl Player {
         None
     }
 
+    /// Set the faction of the player (see `GameConfig.factions`)
+    pub fn set_faction(&mut self, faction: FactionConfig) {
+        self.config.faction = Some(faction);
+    }
+
+    /// Give money to the player, update state
+    pub fn add_money(&mut self, amount: f64) {
+        self.money += amount;
+        self.state_handle.get_mut().money = Some(self.money);
+    }
+
+    /// Return the current amount of money of the player
+    pub fn get_money(&self) -> f64 {
+        self.money
+    }
+
+    /// Return the total number of probes of the player
+    pub fn get_num_probes(&self) -> usize {
+        self.factories.iter().map(|f| f.get_num_probes()).sum()
+    }
+
     /// Iterator over each probe of each factory of player
     pub fn iter_mut_probes(&mut self) -> impl Iterator<Item = &mut Probe> {
         self.factories.iter_mut().flat_map(|f| f.iter_mut_probes())
     }
 
+    /// Return the probe at the given indexes (of the factory in the player's
+    /// factories and of the probe in the factory's probes), if it exists
+    pub fn get_mut_probe_at(&mut self, factory_idx: usize, probe_idx: usize) -> Option<&mut Probe> {
+        self.factories
+            .get_mut(factory_idx)?
+            .get_mut_probe_at(probe_idx)
+    }
+
     /// Return the probe with the given id, if it exists
-    fn get_mut_probe_by_id(&mut self, probe_id: u128) -> Option<&mut Probe> {
+    pub fn get_mut_probe_by_id(&mut self, probe_id: u128) -> Option<&mut Probe> {
         self.factories
             .iter_mut()
             .find_map(|f| f.get_mut_probe_by_id(probe_id))
     }
 
+    /// Redirect the farming probes close to a threatened building
+    /// to its tile, in order to reinforce it
+    /// (see `GameConfig.probe_reinforce_distance`)
+    fn reinforce_buildings(&mut self, map: &mut Map) {
+        if self.config.probe_reinforce_distance == 0.0 {
+            return;
+        }
+        let distance = self.config.probe_reinforce_distance;
+        for coord in map.take_threatened_buildings(self.id) {
+            let target = coord.as_point();
+            for probe in self.iter_mut_probes() {
+                if probe.is_farming() && probe.get_distance(&coord) <= distance {
+                    probe.set_farm_target(target.clone());
+                }
+            }
+        }
+    }
+
     /// Return if the player has acquired the `tech`
     pub fn has_tech(&self, tech: &Techs) -> bool {
         self.techs.contains(tech)
@@ -358,6 +680,74 @@ impl Player {
         self.config.probe_price
     }
 
+    /// Return the speed of the player's probes, taking tech into account
+    pub fn get_probe_speed(&self) -> f64 {
+        if self.has_tech(&Techs::PROBE_SPEED) {
+            return self.config.probe_speed + self.config.tech_probe_speed_increase;
+        }
+        self.config.probe_speed
+    }
+
+    /// Return the effective stats of the player's units,
+    /// as modified by the acquired techs
+    pub fn get_effective_stats(&self, config: &GameConfig) -> PlayerEffectiveStats {
+        let mut stats = PlayerEffectiveStats {
+            probe_speed: self.config.probe_speed,
+            probe_hp: config.probe_hp,
+            probe_claim_intensity: config.probe_claim_intensity,
+            probe_explosion_intensity: config.probe_explosion_intensity,
+            factory_max_probe: config.factory_max_probe,
+            factory_build_probe_delay: config.factory_build_probe_delay,
+            factory_probe_price: self.get_probe_price(),
+            turret_scope: config.turret_scope,
+            turret_damage: config.turret_damage,
+            turret_fire_delay: config.turret_fire_delay,
+            turret_maintenance_costs: config.turret_maintenance_costs,
+        };
+        for tech in self.techs.iter() {
+            match tech {
+                Techs::PROBE_HP => {
+                    stats.probe_hp += config.tech_probe_hp_increase;
+                }
+                Techs::PROBE_SPEED => {
+                    stats.probe_speed += config.tech_probe_speed_increase;
+                }
+                Techs::PROBE_CLAIM_INTENSITY => {
+                    stats.probe_claim_intensity = stats
+                        .probe_claim_intensity
+                        .saturating_add(config.tech_probe_claim_intensity_increase);
+                }
+                Techs::PROBE_EXPLOSION_INTENSITY => {
+                    stats.probe_explosion_intensity = stats
+                        .probe_explosion_intensity
+                        .saturating_add(config.tech_probe_explosion_intensity_increase);
+                }
+                Techs::FACTORY_MAX_PROBE => {
+                    stats.factory_max_probe += config.tech_factory_max_probe_increase;
+                }
+                Techs::FACTORY_BUILD_DELAY => {
+                    stats.factory_build_probe_delay -= config.tech_factory_build_delay_decrease;
+                }
+                Techs::TURRET_SCOPE => {
+                    stats.turret_scope += config.tech_turret_scope_increase;
+                }
+                Techs::TURRET_DAMAGE => {
+                    stats.turret_damage = stats
+                        .turret_damage
+                        .saturating_add(config.tech_turret_damage_increase);
+                }
+                Techs::TURRET_FIRE_DELAY => {
+                    stats.turret_fire_delay -= config.tech_turret_fire_delay_decrease;
+                }
+                Techs::TURRET_MAINTENANCE_COSTS => {
+                    stats.turret_maintenance_costs -= config.tech_turret_maintenance_costs_decrease;
+                }
+                Techs::FACTORY_PROBE_PRICE => {}
+            }
+        }
+        stats
+    }
+
     /// Set a new target for the probe \
     /// Update involved states \
     /// Return if it could be done (if the probe exists)
@@ -372,6 +762,249 @@ impl Player {
         true
     }
 
+    /// Set the policy given to newly created probes
+    /// (existing probes are unaffected) \
+    /// Return an error in case the policy is neither Farm nor Attack
+    pub fn set_default_probe_policy(&mut self, policy: ProbePolicy) -> Result<(), String> {
+        match policy {
+            ProbePolicy::Farm | ProbePolicy::Attack => {}
+            _ => {
+                return Err(format!("Invalid default probe policy: {:?}", policy));
+            }
+        }
+        self.default_probe_policy = policy.clone();
+        self.state_handle.get_mut().default_probe_policy = Some(policy);
+        Ok(())
+    }
+
+    /// Make the probe hold its position \
+    /// Update involved states \
+    /// Return if it could be done (if the probe exists)
+    pub fn hold_probe(&mut self, probe_id: u128) -> bool {
+        let probe = match self.get_mut_probe_by_id(probe_id) {
+            Some(probe) => probe,
+            None => {
+                return false;
+            }
+        };
+        probe.hold();
+        true
+    }
+
+    /// Stop the probes: make them hold their position (see `hold_probe`) \
+    /// Return the number of probes that could be stopped
+    pub fn stop_probes(&mut self, ids: &[u128]) -> usize {
+        ids.iter().filter(|id| self.hold_probe(**id)).count()
+    }
+
+    /// Set the region where the probe searches its farm targets in priority,
+    /// and (if a target is found in the region) send the probe to it \
+    /// Update involved states \
+    /// Return if it could be done (if the probe exists)
+    pub fn set_probe_farm_region(
+        &mut self,
+        probe_id: u128,
+        center: Coord,
+        radius: u32,
+        map: &mut Map,
+    ) -> bool {
+        let target = match radius {
+            0 => None,
+            _ => map.get_region_probe_farm_target(&self, &center, radius, None),
+        };
+        let probe = match self.get_mut_probe_by_id(probe_id) {
+            Some(probe) => probe,
+            None => {
+                return false;
+            }
+        };
+        probe.set_farm_region(center, radius);
+        if let Some(target) = target {
+            probe.set_farm_target(target.as_point());
+        }
+        true
+    }
+
+    /// Abandon the player (ex: on disconnection): pause the production
+    /// of all factories and recall the probes to the closest owned tile,
+    /// the buildings are left standing (see `Player::resume`) \
+    /// Return an error in case the player is already abandoned
+    pub fn abandon(&mut self, map: &Map) -> Result<(), String> {
+        if self.abandoned_factories.is_some() {
+            return Err(String::from("Player already abandoned"));
+        }
+        // only keep track of the factories that weren't already paused
+        let mut factory_ids = Vec::new();
+        for factory in self.factories.iter_mut() {
+            if !factory.is_paused() {
+                factory.set_paused(true);
+                factory_ids.push(factory.id);
+            }
+        }
+        self.abandoned_factories = Some(factory_ids);
+
+        let id = self.id;
+        for probe in self.iter_mut_probes() {
+            if let Some(coord) = map.get_closest_owned_tile(id, &probe.get_coord()) {
+                probe.set_farm_target(coord.as_point());
+            }
+        }
+        Ok(())
+    }
+
+    /// Resume an abandoned player: resume the production of
+    /// the factories paused when the player was abandoned \
+    /// Return an error in case the player isn't abandoned
+    pub fn resume(&mut self) -> Result<(), String> {
+        let factory_ids = match self.abandoned_factories.take() {
+            Some(factory_ids) => factory_ids,
+            None => {
+                return Err(String::from("Player isn't abandoned"));
+            }
+        };
+        for factory in self.factories.iter_mut() {
+            if factory_ids.contains(&factory.id) {
+                factory.set_paused(false);
+            }
+        }
+        Ok(())
+    }
+
+    /// Return if the player is abandoned (see `Player::abandon`)
+    pub fn is_abandoned(&self) -> bool {
+        self.abandoned_factories.is_some()
+    }
+
+    /// Create a new (empty) group of probes \
+    /// Update involved states \
+    /// Return the id of the group
+    pub fn create_group(&mut self, name: String) -> u128 {
+        let id = core::generate_unique_id();
+        self.groups.insert(
+            id,
+            ProbeGroup {
+                id: id,
+                name: name,
+                probe_ids: HashSet::new(),
+            },
+        );
+        self.state_handle.get_mut().groups = Some(self.get_groups());
+        id
+    }
+
+    /// Add the probes to the group, the unknown probes are ignored \
+    /// Update involved states \
+    /// Return the number of probes added,
+    /// or an error in case the group doesn't exist
+    pub fn assign_probes(&mut self, group_id: u128, ids: Vec<u128>) -> Result<usize, String> {
+        let probe_ids: HashSet<u128> = self
+            .factories
+            .iter()
+            .flat_map(|f| f.iter_probes())
+            .map(|p| p.id)
+            .collect();
+        let group = match self.groups.get_mut(&group_id) {
+            Some(group) => group,
+            None => {
+                return Err(String::from("Invalid group"));
+            }
+        };
+        let n_assigned = ids
+            .into_iter()
+            .filter(|id| probe_ids.contains(id))
+            .filter(|id| group.probe_ids.insert(*id))
+            .count();
+        self.state_handle.get_mut().groups = Some(self.get_groups());
+        Ok(n_assigned)
+    }
+
+    /// Return the ids of the probes of the group \
+    /// Return an error in case the group doesn't exist
+    pub fn get_group_probe_ids(&self, group_id: u128) -> Result<Vec<u128>, String> {
+        match self.groups.get(&group_id) {
+            Some(group) => Ok(group.probe_ids.iter().cloned().collect()),
+            None => Err(String::from("Invalid group")),
+        }
+    }
+
+    /// Return all the groups of the player
+    fn get_groups(&self) -> Vec<ProbeGroup> {
+        self.groups.values().cloned().collect()
+    }
+
+    /// Remove the dead probes from the groups \
+    /// Update involved states
+    fn prune_groups(&mut self) {
+        if self.groups.is_empty() {
+            return;
+        }
+        let probe_ids: HashSet<u128> = self
+            .factories
+            .iter()
+            .flat_map(|f| f.iter_probes())
+            .map(|p| p.id)
+            .collect();
+        let mut is_change = false;
+        for group in self.groups.values_mut() {
+            let n_probes = group.probe_ids.len();
+            group.probe_ids.retain(|id| probe_ids.contains(id));
+            is_change |= group.probe_ids.len() != n_probes;
+        }
+        if is_change {
+            self.state_handle.get_mut().groups = Some(self.get_groups());
+        }
+    }
+
+    /// Make the probe patrol between `a` and `b` \
+    /// Update involved states \
+    /// Return if it could be done (if the probe exists)
+    pub fn set_probe_patrol(&mut self, probe_id: u128, a: Point, b: Point) -> bool {
+        let probe = match self.get_mut_probe_by_id(probe_id) {
+            Some(probe) => probe,
+            None => {
+                return false;
+            }
+        };
+        probe.patrol(a, b);
+        true
+    }
+
+    /// Make the probe siege the target (see `Probe::siege`) \
+    /// Update involved states \
+    /// Return if it could be done (if the probe exists)
+    pub fn set_probe_siege(&mut self, probe_id: u128, target: Point) -> bool {
+        let probe = match self.get_mut_probe_by_id(probe_id) {
+            Some(probe) => probe,
+            None => {
+                return false;
+            }
+        };
+        probe.siege(target);
+        true
+    }
+
+    /// Make the probe hold its position as a picket,
+    /// the probe must be on a tile of the player \
+    /// Update involved states \
+    /// Return if it could be done (if the probe exists and is on a tile of the player)
+    pub fn set_probe_picket(&mut self, probe_id: u128, map: &Map) -> bool {
+        let id = self.id;
+        let probe = match self.get_mut_probe_by_id(probe_id) {
+            Some(probe) => probe,
+            None => {
+                return false;
+            }
+        };
+        match map.get_tile(&probe.get_coord()) {
+            Some(tile) if tile.is_owned_by(id) => {}
+            _ => {
+                return false;
+            }
+        }
+        probe.picket();
+        true
+    }
+
     /// Explode the probe \
     /// Update involved states \
     /// Return if it could be done (if the probe exists)
@@ -426,17 +1059,41 @@ impl Player {
         state
     }
 
+    /// Return the surcharge to build at the given position,
+    /// non-zero if close to an opponent tile
+    fn get_building_surcharge(&self, pos: &Coord, map: &Map) -> f64 {
+        if self.config.building_surcharge_distance == 0 {
+            return 0.0;
+        }
+        if map.is_close_to_opponent(self.id, pos, self.config.building_surcharge_distance) {
+            return self.config.building_surcharge;
+        }
+        0.0
+    }
+
+    /// Return the price to build a factory at the given position
+    pub fn get_factory_price(&self, pos: &Coord, map: &Map) -> f64 {
+        self.config.factory_price + self.get_building_surcharge(pos, map)
+    }
+
+    /// Return the price to build a turret at the given position
+    pub fn get_turret_price(&self, pos: &Coord, map: &Map) -> f64 {
+        self.config.turret_price + self.get_building_surcharge(pos, map)
+    }
+
     /// If player has enough money, create a new factory (see `create_factory`) \
     /// Return if the new factory could be created
     pub fn build_factory(&mut self, pos: Coord, map: &mut Map, config: &GameConfig) -> bool {
-        if self.money < self.config.factory_price {
+        let price = self.get_factory_price(&pos, map);
+        if self.money < price {
             return false;
         }
-        self.money -= self.config.factory_price;
+        self.money -= price;
         self.state_handle.get_mut().money = Some(self.money);
 
         let state = self.create_factory(pos, map, config);
         state_vec_insert(&mut self.state_handle.get_mut().factories, state);
+        self.stats.buildings_built += 1;
 
         true
     }
@@ -455,6 +1112,10 @@ impl Player {
 
         if let Some(idx) = idx {
             let factory = self.factories.remove(idx);
+            self.stats.probes_lost += factory.get_num_probes() as u32;
+            if let FactoryDeathCause::Conquered = death_cause {
+                self.stats.buildings_lost += 1;
+            }
             return Some(factory.die(death_cause));
         }
         None
@@ -481,14 +1142,16 @@ impl Player {
     /// If player has enough money, create a new turret (see `create_turret`) \
     /// Return if the new turret could be created
     pub fn build_turret(&mut self, pos: Coord, map: &mut Map, config: &GameConfig) -> bool {
-        if self.money < self.config.turret_price {
+        let price = self.get_turret_price(&pos, map);
+        if self.money < price {
             return false;
         }
-        self.money -= self.config.turret_price;
+        self.money -= price;
         self.state_handle.get_mut().money = Some(self.money);
 
         let state = self.create_turret(pos, map, config);
         state_vec_insert(&mut self.state_handle.get_mut().turrets, state);
+        self.stats.buildings_built += 1;
         true
     }
 
@@ -506,11 +1169,94 @@ impl Player {
 
         if let Some(idx) = idx {
             let turret = self.turrets.remove(idx);
+            if let TurretDeathCause::Conquered = death_cause {
+                self.stats.buildings_lost += 1;
+            }
             return Some(turret.die(death_cause));
         }
         None
     }
 
+    /// Scrap a turret (if `turret_id` is valid),
+    /// refund part of the turret price \
+    /// Return turret state
+    ///
+    /// Note: the turret's tile is not notified (see `Map::remove_building`)
+    pub fn scrap_turret(&mut self, turret_id: u128) -> Option<TurretState> {
+        let state = self.kill_turret(turret_id, TurretDeathCause::Scrapped)?;
+        let refund = self.config.turret_price * self.config.turret_scrap_refund_rate;
+        if refund > 0.0 {
+            self.add_money(refund);
+        }
+        Some(state)
+    }
+
+    /// Set the policy of the factory: "Produce" or "Pause"
+    /// (see `Factory::set_paused`) \
+    /// Return an error in case the factory doesn't exist
+    pub fn set_factory_policy(
+        &mut self,
+        factory_id: u128,
+        policy: FactoryPolicy,
+    ) -> Result<(), String> {
+        let factory = match self.factories.iter_mut().find(|f| f.id == factory_id) {
+            Some(factory) => factory,
+            None => {
+                return Err(String::from("Invalid factory"));
+            }
+        };
+        factory.set_paused(matches!(policy, FactoryPolicy::Paused));
+        Ok(())
+    }
+
+    /// Re-run the expansion of the factory (see `Factory::reexpand`)
+    /// for `factory_expansion_cost` \
+    /// Update involved states \
+    /// Return an error in case the factory doesn't exist,
+    /// is already expanding or there isn't enough money
+    pub fn reexpand_factory(&mut self, factory_id: u128) -> Result<(), String> {
+        let cost = self.config.factory_expansion_cost;
+        if self.money < cost {
+            return Err(format!("Not enough money (<{})", cost));
+        }
+        let factory = match self.factories.iter_mut().find(|f| f.id == factory_id) {
+            Some(factory) => factory,
+            None => {
+                return Err(String::from("Invalid factory"));
+            }
+        };
+        factory.reexpand()?;
+
+        self.money -= cost;
+        self.state_handle.get_mut().money = Some(self.money);
+        Ok(())
+    }
+
+    /// Set the target of the probes produced by the factory
+    /// (see `Factory::set_rally`) \
+    /// Return if the factory exists
+    pub fn set_factory_rally(&mut self, factory_id: u128, rally: Coord) -> bool {
+        match self.factories.iter_mut().find(|f| f.id == factory_id) {
+            Some(factory) => {
+                factory.set_rally(rally);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set the focus point of the turret (see `Turret::set_focus`) \
+    /// Return if the turret exists
+    pub fn set_turret_focus(&mut self, turret_id: u128, focus: Coord) -> bool {
+        match self.turrets.iter_mut().find(|t| t.id == turret_id) {
+            Some(turret) => {
+                turret.set_focus(focus);
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Acquire the given technology \
     /// Return an error in case this fails
     pub fn acquire_tech(&mut self, tech: Techs) -> Result<(), String> {
@@ -518,7 +1264,11 @@ impl Player {
             return Err(String::from("Technology already acquired."));
         }
 
-        if !Techs::is_tech_acquirable(&self.techs, &tech) {
+        if !Techs::is_tech_available(&self.config, &tech) {
+            return Err(String::from("Technology not available for the faction."));
+        }
+
+        if !Techs::is_tech_acquirable(&self.config, &self.techs, &tech) {
             return Err(String::from(
                 "Can't acquire multiple technologies of same category.",
             ));
@@ -538,9 +1288,60 @@ impl Player {
         Ok(())
     }
 
+    /// Refund the given technology, part of its price is refunded
+    /// (see `GameConfig.tech_refund_rate`), its effects on the existing
+    /// factories, turrets and probes are reverted \
+    /// Note: when refunding `PROBE_HP`, the max hp of the probes is brought
+    /// back to the base hp, the probes keep at most that many hp \
+    /// Return an error in case the technology isn't acquired
+    pub fn refund_tech(&mut self, tech: Techs) -> Result<(), String> {
+        if !self.techs.remove(&tech) {
+            return Err(String::from("Technology not acquired."));
+        }
+        // in case the tech was acquired during the frame
+        self.state_handle.get_mut().techs.retain(|t| *t != tech);
+        self.state_handle
+            .get_mut()
+            .refunded_techs
+            .push(tech.clone());
+
+        match tech {
+            Techs::PROBE_SPEED => {
+                let speed = self.get_probe_speed();
+                for probe in self.iter_mut_probes() {
+                    probe.set_speed(speed);
+                }
+            }
+            Techs::PROBE_HP => {
+                let hp = self.config.probe_hp;
+                for probe in self.iter_mut_probes() {
+                    probe.set_max_hp(hp);
+                }
+            }
+            Techs::FACTORY_BUILD_DELAY => {
+                for factory in self.factories.iter_mut() {
+                    factory.set_build_probe_delay(self.config.factory_build_probe_delay);
+                }
+            }
+            Techs::TURRET_FIRE_DELAY => {
+                for turret in self.turrets.iter_mut() {
+                    turret.set_fire_delay(self.config.turret_fire_delay);
+                }
+            }
+            _ => {}
+        }
+
+        let refund = Techs::get_tech_price(&self.config, &tech) * self.config.tech_refund_rate;
+        if refund > 0.0 {
+            self.add_money(refund);
+        }
+        Ok(())
+    }
+
     /// Compute the income prediction given the last computed income
-    fn get_income_prediction(&self, income: f64) -> f64 {
-        let mut prediction = income;
+    /// (see `IncomeBreakdown`)
+    fn get_income_prediction(&self, breakdown: &IncomeBreakdown) -> f64 {
+        let mut prediction = breakdown.total();
         // NOTE: do not take into account factory production costs for now
         // let probe_price = self.get_probe_price();
         // for factory in self.factories.iter() {
@@ -557,26 +1358,50 @@ impl Player {
     /// Wait for income delay, then compute income,
     /// update money and compute income prediction
     fn update_money(&mut self, ctx: &mut FrameContext) {
-        if !self.delayer_income.wait(ctx.dt) {
+        // always wait on own delayer to keep track of the time
+        let is_own_tick = self.delayer_income.wait(ctx.dt);
+        let is_income_tick = match ctx.config.align_income_ticks {
+            true => ctx.is_income_tick,
+            false => is_own_tick,
+        };
+        if !is_income_tick {
             return;
         }
         let total_occupation = ctx.map.get_player_occupation(&self);
-
-        let mut income = self.config.base_income;
-        income += total_occupation as f64 * self.config.income_rate;
+        let income_occupation = ctx.map.get_player_income_occupation(&self);
+
+        let mut breakdown = IncomeBreakdown {
+            base: self.config.base_income,
+            occupation: income_occupation as f64 * self.config.income_rate,
+            bonus: ctx.map.get_player_bonus_income(&self),
+            occupation_upkeep: -(total_occupation as f64) * self.config.occupation_upkeep_rate,
+            ..IncomeBreakdown::default()
+        };
         for factory in self.factories.iter() {
-            income += factory.get_income();
+            breakdown.factory_maintenance += factory.get_income();
+            breakdown.probe_maintenance += factory.get_probes_income();
         }
         for turret in self.turrets.iter() {
-            income += turret.get_income(&self);
+            breakdown.turret_maintenance += turret.get_income(&self);
         }
+        let income = breakdown.total();
 
         self.money = f64::max(self.money + income, 0.0);
 
-        let prediction = self.get_income_prediction(income);
+        if income < 0.0 && self.last_income >= 0.0 {
+            ctx.events.push(GameEvent::IncomeNegative {
+                player_id: self.id,
+                income: income,
+            });
+        }
+        self.last_income = income;
+
+        let prediction = self.get_income_prediction(&breakdown);
 
         self.state_handle.get_mut().money = Some(self.money);
         self.state_handle.get_mut().income = Some(prediction);
+        self.state_handle.get_mut().income_breakdown = Some(breakdown.clone());
+        self.income_breakdown = breakdown;
 
         self.record(total_occupation);
     }
@@ -589,7 +1414,7 @@ impl Player {
             total_occupation,
             self.factories.len(),
             self.turrets.len(),
-            self.factories.iter().map(|f| f.get_num_probes()).sum(),
+            self.get_num_probes(),
         );
     }
 
@@ -602,8 +1427,12 @@ impl Player {
     fn handle_new_techs(&mut self) {
         let mut is_build_delay = false;
         let mut is_fire_delay = false;
+        let mut is_probe_speed = false;
         for tech in self.state_handle.get().techs.iter() {
             match tech {
+                Techs::PROBE_SPEED => {
+                    is_probe_speed = true;
+                }
                 Techs::FACTORY_BUILD_DELAY => {
                     is_build_delay = true;
                 }
@@ -622,6 +1451,16 @@ impl Player {
                 );
             }
         }
+        if is_probe_speed {
+            // existing probes cached their move direction and travel
+            // delay (see Probe::set_target_manually) -> recompute them
+            let speed = self.get_probe_speed();
+            for factory in self.factories.iter_mut() {
+                for probe in factory.iter_mut_probes() {
+                    probe.set_speed(speed);
+                }
+            }
+        }
         if is_fire_delay {
             for turret in self.turrets.iter_mut() {
                 turret.set_fire_delay(
@@ -641,6 +1480,19 @@ impl Player {
     }
 
     /// run function
+    /// Advance the factories of the player of one frame, without
+    /// modifying the map (see `Factory::advance`) \
+    /// Independent of the other players, the rest of the frame is
+    /// resolved on the next call to `run` (see `GameConfig.parallel`)
+    pub fn advance(&mut self, map: &Map, dt: f64, n_probes: usize) {
+        // extract factories for iteration
+        let mut factories: Vec<Factory> = self.factories.drain(..).collect();
+        for factory in factories.iter_mut() {
+            factory.advance(self, map, dt, n_probes);
+        }
+        self.factories = factories;
+    }
+
     pub fn run(
         &mut self,
         ctx: &mut FrameContext,
@@ -650,6 +1502,8 @@ impl Player {
 
         let probe_price = self.get_probe_price();
 
+        self.reinforce_buildings(ctx.map);
+
         // extract factories for iteration
         let mut factories: Vec<Factory> = self.factories.drain(..).collect();
 
@@ -665,12 +1519,30 @@ impl Player {
 
                 // create new probes
                 for probe_state in state.probes.iter_mut() {
-                    if probe_state.just_created() && self.money >= probe_price {
-                        if let Some(probe) = self.create_probe(probe_state, ctx) {
-                            is_money_change = true;
-                            self.money -= probe_price;
-                            factory.attach_probe(probe);
-                        }
+                    if probe_state.death.is_some() {
+                        self.stats.probes_lost += 1;
+                    }
+                    if !probe_state.just_created() {
+                        continue;
+                    }
+                    if self.money < probe_price {
+                        // don't waste the production delay
+                        factory.retry_production();
+                        continue;
+                    }
+                    if factory.is_throttled(ctx.n_probes) {
+                        // the factories of all the players advanced in
+                        // parallel, on the number of probes at the start
+                        // of the frame (see `GameConfig.parallel`)
+                        factory.retry_production();
+                        continue;
+                    }
+                    if let Some(probe) = self.create_probe(probe_state, factory.get_rally(), ctx) {
+                        ctx.n_probes += 1;
+                        self.stats.probes_produced += 1;
+                        is_money_change = true;
+                        self.money -= probe_price;
+                        factory.attach_probe(probe);
                     }
                 }
                 // remove probe states that could not be created
@@ -692,6 +1564,8 @@ impl Player {
             self.factories.remove(*idx);
         }
 
+        self.prune_groups();
+
         // extract turrets for iteration
         let mut turrets: Vec<Turret> = self.turrets.drain(..).collect();
 
@@ -727,3 +1601,652 @@ impl Player {
         self.state_handle.flush(&self.id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::tests::test_config;
+    use crate::game::ProbeGrid;
+
+    /// Update the money of the players over `n_frames` frames of 0.25 sec,
+    /// the global income tick occurs every 4 frames (1 sec) \
+    /// Return the frames on which the money of each player changed
+    fn get_income_frames(
+        config: &GameConfig,
+        players: &mut Vec<Player>,
+        n_frames: usize,
+    ) -> Vec<Vec<usize>> {
+        let mut map = Map::new(config);
+        let mut frames = vec![Vec::new(); players.len()];
+        for frame in 0..n_frames {
+            let mut ctx = FrameContext {
+                dt: 0.25,
+                is_income_tick: frame % 4 == 3,
+                config: config,
+                map: &mut map,
+                n_probes: 0,
+                probe_grid: ProbeGrid::new(1.0, 0.0),
+                events: Vec::new(),
+                is_advanced: false,
+            };
+            for (i, player) in players.iter_mut().enumerate() {
+                let money = player.get_money();
+                player.update_money(&mut ctx);
+                if player.get_money() != money {
+                    frames[i].push(frame);
+                }
+            }
+        }
+        frames
+    }
+
+    /// Run the player (without opponents) for one frame of `dt` seconds
+    fn run_player(
+        player: &mut Player,
+        config: &GameConfig,
+        map: &mut Map,
+        dt: f64,
+    ) -> Option<PlayerState> {
+        let mut ctx = FrameContext {
+            dt: dt,
+            is_income_tick: false,
+            config: config,
+            map: map,
+            n_probes: player.get_num_probes(),
+            probe_grid: ProbeGrid::new(1.0, 0.0),
+            events: Vec::new(),
+            is_advanced: false,
+        };
+        player.run(&mut ctx, Vec::new())
+    }
+
+    /// Wait for the (own) income delay of the player and update its money \
+    /// Return the events of the update
+    fn update_income(player: &mut Player, config: &GameConfig, map: &mut Map) -> Vec<GameEvent> {
+        let mut ctx = FrameContext {
+            dt: 1.0,
+            is_income_tick: false,
+            config: config,
+            map: map,
+            n_probes: player.get_num_probes(),
+            probe_grid: ProbeGrid::new(1.0, 0.0),
+            events: Vec::new(),
+            is_advanced: false,
+        };
+        player.update_money(&mut ctx);
+        ctx.events
+    }
+
+    /// Return two players, whose own income schedules are staggered
+    fn create_staggered_players(config: &GameConfig) -> Vec<Player> {
+        let mut late = Player::new(2, config);
+        late.delayer_income.wait(0.5);
+        vec![Player::new(1, config), late]
+    }
+
+    #[test]
+    fn effective_stats_without_techs_match_config() {
+        let config = test_config();
+        let player = Player::new(1, &config);
+        let stats = player.get_effective_stats(&config);
+        assert_eq!(stats.probe_speed, config.probe_speed);
+        assert_eq!(stats.probe_hp, config.probe_hp);
+        assert_eq!(stats.factory_max_probe, config.factory_max_probe);
+        assert_eq!(stats.factory_probe_price, config.probe_price);
+        assert_eq!(stats.turret_fire_delay, config.turret_fire_delay);
+    }
+
+    #[test]
+    fn effective_stats_apply_acquired_techs() {
+        let mut config = test_config();
+        config.initial_money = 1000.0;
+        let mut player = Player::new(1, &config);
+        player.acquire_tech(Techs::PROBE_HP).unwrap();
+        player.acquire_tech(Techs::FACTORY_PROBE_PRICE).unwrap();
+        player.acquire_tech(Techs::TURRET_DAMAGE).unwrap();
+
+        let stats = player.get_effective_stats(&config);
+        assert_eq!(
+            stats.probe_hp,
+            config.probe_hp + config.tech_probe_hp_increase
+        );
+        assert_eq!(
+            stats.factory_probe_price,
+            config.probe_price - config.tech_factory_probe_price_decrease
+        );
+        assert_eq!(
+            stats.turret_damage,
+            config.turret_damage + config.tech_turret_damage_increase
+        );
+        // not acquired
+        assert_eq!(stats.probe_speed, config.probe_speed);
+        assert_eq!(stats.turret_scope, config.turret_scope);
+    }
+
+    #[test]
+    fn effective_stats_probe_speed_tech() {
+        let config = test_config();
+        let mut player = Player::new(1, &config);
+        player.acquire_tech(Techs::PROBE_SPEED).unwrap();
+        let stats = player.get_effective_stats(&config);
+        assert_eq!(
+            stats.probe_speed,
+            config.probe_speed + config.tech_probe_speed_increase
+        );
+    }
+
+    #[test]
+    fn aligned_income_ticks_on_same_frame() {
+        let mut config = test_config();
+        config.base_income = 1.0;
+        config.align_income_ticks = true;
+        let mut players = create_staggered_players(&config);
+        let frames = get_income_frames(&config, &mut players, 12);
+        assert_eq!(frames[0], vec![3, 7, 11]);
+        assert_eq!(frames[1], frames[0]);
+    }
+
+    #[test]
+    fn own_income_ticks_drift_apart() {
+        let mut config = test_config();
+        config.base_income = 1.0;
+        let mut players = create_staggered_players(&config);
+        let frames = get_income_frames(&config, &mut players, 12);
+        assert_eq!(frames[0], vec![3, 7, 11]);
+        assert_eq!(frames[1], vec![1, 5, 9]);
+    }
+
+    #[test]
+    fn production_blocked_by_money_is_retried() {
+        let mut config = test_config();
+        config.initial_money = 0.0;
+        config.factory_expansion_size = 0;
+        let mut map = Map::new(&config);
+        let mut player = Player::new(1, &config);
+        let pos = Coord::new(5, 5);
+        map.claim_tile(player.id, &pos, 2);
+        player.create_factory(pos, &mut map, &config);
+
+        // expansion (0.5 sec) and well past the produce delay
+        for _ in 0..20 {
+            run_player(&mut player, &config, &mut map, 0.25);
+        }
+        assert_eq!(player.get_num_probes(), 0);
+
+        // produced on the next frame, without waiting the produce delay
+        player.add_money(10.0);
+        run_player(&mut player, &config, &mut map, 0.25);
+        assert_eq!(player.get_num_probes(), 1);
+        assert_eq!(player.get_money(), 10.0 - config.probe_price);
+
+        // then the produce delay applies again
+        for _ in 0..3 {
+            run_player(&mut player, &config, &mut map, 0.25);
+        }
+        assert_eq!(player.get_num_probes(), 1);
+        run_player(&mut player, &config, &mut map, 0.25);
+        assert_eq!(player.get_num_probes(), 2);
+    }
+
+    #[test]
+    fn income_only_from_developed_tiles() {
+        let mut config = test_config();
+        config.income_rate = 1.0;
+        config.income_occupation_min = 5;
+        let mut map = Map::new(&config);
+        let mut undeveloped = Player::new(1, &config);
+        let mut developed = Player::new(2, &config);
+        for y in 0..3 {
+            map.claim_tile(1, &Coord::new(2, y), 4);
+            map.claim_tile(2, &Coord::new(8, y), 5);
+        }
+
+        update_income(&mut undeveloped, &config, &mut map);
+        update_income(&mut developed, &config, &mut map);
+        assert_eq!(undeveloped.income_breakdown.occupation, 0.0);
+        assert_eq!(undeveloped.get_money(), config.initial_money);
+        assert_eq!(developed.income_breakdown.occupation, 15.0);
+        assert_eq!(developed.get_money(), config.initial_money + 15.0);
+    }
+
+    #[test]
+    fn negative_income_event_on_transition() {
+        let mut config = test_config();
+        config.income_rate = 1.0;
+        config.turret_maintenance_costs = 4.0;
+        let mut map = Map::new(&config);
+        let mut player = Player::new(1, &config);
+        for y in 0..5 {
+            map.claim_tile(1, &Coord::new(2, y), 2);
+        }
+
+        // income: 10 - 4 * n_turrets
+        let mut n_events = Vec::new();
+        for y in 0..5 {
+            player.create_turret(Coord::new(2, y), &mut map, &config);
+            let events = update_income(&mut player, &config, &mut map);
+            n_events.push(
+                events
+                    .iter()
+                    .filter(|e| matches!(e, GameEvent::IncomeNegative { .. }))
+                    .count(),
+            );
+        }
+        assert_eq!(n_events, vec![0, 0, 1, 0, 0]);
+        assert_eq!(player.last_income, -10.0);
+    }
+
+    #[test]
+    fn default_attack_policy_applies_to_new_probes() {
+        let mut config = test_config();
+        config.factory_expansion_size = 0;
+        let mut map = Map::new(&config);
+        let mut player = Player::new(1, &config);
+        let pos = Coord::new(5, 5);
+        map.claim_tile(player.id, &pos, 2);
+        map.claim_tile(2, &Coord::new(12, 5), 2);
+        player.create_factory(pos, &mut map, &config);
+
+        let mut run_until_n_probes = |player: &mut Player, n_probes: usize| {
+            for _ in 0..100 {
+                if player.get_num_probes() == n_probes {
+                    return;
+                }
+                run_player(player, &config, &mut map, 0.1);
+            }
+            panic!("No probe produced");
+        };
+        run_until_n_probes(&mut player, 1);
+        player
+            .set_default_probe_policy(ProbePolicy::Attack)
+            .unwrap();
+        run_until_n_probes(&mut player, 2);
+
+        let probes: Vec<&Probe> = player.factories[0].iter_probes().collect();
+        // existing probes are unaffected
+        assert!(!probes[0].is_attacking());
+        assert!(probes[1].is_attacking());
+        let target = probes[1].get_complete_state().target.unwrap();
+        assert_eq!(target, Coord::new(12, 5));
+    }
+
+    #[test]
+    fn enclosed_factory_probes_idle() {
+        let mut config = test_config();
+        config.dim = Coord::new(3, 3);
+        config.factory_expansion_size = 0;
+        config.probe_idle_without_target = true;
+        let mut map = Map::new(&config);
+        let mut player = Player::new(1, &config);
+        for x in 0..3 {
+            for y in 0..3 {
+                map.claim_tile(player.id, &Coord::new(x, y), config.max_occupation);
+            }
+        }
+        player.create_factory(Coord::new(1, 1), &mut map, &config);
+        for _ in 0..20 {
+            run_player(&mut player, &config, &mut map, 0.1);
+        }
+        assert_eq!(player.get_num_probes(), 1);
+        map.state_handle.flush(&());
+
+        // no claim on the factory tile
+        for _ in 0..20 {
+            run_player(&mut player, &config, &mut map, 0.1);
+            assert!(map.state_handle.flush(&()).is_none());
+        }
+        let probe = player.factories[0].iter_probes().next().unwrap();
+        let state = probe.get_complete_state();
+        assert!(matches!(state.policy, Some(ProbePolicy::Idle)));
+        assert_eq!(state.pos, Some(Point::new(1.0, 1.0)));
+
+        // a tile to farm again
+        map.claim_tile(2, &Coord::new(0, 0), 3);
+        for _ in 0..5 {
+            run_player(&mut player, &config, &mut map, 0.1);
+        }
+        let probe = player.factories[0].iter_probes().next().unwrap();
+        assert!(probe.is_farming());
+        assert_eq!(probe.get_complete_state().target, Some(Coord::new(0, 0)));
+    }
+
+    #[test]
+    fn building_near_opponent_is_surcharged() {
+        let mut config = test_config();
+        config.building_surcharge = 4.0;
+        config.building_surcharge_distance = 2;
+        let mut map = Map::new(&config);
+        let mut player = Player::new(1, &config);
+        for x in 0..8 {
+            map.claim_tile(player.id, &Coord::new(x, 5), 2);
+        }
+        map.claim_tile(2, &Coord::new(8, 5), 2);
+
+        let core = Coord::new(1, 5);
+        let border = Coord::new(7, 5);
+        assert_eq!(player.get_factory_price(&core, &map), config.factory_price);
+        assert_eq!(
+            player.get_factory_price(&border, &map),
+            config.factory_price + 4.0
+        );
+        assert_eq!(
+            player.get_turret_price(&border, &map),
+            config.turret_price + 4.0
+        );
+
+        // the surcharge is paid when building
+        let money = player.get_money();
+        assert!(player.build_factory(border, &mut map, &config));
+        assert_eq!(player.get_money(), money - config.factory_price - 4.0);
+    }
+
+    #[test]
+    fn probes_reinforce_threatened_factory() {
+        let mut config = test_config();
+        config.factory_expansion_size = 0;
+        config.probe_reinforce_distance = 5.0;
+        let mut map = Map::new(&config);
+        let mut player = Player::new(1, &config);
+        let pos = Coord::new(5, 5);
+        map.claim_tile(player.id, &pos, config.max_occupation);
+        player.create_factory(pos.clone(), &mut map, &config);
+        while player.get_num_probes() == 0 {
+            run_player(&mut player, &config, &mut map, 0.1);
+        }
+        run_player(&mut player, &config, &mut map, 0.1);
+        let probe = player.factories[0].iter_probes().next().unwrap();
+        assert!(probe.is_farming());
+        assert_ne!(probe.get_complete_state().target, Some(pos.clone()));
+
+        // an opponent starts eroding the factory tile
+        map.claim_tile(2, &pos, 1);
+        run_player(&mut player, &config, &mut map, 0.1);
+        let probe = player.factories[0].iter_probes().next().unwrap();
+        assert_eq!(probe.get_complete_state().target, Some(pos));
+    }
+
+    #[test]
+    fn tech_names_round_trip() {
+        let techs = [
+            Techs::PROBE_EXPLOSION_INTENSITY,
+            Techs::PROBE_CLAIM_INTENSITY,
+            Techs::PROBE_HP,
+            Techs::PROBE_SPEED,
+            Techs::FACTORY_BUILD_DELAY,
+            Techs::FACTORY_PROBE_PRICE,
+            Techs::FACTORY_MAX_PROBE,
+            Techs::TURRET_SCOPE,
+            Techs::TURRET_FIRE_DELAY,
+            Techs::TURRET_MAINTENANCE_COSTS,
+            Techs::TURRET_DAMAGE,
+        ];
+        for tech in techs {
+            assert_eq!(Techs::from_string(&format!("{:?}", tech)), Ok(tech));
+        }
+        assert_eq!(
+            Techs::from_string("PROBE_ARMOR"),
+            Err(String::from("Invalid tech name: PROBE_ARMOR"))
+        );
+    }
+
+    #[test]
+    fn faction_restricts_techs_and_prices() {
+        let config = test_config();
+        let mut restricted = Player::new(1, &config);
+        restricted.set_faction(FactionConfig {
+            techs: vec![Techs::PROBE_HP],
+            tech_price_factor: 1.0,
+        });
+        let mut other = Player::new(2, &config);
+        other.set_faction(FactionConfig {
+            techs: vec![Techs::TURRET_SCOPE],
+            tech_price_factor: 2.0,
+        });
+
+        assert_eq!(
+            restricted.acquire_tech(Techs::TURRET_SCOPE),
+            Err(String::from("Technology not available for the faction."))
+        );
+        assert_eq!(restricted.get_money(), 100.0);
+        assert_eq!(other.acquire_tech(Techs::TURRET_SCOPE), Ok(()));
+        assert_eq!(other.get_money(), 80.0);
+    }
+
+    #[test]
+    fn techs_are_independent_without_exclusion_groups() {
+        let mut config = test_config();
+        config.initial_money = 1000.0;
+        config.tech_exclusion_groups = Vec::new();
+        let mut player = Player::new(1, &config);
+        for tech in [
+            Techs::PROBE_HP,
+            Techs::PROBE_SPEED,
+            Techs::PROBE_CLAIM_INTENSITY,
+        ] {
+            assert_eq!(player.acquire_tech(tech), Ok(()));
+        }
+    }
+
+    #[test]
+    fn default_exclusion_groups_match_categories() {
+        let mut config = test_config();
+        config.initial_money = 1000.0;
+        let mut player = Player::new(1, &config);
+        assert_eq!(player.acquire_tech(Techs::PROBE_HP), Ok(()));
+        assert_eq!(player.acquire_tech(Techs::FACTORY_MAX_PROBE), Ok(()));
+        assert_eq!(player.acquire_tech(Techs::TURRET_DAMAGE), Ok(()));
+        let error = Err(String::from(
+            "Can't acquire multiple technologies of same category.",
+        ));
+        assert_eq!(player.acquire_tech(Techs::PROBE_SPEED), error);
+        assert_eq!(player.acquire_tech(Techs::FACTORY_BUILD_DELAY), error);
+        assert_eq!(player.acquire_tech(Techs::TURRET_SCOPE), error);
+    }
+
+    #[test]
+    fn stats_series_stay_aligned() {
+        let config = test_config();
+        let mut map = Map::new(&config);
+        let mut player = Player::new(1, &config);
+        for _ in 0..50 {
+            update_income(&mut player, &config, &mut map);
+        }
+        let stats = player.get_stats(1.0);
+        assert_eq!(stats.time.len(), 50);
+        for len in [
+            stats.money.len(),
+            stats.occupation.len(),
+            stats.factories.len(),
+            stats.turrets.len(),
+            stats.probes.len(),
+        ] {
+            assert_eq!(len, stats.time.len());
+        }
+        assert!(stats.time.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(stats.time[49], 50.0);
+    }
+
+    #[test]
+    fn bonus_tile_income_follows_ownership() {
+        let mut config = test_config();
+        config.bonus_tiles = vec![(Coord::new(5, 5), 3.0)];
+        let mut map = Map::new(&config);
+        let mut player = Player::new(1, &config);
+        let bonus = Coord::new(5, 5);
+
+        update_income(&mut player, &config, &mut map);
+        assert_eq!(player.last_income, 0.0);
+
+        // capture the bonus tile (and a regular one)
+        map.claim_tile(1, &bonus, 2);
+        map.claim_tile(1, &Coord::new(6, 5), 2);
+        update_income(&mut player, &config, &mut map);
+        assert_eq!(player.last_income, 3.0);
+
+        // lose the bonus tile
+        map.claim_tile(2, &bonus, 2);
+        map.claim_tile(2, &bonus, 2);
+        assert!(map.get_tile(&bonus).unwrap().is_owned_by(2));
+        update_income(&mut player, &config, &mut map);
+        assert_eq!(player.last_income, 0.0);
+        let opponent = Player::new(2, &config);
+        assert_eq!(map.get_player_bonus_income(&opponent), 3.0);
+    }
+
+    #[test]
+    fn income_breakdown_details_each_source() {
+        let mut config = test_config();
+        config.base_income = 2.0;
+        config.income_rate = 1.0;
+        config.factory_maintenance_costs = 1.0;
+        config.probe_maintenance_costs = 0.5;
+        config.turret_maintenance_costs = 4.0;
+        config.occupation_upkeep_rate = 0.1;
+        let mut map = Map::new(&config);
+        let mut player = Player::new(1, &config);
+        for x in 0..10 {
+            map.claim_tile(1, &Coord::new(x, 2), 2);
+        }
+        player.create_factory(Coord::new(1, 2), &mut map, &config);
+        for _ in 0..2 {
+            let probe = Probe::new(&config, &player, Point::new(1.0, 2.0));
+            player.factories[0].attach_probe(probe);
+        }
+        player.create_turret(Coord::new(3, 2), &mut map, &config);
+        player.state_handle.flush(&player.id);
+
+        let money = player.get_money();
+        update_income(&mut player, &config, &mut map);
+        let breakdown = player.income_breakdown.clone();
+        assert_eq!(breakdown.base, 2.0);
+        assert_eq!(breakdown.occupation, 20.0);
+        assert_eq!(breakdown.factory_maintenance, -1.0);
+        assert_eq!(breakdown.probe_maintenance, -1.0);
+        assert_eq!(breakdown.turret_maintenance, -4.0);
+        assert_eq!(breakdown.occupation_upkeep, -2.0);
+        assert_eq!(breakdown.total(), 14.0);
+        assert_eq!(player.get_money(), money + 14.0);
+
+        let state = player.state_handle.flush(&player.id).unwrap();
+        assert_eq!(state.income, Some(14.0));
+        assert_eq!(state.income_breakdown.unwrap().total(), 14.0);
+    }
+
+    #[test]
+    fn dead_probes_are_pruned_from_groups() {
+        let mut config = test_config();
+        config.factory_max_probe = 0;
+        let mut map = Map::new(&config);
+        let mut player = Player::new(1, &config);
+        map.claim_tile(1, &Coord::new(5, 5), 2);
+        player.create_factory(Coord::new(5, 5), &mut map, &config);
+        for _ in 0..3 {
+            let probe = Probe::new(&config, &player, Point::new(5.0, 5.0));
+            player.factories[0].attach_probe(probe);
+        }
+        let ids: Vec<u128> = player.iter_mut_probes().map(|p| p.id).collect();
+
+        let group_id = player.create_group(String::from("squad"));
+        assert_eq!(
+            player.assign_probes(group_id, vec![ids[0], ids[1], 0]),
+            Ok(2)
+        );
+        assert_eq!(player.assign_probes(group_id, vec![ids[0]]), Ok(0));
+        assert!(player.assign_probes(0, vec![ids[2]]).is_err());
+        player.state_handle.flush(&player.id);
+
+        // kill a grouped probe
+        player
+            .get_mut_probe_by_id(ids[0])
+            .unwrap()
+            .inflict_damage(10);
+        let state = run_player(&mut player, &config, &mut map, 0.1).unwrap();
+        assert_eq!(player.get_group_probe_ids(group_id), Ok(vec![ids[1]]));
+        let groups = state.groups.unwrap();
+        assert_eq!(groups[0].probe_ids, HashSet::from([ids[1]]));
+
+        // kill a probe out of the group: the groups are unchanged
+        player
+            .get_mut_probe_by_id(ids[2])
+            .unwrap()
+            .inflict_damage(10);
+        let state = run_player(&mut player, &config, &mut map, 0.1);
+        assert_eq!(player.get_group_probe_ids(group_id), Ok(vec![ids[1]]));
+        assert!(state.map_or(true, |s| s.groups.is_none()));
+    }
+
+    #[test]
+    fn refund_reverts_build_delay() {
+        let config = test_config();
+        let mut map = Map::new(&config);
+        let mut player = Player::new(1, &config);
+        map.claim_tile(1, &Coord::new(5, 5), 2);
+        player.create_factory(Coord::new(5, 5), &mut map, &config);
+        assert_eq!(player.factories[0].get_build_probe_delay(), 1.0);
+
+        player.acquire_tech(Techs::FACTORY_BUILD_DELAY).unwrap();
+        run_player(&mut player, &config, &mut map, 0.1);
+        assert_eq!(player.factories[0].get_build_probe_delay(), 0.5);
+
+        let money = player.get_money();
+        assert_eq!(player.refund_tech(Techs::FACTORY_BUILD_DELAY), Ok(()));
+        assert_eq!(player.factories[0].get_build_probe_delay(), 1.0);
+        assert_eq!(player.get_money(), money + 5.0);
+        assert!(player.refund_tech(Techs::FACTORY_BUILD_DELAY).is_err());
+    }
+
+    #[test]
+    fn refund_reverts_probe_hp() {
+        let config = test_config();
+        let mut map = Map::new(&config);
+        let mut player = Player::new(1, &config);
+        let mut factory = Factory::new(&config, Coord::new(5, 5));
+        factory.attach_probe(Probe::new(&config, &player, Point::new(5.0, 5.0)));
+        player.acquire_tech(Techs::PROBE_HP).unwrap();
+        factory.attach_probe(Probe::new(&config, &player, Point::new(5.0, 5.0)));
+        factory.attach_probe(Probe::new(&config, &player, Point::new(5.0, 5.0)));
+        player.factories.push(factory);
+        let ids: Vec<u128> = player.iter_mut_probes().map(|p| p.id).collect();
+        player
+            .get_mut_probe_by_id(ids[2])
+            .unwrap()
+            .inflict_damage(3);
+        run_player(&mut player, &config, &mut map, 0.1);
+
+        player.refund_tech(Techs::PROBE_HP).unwrap();
+        let state = run_player(&mut player, &config, &mut map, 0.1).unwrap();
+        let get_hps = |id: u128| {
+            let probe = state.factories[0].probes.iter().find(|p| p.id == id);
+            probe.map_or((None, None), |p| (p.hp, p.max_hp))
+        };
+        // created before the tech: unaffected
+        assert_eq!(get_hps(ids[0]), (None, None));
+        // created with the tech: back to the base hp
+        assert_eq!(get_hps(ids[1]), (Some(2), Some(2)));
+        // damaged below the base hp: keep its hp
+        assert_eq!(get_hps(ids[2]), (None, Some(2)));
+        let hps: Vec<u32> = player.iter_mut_probes().map(|p| p.get_hp()).collect();
+        assert_eq!(hps, vec![2, 2, 1]);
+    }
+
+    #[test]
+    fn over_expanded_player_income_is_negative() {
+        let mut config = test_config();
+        config.base_income = 2.0;
+        config.occupation_upkeep_rate = 0.1;
+        for (n_tiles, is_positive) in [(5, true), (50, false)] {
+            let mut map = Map::new(&config);
+            let mut player = Player::new(1, &config);
+            for i in 0..n_tiles {
+                map.claim_tile(1, &Coord::new(i % 20, i / 20), 2);
+            }
+
+            let money = player.get_money();
+            update_income(&mut player, &config, &mut map);
+            let total = player.income_breakdown.total();
+            assert_eq!(total, 2.0 - 0.2 * n_tiles as f64);
+            assert_eq!(total > 0.0, is_positive);
+            assert_eq!(player.get_money() > money, is_positive);
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 use super::core::{self, FrameContext};
 use super::core::{Coord, Point};
 use super::player::Player;
@@ -5,36 +7,97 @@ use super::{
     geometry, Delayer, GameConfig, Identifiable, Map, State, StateHandler, Techs, NOT_IDENTIFIABLE,
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum ProbePolicy {
     Farm,
     Attack,
     Claim,
+    Hold,
+    /// No tile to farm, wait for one (see `GameConfig.probe_idle_without_target`)
+    Idle,
+    /// Hold the position and guard the tile against opponents claims
+    /// (see `GameConfig.probe_picket_intensity_factor`)
+    Picket,
+    /// Move back and forth between two points, without claiming
+    Patrol {
+        a: Point,
+        b: Point,
+    },
+    /// Move to an opponent tile, then claim it repeatedly (staying alive)
+    /// until it is owned by the player, then switch to Farm policy
+    Siege,
 }
 
-#[derive(Clone, Debug)]
+impl ProbePolicy {
+    /// Create an instance from a string \
+    /// Return an error in case the `string` is invalid
+    pub fn from_string(string: &str) -> Result<Self, String> {
+        match string {
+            "Farm" => Ok(ProbePolicy::Farm),
+            "Attack" => Ok(ProbePolicy::Attack),
+            "Claim" => Ok(ProbePolicy::Claim),
+            "Hold" => Ok(ProbePolicy::Hold),
+            "Idle" => Ok(ProbePolicy::Idle),
+            "Picket" => Ok(ProbePolicy::Picket),
+            "Siege" => Ok(ProbePolicy::Siege),
+            _ => Err(format!("Invalid probe policy: {}", string)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum ProbeDeathCause {
     Exploded,
     Shot,
     Scrapped,
+    Expired,
+    OutOfRange,
+}
+
+/// Movement of the probe during the current frame,
+/// computed by `Probe::advance`, resolved by `Probe::run`
+#[derive(Clone, Serialize, Deserialize)]
+enum Movement {
+    /// The probe doesn't move (its policy isn't a moving one)
+    Still,
+    Moving,
+    /// The probe reached its target
+    Arrived,
+    /// The probe travelled its maximal range (see `is_out_of_range`)
+    OutOfRange,
+    /// The probe reached the end of its lifespan
+    Expired,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 struct ProbeConfig {
     speed: f64,
+    lifespan: f64,
+    max_range: f64,
     claim_delay: f64,
     claim_intensity: u32,
     explosion_intensity: u32,
+    explosion_reinforce: bool,
+    idle_without_target: bool,
+    hp_regen_per_sec: f64,
     tech_explosion_intensity_increase: u32,
     tech_claim_intensity_increase: u32,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ProbeState {
     pub id: u128,
     pub death: Option<ProbeDeathCause>,
     pub pos: Option<Point>,
     pub target: Option<Coord>,
     pub policy: Option<ProbePolicy>,
+    /// Specified when the hp change (shot or regenerated)
+    pub hp: Option<u32>,
+    /// Only specified in the complete state
+    pub max_hp: Option<u32>,
+    /// Distance travelled since produced (see `GameConfig.probe_max_range`),
+    /// only specified in the complete state
+    pub travelled: Option<f64>,
     /// Specify that the probe should be created
     /// Internal to rust implementation
     just_created: bool,
@@ -56,6 +119,9 @@ impl State for ProbeState {
             pos: None,
             target: None,
             policy: None,
+            hp: None,
+            max_hp: None,
+            travelled: None,
             just_created: false,
         }
     }
@@ -70,6 +136,18 @@ impl State for ProbeState {
         if let Some(target) = state.target {
             self.target = Some(target);
         }
+        if let Some(policy) = state.policy {
+            self.policy = Some(policy);
+        }
+        if let Some(hp) = state.hp {
+            self.hp = Some(hp);
+        }
+        if let Some(max_hp) = state.max_hp {
+            self.max_hp = Some(max_hp);
+        }
+        if let Some(travelled) = state.travelled {
+            self.travelled = Some(travelled);
+        }
     }
 }
 
@@ -89,11 +167,15 @@ impl ProbeState {
             pos: Some(pos),
             target: None,
             policy: Some(ProbePolicy::Farm),
+            hp: None,
+            max_hp: None,
+            travelled: None,
             just_created: true,
         }
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Probe {
     pub id: u128,
     config: ProbeConfig,
@@ -101,6 +183,11 @@ pub struct Probe {
     policy: ProbePolicy,
     pub pos: Point,
     hp: u32,
+    /// Hitpoints of the probe when produced,
+    /// taking the `PROBE_HP` tech into account
+    max_hp: u32,
+    /// Fraction of hitpoint regenerated, not yet applied to `hp`
+    hp_regen: f64,
     /// store target as Point for optimization
     /// but target is always a coordinate
     target: Point,
@@ -110,6 +197,19 @@ pub struct Probe {
     delayer_travel: Delayer,
     /// Delay to wait in order to claim a tile
     delayer_claim: Delayer,
+    /// Delay to wait before the probe expires
+    delayer_lifespan: Delayer,
+    /// Distance travelled since the probe was produced
+    /// (see `is_out_of_range`)
+    travelled: f64,
+    /// Region (center, radius) where the farm targets are searched
+    /// in priority (see `Map::get_probe_farm_target`)
+    farm_region: Option<(Coord, u32)>,
+    /// Offset of the reported position, to spread apart the probes
+    /// sharing a tile (see `GameConfig.probe_separation`)
+    separation: Point,
+    /// Movement of the current frame (see `advance`)
+    movement: Movement,
 }
 
 impl Probe {
@@ -124,28 +224,72 @@ impl Probe {
         if player.has_tech(&Techs::PROBE_HP) {
             hp += config.tech_probe_hp_increase;
         }
+        let speed = player.get_probe_speed();
 
         Probe {
             id: id,
             config: ProbeConfig {
-                speed: config.probe_speed,
+                speed: speed,
+                lifespan: config.probe_lifespan,
+                max_range: config.probe_max_range,
                 claim_delay: config.probe_claim_delay,
                 claim_intensity: config.probe_claim_intensity,
                 explosion_intensity: config.probe_explosion_intensity,
+                explosion_reinforce: config.probe_explosion_reinforce,
+                idle_without_target: config.probe_idle_without_target,
+                hp_regen_per_sec: config.probe_hp_regen_per_sec,
                 tech_explosion_intensity_increase: config.tech_probe_explosion_intensity_increase,
                 tech_claim_intensity_increase: config.tech_probe_claim_intensity_increase,
             },
             state_handle: StateHandler::new(&id),
             policy: ProbePolicy::Farm,
             hp: hp,
+            max_hp: hp,
+            hp_regen: 0.0,
             target: pos.clone(),
+            travelled: 0.0,
+            farm_region: None,
             pos: pos,
             move_dir: Point::new(0.0, 0.0),
             delayer_travel: Delayer::new(0.0),
             delayer_claim: Delayer::new(config.probe_claim_delay),
+            delayer_lifespan: Delayer::new(config.probe_lifespan),
+            separation: Point::new(0.0, 0.0),
+            movement: Movement::Still,
         }
     }
 
+    /// Create a Probe instance from its (complete) state \
+    /// Return an error in case the state has no position
+    pub fn from_state(
+        config: &GameConfig,
+        player: &Player,
+        state: &ProbeState,
+    ) -> Result<Probe, String> {
+        let pos = match &state.pos {
+            Some(pos) => pos.clone(),
+            None => {
+                return Err(String::from("Missing probe position"));
+            }
+        };
+        let mut probe = Probe::new(config, player, pos);
+        probe.id = state.id;
+        probe.state_handle = StateHandler::new(&state.id);
+        if let Some(policy) = &state.policy {
+            probe.policy = policy.clone();
+        }
+        if let Some(target) = &state.target {
+            probe.set_target_manually(target.as_point());
+        }
+        if let Some(hp) = state.hp {
+            probe.hp = u32::min(hp, probe.max_hp);
+        }
+        if let Some(travelled) = state.travelled {
+            probe.travelled = travelled;
+        }
+        Ok(probe)
+    }
+
     pub fn get_coord(&self) -> Coord {
         self.pos.as_coord()
     }
@@ -158,10 +302,57 @@ impl Probe {
             pos: Some(self.pos.clone()),
             target: Some(self.target.as_coord()),
             policy: Some(self.policy.clone()),
+            hp: Some(self.hp),
+            max_hp: Some(self.max_hp),
+            travelled: Some(self.travelled),
             just_created: false,
         }
     }
 
+    /// Return if the probe is moving toward its target
+    pub fn is_moving(&self) -> bool {
+        matches!(
+            self.policy,
+            ProbePolicy::Farm
+                | ProbePolicy::Attack
+                | ProbePolicy::Patrol { .. }
+                | ProbePolicy::Siege
+        )
+    }
+
+    /// Set the offset of the reported position, update state if changed
+    /// or if the position (without offset) was reported during the frame \
+    /// Note: the position itself is unchanged (as the claimed tile)
+    pub fn set_separation(&mut self, separation: Point) {
+        if separation == self.separation && self.state_handle.get().pos.is_none() {
+            return;
+        }
+        let mut pos = self.pos.clone();
+        pos.add(&separation);
+        self.separation = separation;
+        self.state_handle.get_mut().pos = Some(pos);
+    }
+
+    /// Set the maximal hp of the probe, the hp are clamped accordingly \
+    /// Update current state if changed
+    pub fn set_max_hp(&mut self, max_hp: u32) {
+        if max_hp == self.max_hp {
+            return;
+        }
+        self.max_hp = max_hp;
+        self.state_handle.get_mut().max_hp = Some(max_hp);
+        if self.hp > max_hp {
+            self.hp = max_hp;
+            self.state_handle.get_mut().hp = Some(max_hp);
+        }
+    }
+
+    /// Clear the offset of the reported position, without updating
+    /// the state, as the moving probes report their position without it
+    pub fn clear_separation(&mut self) {
+        self.separation = Point::new(0.0, 0.0);
+    }
+
     /// Inflict damage (reduce probe's hp) \
     /// In case, the probe has no hp left: update state with death cause
     pub fn inflict_damage(&mut self, damage: u32) {
@@ -170,15 +361,49 @@ impl Probe {
             self.state_handle.get_mut().death = Some(ProbeDeathCause::Shot);
         } else {
             self.hp -= damage;
+            self.state_handle.get_mut().hp = Some(self.hp);
         }
     }
 
+    /// Regenerate hp while on a tile owned by the player and not attacking,
+    /// up to the maximal hp (see `GameConfig.probe_hp_regen_per_sec`) \
+    /// Update state when the hp change
+    fn regenerate(&mut self, player: &Player, map: &Map, dt: f64) {
+        if self.config.hp_regen_per_sec <= 0.0 || self.hp >= self.max_hp {
+            return;
+        }
+        if self.is_attacking() {
+            return;
+        }
+        match map.get_tile(&self.get_coord()) {
+            Some(tile) if tile.is_owned_by(player.id) => {}
+            _ => {
+                return;
+            }
+        }
+        self.hp_regen += self.config.hp_regen_per_sec * dt;
+        if self.hp_regen < 1.0 {
+            return;
+        }
+        let regen = self.hp_regen.floor();
+        self.hp_regen -= regen;
+        self.hp = u32::min(self.hp.saturating_add(regen as u32), self.max_hp);
+        if self.hp == self.max_hp {
+            self.hp_regen = 0.0;
+        }
+        self.state_handle.get_mut().hp = Some(self.hp);
+    }
+
     /// Select a new target and (if found) set the new target
-    /// (see `set_target_mannually` for details), update state
+    /// (see `set_target_mannually` for details), update state \
+    /// If not found and `idle_without_target`, switch to Idle policy
     fn select_farm_target(&mut self, player: &Player, map: &mut Map) {
         let target = match map.get_probe_farm_target(player, &self) {
             Some(target) => target,
             None => {
+                if self.config.idle_without_target {
+                    self.idle();
+                }
                 return;
             }
         };
@@ -229,6 +454,16 @@ impl Probe {
         self.move_dir.mul(self.config.speed);
     }
 
+    /// Set the speed of the probe \
+    /// In case the probe is moving, recompute the move direction
+    /// and travel delay toward its current target
+    pub fn set_speed(&mut self, speed: f64) {
+        self.config.speed = speed;
+        if self.is_moving() {
+            self.set_target_manually(self.target.clone());
+        }
+    }
+
     /// Set a new farm target \
     /// Update current state, move direction, travel delayer, policy
     pub fn set_farm_target(&mut self, target: Point) {
@@ -239,6 +474,20 @@ impl Probe {
         self.set_target_manually(target);
     }
 
+    /// Set the region where the farm targets are searched in priority,
+    /// if the radius is 0: clear the region
+    pub fn set_farm_region(&mut self, center: Coord, radius: u32) {
+        self.farm_region = match radius {
+            0 => None,
+            _ => Some((center, radius)),
+        };
+    }
+
+    /// Return the region where the farm targets are searched in priority
+    pub fn get_farm_region(&self) -> Option<&(Coord, u32)> {
+        self.farm_region.as_ref()
+    }
+
     /// Set a new attack target \
     /// Update current state, move direction, travel delayer, policy
     pub fn set_attack(&mut self, player_id: u128, map: &mut Map) {
@@ -248,38 +497,250 @@ impl Probe {
         self.select_attack_target(player_id, map);
     }
 
+    /// Hold the current position: the probe won't move,
+    /// claim or select a new target until given a new command \
+    /// Update current state, move direction, travel delayer, policy
+    pub fn hold(&mut self) {
+        self.state_handle.get_mut().pos = Some(self.pos.clone());
+        self.state_handle.get_mut().target = Some(self.pos.as_coord());
+        self.state_handle.get_mut().policy = Some(ProbePolicy::Hold);
+        self.policy = ProbePolicy::Hold;
+        self.set_target_manually(self.pos.clone());
+    }
+
+    /// Hold the current position as a picket
+    /// (same as `hold`, additionally guards the tile) \
+    /// Update current state, move direction, travel delayer, policy
+    pub fn picket(&mut self) {
+        self.hold();
+        self.state_handle.get_mut().policy = Some(ProbePolicy::Picket);
+        self.policy = ProbePolicy::Picket;
+    }
+
+    /// Patrol between `a` and `b`, starting by moving to `a` \
+    /// Update current state, move direction, travel delayer, policy
+    pub fn patrol(&mut self, a: Point, b: Point) {
+        self.state_handle.get_mut().pos = Some(self.pos.clone());
+        self.state_handle.get_mut().target = Some(a.as_coord());
+        self.set_target_manually(a.clone());
+        self.policy = ProbePolicy::Patrol { a: a, b: b };
+        self.state_handle.get_mut().policy = Some(self.policy.clone());
+    }
+
+    /// Siege the target: move to it, then claim it until it
+    /// is owned by the player (see `ProbePolicy::Siege`) \
+    /// Update current state, move direction, travel delayer, policy
+    pub fn siege(&mut self, target: Point) {
+        self.state_handle.get_mut().pos = Some(self.pos.clone());
+        self.state_handle.get_mut().target = Some(target.as_coord());
+        self.state_handle.get_mut().policy = Some(ProbePolicy::Siege);
+        self.policy = ProbePolicy::Siege;
+        self.delayer_claim.reset();
+        self.set_target_manually(target);
+    }
+
+    /// Once the target is reached, every `claim_delay`, claim the tile,
+    /// switch to Farm policy once the tile is owned by the player
+    fn handle_siege(&mut self, player: &Player, ctx: &mut FrameContext) {
+        if self.pos != self.target {
+            // stop on the target
+            self.pos = self.target.clone();
+            self.state_handle.get_mut().pos = Some(self.pos.clone());
+            self.set_target_manually(self.target.clone());
+        }
+        if !self.delayer_claim.wait(ctx.dt) {
+            return;
+        }
+        let coord = self.get_coord();
+        let is_owned = |map: &Map| match map.get_tile(&coord) {
+            Some(tile) => tile.is_owned_by(player.id),
+            None => true,
+        };
+        if !is_owned(ctx.map) {
+            let mut intensity = self.config.claim_intensity;
+            if player.has_tech(&Techs::PROBE_CLAIM_INTENSITY) {
+                intensity = intensity.saturating_add(self.config.tech_claim_intensity_increase);
+            }
+            ctx.map.claim_tile(player.id, &coord, intensity);
+        }
+        if is_owned(ctx.map) {
+            self.state_handle.get_mut().policy = Some(ProbePolicy::Farm);
+            self.policy = ProbePolicy::Farm;
+            self.select_farm_target(player, ctx.map);
+        }
+    }
+
+    /// Move to the other end of the patrol, once the target is reached
+    fn handle_patrol(&mut self) {
+        let next = match &self.policy {
+            ProbePolicy::Patrol { a, b } => match self.target == *a {
+                true => b.clone(),
+                false => a.clone(),
+            },
+            _ => {
+                return;
+            }
+        };
+        self.pos = self.target.clone();
+        self.state_handle.get_mut().pos = Some(self.pos.clone());
+        self.state_handle.get_mut().target = Some(next.as_coord());
+        self.set_target_manually(next);
+    }
+
+    /// Return if the probe is a picket
+    pub fn is_picket(&self) -> bool {
+        match self.policy {
+            ProbePolicy::Picket => true,
+            _ => false,
+        }
+    }
+
+    /// Wait on the current position until a farm target is found
+    /// (see `ProbePolicy::Idle`) \
+    /// Update current state, move direction, travel delayer, policy \
+    /// Return if the probe is idle, always false if not `idle_without_target`
+    pub fn idle(&mut self) -> bool {
+        if !self.config.idle_without_target {
+            return false;
+        }
+        self.state_handle.get_mut().pos = Some(self.pos.clone());
+        self.state_handle.get_mut().target = Some(self.pos.as_coord());
+        self.state_handle.get_mut().policy = Some(ProbePolicy::Idle);
+        self.policy = ProbePolicy::Idle;
+        self.set_target_manually(self.pos.clone());
+        true
+    }
+
+    /// Every `claim_delay`, look for a farm target,
+    /// if found, switch to Farm policy
+    fn wait_farm_target(&mut self, player: &Player, ctx: &mut FrameContext) {
+        if !self.delayer_claim.wait(ctx.dt) {
+            return;
+        }
+        if let Some(target) = ctx.map.get_probe_farm_target(player, &self) {
+            self.set_farm_target(target.as_point());
+        }
+    }
+
+    /// Return the remaining hp of the probe
+    pub fn get_hp(&self) -> u32 {
+        self.hp
+    }
+
+    /// Return if the probe is attacking (Attack policy)
+    pub fn is_attacking(&self) -> bool {
+        matches!(self.policy, ProbePolicy::Attack)
+    }
+
+    /// Return if the probe is farming (Farm or Idle policy)
+    pub fn is_farming(&self) -> bool {
+        match self.policy {
+            ProbePolicy::Farm | ProbePolicy::Idle => true,
+            _ => false,
+        }
+    }
+
+    /// Return the distance (unit: coord) between the probe and the coordinate
+    pub fn get_distance(&self, coord: &Coord) -> f64 {
+        Point::new(coord.x as f64 - self.pos.x, coord.y as f64 - self.pos.y).norm()
+    }
+
     /// Return if the current position is sufficiently close to the target
     /// to be considered equals
-    fn is_target_reached(&mut self, ctx: &mut FrameContext) -> bool {
-        self.delayer_travel.wait(ctx.dt)
+    fn is_target_reached(&mut self, dt: f64) -> bool {
+        self.delayer_travel.wait(dt)
     }
 
-    /// Update current position: move to target
-    fn update_pos(&mut self, ctx: &mut FrameContext) {
-        self.pos.x += self.move_dir.x * ctx.dt;
-        self.pos.y += self.move_dir.y * ctx.dt;
+    /// Return if the probe has reached the end of its lifespan
+    /// (never the case if the lifespan is 0)
+    fn is_expired(&mut self, dt: f64) -> bool {
+        if self.config.lifespan == 0.0 {
+            return false;
+        }
+        self.delayer_lifespan.wait(dt)
     }
 
-    /// Claims neighbours tiles twice \
+    /// Update current position: move to target (if not out of range),
+    /// accumulate the distance travelled
+    fn update_pos(&mut self, dt: f64) {
+        if self.is_out_of_range() {
+            return;
+        }
+        let step = Point::new(self.move_dir.x * dt, self.move_dir.y * dt);
+        let remaining = Point::new(self.target.x - self.pos.x, self.target.y - self.pos.y);
+        self.travelled += f64::min(step.norm(), remaining.norm());
+        self.pos.x += step.x;
+        self.pos.y += step.y;
+    }
+
+    /// Return if the probe travelled more than `max_range` since produced,
+    /// and is still away from its target
+    /// (never the case if the max range is 0)
+    fn is_out_of_range(&self) -> bool {
+        if self.config.max_range == 0.0 {
+            return false;
+        }
+        self.travelled > self.config.max_range && self.pos != self.target
+    }
+
+    /// Move to the target (see `update_pos`) \
+    /// Return the resulting movement
+    fn move_to_target(&mut self, dt: f64) -> Movement {
+        self.update_pos(dt);
+        if self.is_out_of_range() {
+            return Movement::OutOfRange;
+        }
+        match self.is_target_reached(dt) {
+            true => Movement::Arrived,
+            false => Movement::Moving,
+        }
+    }
+
+    /// Stop the probe at the current tile: claim it (switch to
+    /// Claim policy), or die if it is owned by an opponent \
+    /// Update current state
+    fn stop_out_of_range(&mut self, player: &Player, ctx: &mut FrameContext) {
+        let coord = self.get_coord();
+        let is_opponent_tile = match ctx.map.get_tile(&coord) {
+            Some(tile) => tile.is_owned_by_opponent_of(player.id),
+            None => false,
+        };
+        if is_opponent_tile {
+            self.state_handle.get_mut().death = Some(ProbeDeathCause::OutOfRange);
+            return;
+        }
+        self.policy = ProbePolicy::Claim;
+        self.pos = coord.as_point();
+        self.target = self.pos.clone();
+        self.state_handle.get_mut().pos = Some(self.pos.clone());
+        self.state_handle.get_mut().target = Some(coord);
+        self.state_handle.get_mut().policy = Some(ProbePolicy::Claim);
+    }
+
+    /// Claims neighbours opponent tiles (and own tiles if
+    /// `explosion_reinforce`) with the explosion intensity \
     /// Notify death in probe state
     pub fn explode(&mut self, player_id: u128, map: &mut Map, tech_explosion_intensity: bool) {
         self.state_handle.get_mut().death = Some(ProbeDeathCause::Exploded);
         let coords = geometry::square(&self.get_coord(), 1);
         for coord in coords.iter() {
             // make sure to explode on opponent tile
+            // (or own tile if explosion reinforces)
             match map.get_tile(coord) {
                 None => {
                     continue;
                 }
                 Some(tile) => {
-                    if !tile.is_owned_by_opponent_of(player_id) {
+                    let is_reinforced =
+                        self.config.explosion_reinforce && tile.is_owned_by(player_id);
+                    if !tile.is_owned_by_opponent_of(player_id) && !is_reinforced {
                         continue;
                     }
                 }
             };
             let mut intensity = self.config.explosion_intensity;
             if tech_explosion_intensity {
-                intensity += self.config.tech_explosion_intensity_increase;
+                intensity = intensity.saturating_add(self.config.tech_explosion_intensity_increase);
             }
             map.claim_tile(player_id, coord, intensity);
         }
@@ -313,7 +774,7 @@ impl Probe {
 
             let mut intensity = self.config.claim_intensity;
             if player.has_tech(&Techs::PROBE_CLAIM_INTENSITY) {
-                intensity += self.config.tech_claim_intensity_increase;
+                intensity = intensity.saturating_add(self.config.tech_claim_intensity_increase);
             }
 
             ctx.map.claim_tile(player.id, &self.get_coord(), intensity);
@@ -322,6 +783,28 @@ impl Probe {
     }
 
     /// run function
+    /// Advance the probe of one frame, without modifying the map:
+    /// expire, regenerate, move to the target \
+    /// The movement is resolved on the next call to `run`, allowing to
+    /// advance the probes of all the players in parallel
+    /// (see `GameConfig.parallel`)
+    pub fn advance(&mut self, player: &Player, map: &Map, dt: f64) {
+        if self.is_expired(dt) {
+            self.state_handle.get_mut().death = Some(ProbeDeathCause::Expired);
+            self.movement = Movement::Expired;
+            return;
+        }
+        self.regenerate(player, map, dt);
+
+        self.movement = match self.policy {
+            ProbePolicy::Farm
+            | ProbePolicy::Attack
+            | ProbePolicy::Patrol { .. }
+            | ProbePolicy::Siege => self.move_to_target(dt),
+            _ => Movement::Still,
+        };
+    }
+
     pub fn run(&mut self, player: &Player, ctx: &mut FrameContext) -> Option<ProbeState> {
         log::debug!(
             "[({:.3}) probe {:.3}] run... ({:?})",
@@ -329,26 +812,317 @@ impl Probe {
             self.id.to_string(),
             &self.policy
         );
+        if !ctx.is_advanced {
+            self.advance(player, ctx.map, ctx.dt);
+        }
+        let movement = std::mem::replace(&mut self.movement, Movement::Still);
+
+        match movement {
+            Movement::Expired => {
+                return self.state_handle.flush(&self.id);
+            }
+            Movement::OutOfRange => {
+                self.stop_out_of_range(player, ctx);
+                return self.state_handle.flush(&self.id);
+            }
+            _ => {}
+        }
+        let is_arrived = matches!(movement, Movement::Arrived);
+
         match self.policy {
             ProbePolicy::Farm => {
-                self.update_pos(ctx);
-                if self.is_target_reached(ctx) {
+                if is_arrived {
                     self.policy = ProbePolicy::Claim;
                     self.pos = self.target.clone();
                     self.state_handle.get_mut().pos = Some(self.target.clone());
                 }
             }
             ProbePolicy::Attack => {
-                self.update_pos(ctx);
-                if self.is_target_reached(ctx) {
+                if is_arrived {
                     self.attack(player, ctx);
                 }
             }
             ProbePolicy::Claim => {
                 self.claim(player, ctx);
             }
+            ProbePolicy::Hold | ProbePolicy::Picket => {}
+            ProbePolicy::Patrol { .. } => {
+                if is_arrived {
+                    self.handle_patrol();
+                }
+            }
+            ProbePolicy::Siege => {
+                if is_arrived {
+                    self.handle_siege(player, ctx);
+                }
+            }
+            ProbePolicy::Idle => {
+                self.wait_farm_target(player, ctx);
+            }
         }
 
         self.state_handle.flush(&self.id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::tests::test_config;
+    use crate::game::ProbeGrid;
+
+    /// Run the probe for one frame of `dt` seconds
+    fn run_probe(
+        probe: &mut Probe,
+        player: &Player,
+        config: &GameConfig,
+        map: &mut Map,
+        dt: f64,
+    ) -> Option<ProbeState> {
+        let mut ctx = FrameContext {
+            dt: dt,
+            is_income_tick: false,
+            config: config,
+            map: map,
+            n_probes: 1,
+            probe_grid: ProbeGrid::new(1.0, 0.0),
+            events: Vec::new(),
+            is_advanced: false,
+        };
+        probe.run(player, &mut ctx)
+    }
+
+    #[test]
+    fn separation_is_reported_again_after_moving() {
+        let config = test_config();
+        let player = Player::new(1, &config);
+        let mut probe = Probe::new(&config, &player, Point::new(5.0, 5.0));
+        probe.hold();
+        probe.state_handle.flush(&probe.id);
+
+        let separation = Point::new(0.3, 0.0);
+        probe.set_separation(separation.clone());
+        let pos = probe.state_handle.flush(&probe.id).unwrap().pos.unwrap();
+        assert_eq!(pos, Point::new(5.3, 5.0));
+
+        // unchanged offset: nothing to report
+        probe.set_separation(separation.clone());
+        assert!(probe.state_handle.flush(&probe.id).is_none());
+
+        // the probe moves away (reported without offset) then comes back
+        probe.clear_separation();
+        probe.set_separation(separation.clone());
+        let pos = probe.state_handle.flush(&probe.id).unwrap().pos.unwrap();
+        assert_eq!(pos, Point::new(5.3, 5.0));
+
+        // the position without offset is reported during the frame
+        probe.hold();
+        probe.set_separation(separation.clone());
+        let pos = probe.state_handle.flush(&probe.id).unwrap().pos.unwrap();
+        assert_eq!(pos, Point::new(5.3, 5.0));
+    }
+
+    #[test]
+    fn probe_expires_after_lifespan() {
+        let mut config = test_config();
+        config.probe_lifespan = 2.0;
+        let mut map = Map::new(&config);
+        let player = Player::new(1, &config);
+        let mut probe = Probe::new(&config, &player, Point::new(5.0, 5.0));
+        probe.hold();
+
+        for _ in 0..3 {
+            let state = run_probe(&mut probe, &player, &config, &mut map, 0.5);
+            assert!(state.map_or(true, |s| s.death.is_none()));
+        }
+        let state = run_probe(&mut probe, &player, &config, &mut map, 0.5).unwrap();
+        assert!(matches!(state.death, Some(ProbeDeathCause::Expired)));
+    }
+
+    #[test]
+    fn probe_never_expires_without_lifespan() {
+        let config = test_config();
+        let mut map = Map::new(&config);
+        let player = Player::new(1, &config);
+        let mut probe = Probe::new(&config, &player, Point::new(5.0, 5.0));
+        probe.hold();
+
+        for _ in 0..100 {
+            let state = run_probe(&mut probe, &player, &config, &mut map, 0.5);
+            assert!(state.map_or(true, |s| s.death.is_none()));
+        }
+    }
+
+    #[test]
+    fn explosion_reinforces_own_tiles_if_configured() {
+        let mut config = test_config();
+        for reinforce in [false, true] {
+            config.probe_explosion_reinforce = reinforce;
+            let mut map = Map::new(&config);
+            let player = Player::new(1, &config);
+            map.claim_tile(1, &Coord::new(4, 5), 2);
+            map.claim_tile(1, &Coord::new(5, 5), 2);
+            map.claim_tile(2, &Coord::new(6, 5), 5);
+
+            let mut probe = Probe::new(&config, &player, Point::new(5.0, 5.0));
+            probe.explode(player.id, &mut map, false);
+
+            let grid = map.get_occupation_grid();
+            assert_eq!(grid[6][5], 5 - config.probe_explosion_intensity);
+            match reinforce {
+                true => assert_eq!(grid[4][5], 2 + config.probe_explosion_intensity),
+                false => assert_eq!(grid[4][5], 2),
+            }
+            // unowned tiles are not claimed
+            assert_eq!(grid[5][4], 0);
+        }
+    }
+
+    /// Run a probe at (5, 5) moving toward (x, 5) for `n_frames` of 0.5 sec,
+    /// with a max range of 3 \
+    /// Return the probe and its last state
+    fn run_ranged_probe(x: f64, map: &mut Map, n_frames: usize) -> (Probe, ProbeState) {
+        let mut config = test_config();
+        config.probe_max_range = 3.0;
+        let player = Player::new(1, &config);
+        let mut probe = Probe::new(&config, &player, Point::new(5.0, 5.0));
+        probe.set_target_manually(Point::new(x, 5.0));
+        let mut state = ProbeState::new(&probe.id);
+        for _ in 0..n_frames {
+            if let Some(s) = run_probe(&mut probe, &player, &config, map, 0.5) {
+                state = s;
+            }
+        }
+        (probe, state)
+    }
+
+    #[test]
+    fn probe_stops_beyond_max_range() {
+        let config = test_config();
+
+        // target at the boundary: reached
+        let mut map = Map::new(&config);
+        let (probe, state) = run_ranged_probe(8.0, &mut map, 6);
+        assert!(matches!(probe.policy, ProbePolicy::Claim));
+        assert_eq!(probe.pos, Point::new(8.0, 5.0));
+        assert!(state.death.is_none());
+
+        // target beyond the boundary: claim the current tile
+        let mut map = Map::new(&config);
+        let (probe, state) = run_ranged_probe(12.0, &mut map, 7);
+        assert!(matches!(probe.policy, ProbePolicy::Claim));
+        assert_eq!(probe.pos, Point::new(8.0, 5.0));
+        assert_eq!(probe.target, probe.pos);
+        assert!(state.death.is_none());
+
+        // stopped on an opponent tile: die
+        let mut map = Map::new(&config);
+        map.claim_tile(2, &Coord::new(8, 5), 2);
+        let (_, state) = run_ranged_probe(12.0, &mut map, 7);
+        assert!(matches!(state.death, Some(ProbeDeathCause::OutOfRange)));
+    }
+
+    #[test]
+    fn regeneration_stops_at_max_hp_on_owned_tiles() {
+        let mut config = test_config();
+        config.probe_hp_regen_per_sec = 1.0;
+        for owner in [1, 2] {
+            let mut map = Map::new(&config);
+            map.claim_tile(owner, &Coord::new(5, 5), 2);
+            let player = Player::new(1, &config);
+            let mut probe = Probe::new(&config, &player, Point::new(5.0, 5.0));
+            probe.hold();
+            probe.inflict_damage(1);
+            assert_eq!(probe.hp, 1);
+
+            for _ in 0..10 {
+                run_probe(&mut probe, &player, &config, &mut map, 0.5);
+                assert!(probe.hp <= probe.max_hp);
+            }
+            match owner {
+                1 => assert_eq!(probe.hp, probe.max_hp),
+                _ => assert_eq!(probe.hp, 1),
+            }
+        }
+    }
+
+    #[test]
+    fn siege_converts_enemy_tile_then_farms() {
+        let config = test_config();
+        let mut map = Map::new(&config);
+        let coord = Coord::new(6, 5);
+        map.claim_tile(2, &coord, 2 * config.probe_claim_intensity);
+        map.claim_tile(1, &Coord::new(5, 5), 2);
+        let player = Player::new(1, &config);
+        let mut probe = Probe::new(&config, &player, coord.as_point());
+        probe.siege(coord.as_point());
+
+        // owners of the tile after each claim
+        let mut owners: Vec<Option<u128>> = Vec::new();
+        for _ in 0..10 {
+            if !matches!(probe.policy, ProbePolicy::Siege) {
+                break;
+            }
+            let state = run_probe(
+                &mut probe,
+                &player,
+                &config,
+                &mut map,
+                config.probe_claim_delay,
+            );
+            assert!(state.map_or(true, |s| s.death.is_none()));
+            let owner = map.get_tile(&coord).unwrap().owner_id;
+            if owners.last() != Some(&owner) {
+                owners.push(owner);
+            }
+        }
+        assert_eq!(owners, vec![Some(2), None, Some(1)]);
+        assert!(matches!(
+            probe.policy,
+            ProbePolicy::Farm | ProbePolicy::Claim
+        ));
+    }
+
+    #[test]
+    fn hp_is_reported_only_when_shot() {
+        let mut config = test_config();
+        config.probe_hp = 3;
+        let mut map = Map::new(&config);
+        let player = Player::new(1, &config);
+        let mut probe = Probe::new(&config, &player, Point::new(5.0, 5.0));
+        probe.hold();
+        probe.state_handle.flush(&probe.id);
+
+        probe.inflict_damage(1);
+        let state = run_probe(&mut probe, &player, &config, &mut map, 0.5).unwrap();
+        assert_eq!(state.hp, Some(2));
+        assert!(state.death.is_none());
+
+        let state = run_probe(&mut probe, &player, &config, &mut map, 0.5);
+        assert!(state.map_or(true, |s| s.hp.is_none()));
+    }
+
+    #[test]
+    fn patrolling_probe_runs_out_of_range() {
+        let mut config = test_config();
+        config.probe_max_range = 3.0;
+        let mut map = Map::new(&config);
+        let player = Player::new(1, &config);
+        let mut probe = Probe::new(&config, &player, Point::new(5.0, 5.0));
+        // never further than 1 from its origin
+        probe.patrol(Point::new(6.0, 5.0), Point::new(5.0, 5.0));
+
+        for _ in 0..6 {
+            run_probe(&mut probe, &player, &config, &mut map, 0.5);
+            assert!(matches!(probe.policy, ProbePolicy::Patrol { .. }));
+        }
+        assert_eq!(probe.travelled, 3.0);
+        run_probe(&mut probe, &player, &config, &mut map, 0.5);
+        assert!(matches!(probe.policy, ProbePolicy::Claim));
+        assert_eq!(probe.pos, probe.target);
+
+        // the distance travelled is kept on restore
+        let restored = Probe::from_state(&config, &player, &probe.get_complete_state()).unwrap();
+        assert_eq!(restored.travelled, probe.travelled);
+    }
+}
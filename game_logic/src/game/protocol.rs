@@ -0,0 +1,372 @@
+//! Compact binary encoding of the game actions \
+//! Allow to send a batch of actions in one call,
+//! without building python objects for each of them
+//!
+//! Wire format (all numbers are little-endian) \
+//! A batch is a sequence of actions, until the end of the buffer. \
+//! Each action starts with an opcode (`u8`) and the player id (`u128`),
+//! followed by the fields of the action:
+//!
+//! | opcode | action                   | fields                                  |
+//! |--------|--------------------------|-----------------------------------------|
+//! | 0      | resign game              |                                         |
+//! | 1      | build factory            | `x: i32`, `y: i32`                      |
+//! | 2      | build turret             | `x: i32`, `y: i32`                      |
+//! | 3      | move probes              | `x: i32`, `y: i32`, `n: u32`, `n * u128` |
+//! | 4      | explode probes           | `n: u32`, `n * u128`                    |
+//! | 5      | probes attack            | `n: u32`, `n * u128`                    |
+//! | 6      | hold probes              | `n: u32`, `n * u128`                    |
+//! | 7      | acquire tech             | `n: u8`, `n` bytes (utf-8 tech name)    |
+//! | 8      | set default probe policy | `n: u8`, `n` bytes (utf-8 policy name)  |
+//!
+//! Note: only the actions above are encoded, the other actions
+//! (ex: patrol, siege, factory policy, turret focus) are only
+//! performed through their own method of `Game`
+
+use super::Coord;
+
+const OP_RESIGN_GAME: u8 = 0;
+const OP_BUILD_FACTORY: u8 = 1;
+const OP_BUILD_TURRET: u8 = 2;
+const OP_MOVE_PROBES: u8 = 3;
+const OP_EXPLODE_PROBES: u8 = 4;
+const OP_PROBES_ATTACK: u8 = 5;
+const OP_HOLD_PROBES: u8 = 6;
+const OP_ACQUIRE_TECH: u8 = 7;
+const OP_SET_DEFAULT_PROBE_POLICY: u8 = 8;
+
+/// Action that a player can perform on the game
+/// (see Actions block of `Game`)
+#[derive(Clone, Debug)]
+pub enum Action {
+    ResignGame {
+        player_id: u128,
+    },
+    BuildFactory {
+        player_id: u128,
+        coord: Coord,
+    },
+    BuildTurret {
+        player_id: u128,
+        coord: Coord,
+    },
+    MoveProbes {
+        player_id: u128,
+        ids: Vec<u128>,
+        target: Coord,
+    },
+    ExplodeProbes {
+        player_id: u128,
+        ids: Vec<u128>,
+    },
+    ProbesAttack {
+        player_id: u128,
+        ids: Vec<u128>,
+    },
+    HoldProbes {
+        player_id: u128,
+        ids: Vec<u128>,
+    },
+    AcquireTech {
+        player_id: u128,
+        tech: String,
+    },
+    SetDefaultProbePolicy {
+        player_id: u128,
+        policy: String,
+    },
+}
+
+/// Read the fields of the actions from a buffer
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Reader { buf: buf, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+
+    /// Return the next `n` bytes \
+    /// Return an error in case the buffer is too short
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.buf.len() - self.pos < n {
+            return Err(format!("Unexpected end of buffer (at byte {})", self.pos));
+        }
+        let bytes = &self.buf[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn read_i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn read_u128(&mut self) -> Result<u128, String> {
+        Ok(u128::from_le_bytes(self.take(16)?.try_into().unwrap()))
+    }
+
+    fn read_coord(&mut self) -> Result<Coord, String> {
+        let x = self.read_i32()?;
+        let y = self.read_i32()?;
+        Ok(Coord::new(x, y))
+    }
+
+    fn read_ids(&mut self) -> Result<Vec<u128>, String> {
+        let n = self.read_u32()? as usize;
+        let mut ids = Vec::with_capacity(n.min(self.buf.len() / 16));
+        for _ in 0..n {
+            ids.push(self.read_u128()?);
+        }
+        Ok(ids)
+    }
+
+    fn read_string(&mut self) -> Result<String, String> {
+        let n = self.read_u8()? as usize;
+        match String::from_utf8(self.take(n)?.to_vec()) {
+            Ok(string) => Ok(string),
+            Err(_) => Err(format!("Invalid utf-8 string (at byte {})", self.pos - n)),
+        }
+    }
+}
+
+#[cfg(test)]
+fn write_coord(buf: &mut Vec<u8>, coord: &Coord) {
+    buf.extend_from_slice(&coord.x.to_le_bytes());
+    buf.extend_from_slice(&coord.y.to_le_bytes());
+}
+
+#[cfg(test)]
+fn write_ids(buf: &mut Vec<u8>, ids: &Vec<u128>) {
+    buf.extend_from_slice(&(ids.len() as u32).to_le_bytes());
+    for id in ids.iter() {
+        buf.extend_from_slice(&id.to_le_bytes());
+    }
+}
+
+/// Note: the string is truncated to 255 bytes
+#[cfg(test)]
+fn write_string(buf: &mut Vec<u8>, string: &String) {
+    let bytes = &string.as_bytes()[..string.len().min(u8::MAX as usize)];
+    buf.push(bytes.len() as u8);
+    buf.extend_from_slice(bytes);
+}
+
+impl Action {
+    /// Return the id of the player performing the action
+    #[cfg(test)]
+    pub fn get_player_id(&self) -> u128 {
+        match self {
+            Action::ResignGame { player_id }
+            | Action::BuildFactory { player_id, .. }
+            | Action::BuildTurret { player_id, .. }
+            | Action::MoveProbes { player_id, .. }
+            | Action::ExplodeProbes { player_id, .. }
+            | Action::ProbesAttack { player_id, .. }
+            | Action::HoldProbes { player_id, .. }
+            | Action::AcquireTech { player_id, .. }
+            | Action::SetDefaultProbePolicy { player_id, .. } => *player_id,
+        }
+    }
+
+    /// Append the binary encoding of the action to `buf` \
+    /// Note: the actions are encoded by the clients,
+    /// only used to test the decoding
+    #[cfg(test)]
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        let opcode = match self {
+            Action::ResignGame { .. } => OP_RESIGN_GAME,
+            Action::BuildFactory { .. } => OP_BUILD_FACTORY,
+            Action::BuildTurret { .. } => OP_BUILD_TURRET,
+            Action::MoveProbes { .. } => OP_MOVE_PROBES,
+            Action::ExplodeProbes { .. } => OP_EXPLODE_PROBES,
+            Action::ProbesAttack { .. } => OP_PROBES_ATTACK,
+            Action::HoldProbes { .. } => OP_HOLD_PROBES,
+            Action::AcquireTech { .. } => OP_ACQUIRE_TECH,
+            Action::SetDefaultProbePolicy { .. } => OP_SET_DEFAULT_PROBE_POLICY,
+        };
+        buf.push(opcode);
+        buf.extend_from_slice(&self.get_player_id().to_le_bytes());
+
+        match self {
+            Action::ResignGame { .. } => {}
+            Action::BuildFactory { coord, .. } | Action::BuildTurret { coord, .. } => {
+                write_coord(buf, coord);
+            }
+            Action::MoveProbes { ids, target, .. } => {
+                write_coord(buf, target);
+                write_ids(buf, ids);
+            }
+            Action::ExplodeProbes { ids, .. }
+            | Action::ProbesAttack { ids, .. }
+            | Action::HoldProbes { ids, .. } => {
+                write_ids(buf, ids);
+            }
+            Action::AcquireTech { tech, .. } => {
+                write_string(buf, tech);
+            }
+            Action::SetDefaultProbePolicy { policy, .. } => {
+                write_string(buf, policy);
+            }
+        }
+    }
+
+    /// Read the next action of the reader
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        let opcode = reader.read_u8()?;
+        let player_id = reader.read_u128()?;
+        match opcode {
+            OP_RESIGN_GAME => Ok(Action::ResignGame { player_id }),
+            OP_BUILD_FACTORY => Ok(Action::BuildFactory {
+                player_id,
+                coord: reader.read_coord()?,
+            }),
+            OP_BUILD_TURRET => Ok(Action::BuildTurret {
+                player_id,
+                coord: reader.read_coord()?,
+            }),
+            OP_MOVE_PROBES => {
+                let target = reader.read_coord()?;
+                Ok(Action::MoveProbes {
+                    player_id,
+                    ids: reader.read_ids()?,
+                    target,
+                })
+            }
+            OP_EXPLODE_PROBES => Ok(Action::ExplodeProbes {
+                player_id,
+                ids: reader.read_ids()?,
+            }),
+            OP_PROBES_ATTACK => Ok(Action::ProbesAttack {
+                player_id,
+                ids: reader.read_ids()?,
+            }),
+            OP_HOLD_PROBES => Ok(Action::HoldProbes {
+                player_id,
+                ids: reader.read_ids()?,
+            }),
+            OP_ACQUIRE_TECH => Ok(Action::AcquireTech {
+                player_id,
+                tech: reader.read_string()?,
+            }),
+            OP_SET_DEFAULT_PROBE_POLICY => Ok(Action::SetDefaultProbePolicy {
+                player_id,
+                policy: reader.read_string()?,
+            }),
+            _ => Err(format!("Invalid action opcode: {}", opcode)),
+        }
+    }
+}
+
+/// Return the binary encoding of the batch of actions
+#[cfg(test)]
+pub fn encode_actions(actions: &Vec<Action>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for action in actions.iter() {
+        action.encode(&mut buf);
+    }
+    buf
+}
+
+/// Parse a batch of actions \
+/// Return an error in case the buffer is malformed
+pub fn decode_actions(buf: &[u8]) -> Result<Vec<Action>, String> {
+    let mut reader = Reader::new(buf);
+    let mut actions = Vec::new();
+    while !reader.is_empty() {
+        actions.push(Action::decode(&mut reader)?);
+    }
+    Ok(actions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_actions() -> Vec<Action> {
+        vec![
+            Action::ResignGame { player_id: 1 },
+            Action::BuildFactory {
+                player_id: 2,
+                coord: Coord::new(3, -4),
+            },
+            Action::BuildTurret {
+                player_id: u128::MAX,
+                coord: Coord::new(i32::MAX, i32::MIN),
+            },
+            Action::MoveProbes {
+                player_id: 2,
+                ids: vec![10, 11, 12],
+                target: Coord::new(5, 6),
+            },
+            Action::ExplodeProbes {
+                player_id: 2,
+                ids: vec![],
+            },
+            Action::ProbesAttack {
+                player_id: 2,
+                ids: vec![13],
+            },
+            Action::HoldProbes {
+                player_id: 2,
+                ids: vec![14, 15],
+            },
+            Action::AcquireTech {
+                player_id: 2,
+                tech: String::from("PROBE_HP"),
+            },
+            Action::SetDefaultProbePolicy {
+                player_id: 2,
+                policy: String::from("Attack"),
+            },
+        ]
+    }
+
+    #[test]
+    fn actions_round_trip() {
+        let actions = get_actions();
+        let buf = encode_actions(&actions);
+        let decoded = decode_actions(&buf).unwrap();
+        assert_eq!(format!("{:?}", decoded), format!("{:?}", actions));
+        assert!(decode_actions(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn malformed_buffers_are_rejected() {
+        let buf = encode_actions(&get_actions());
+        for len in [1, 16, 20, buf.len() - 1] {
+            let err = decode_actions(&buf[..len]).unwrap_err();
+            assert!(err.starts_with("Unexpected end of buffer"));
+        }
+
+        let mut buf = encode_actions(&vec![Action::ResignGame { player_id: 1 }]);
+        buf[0] = 42;
+        assert_eq!(
+            decode_actions(&buf).unwrap_err(),
+            "Invalid action opcode: 42"
+        );
+
+        let mut buf = encode_actions(&vec![Action::AcquireTech {
+            player_id: 1,
+            tech: String::from("a"),
+        }]);
+        let n = buf.len();
+        buf[n - 1] = 0xff;
+        assert!(decode_actions(&buf)
+            .unwrap_err()
+            .starts_with("Invalid utf-8 string"));
+    }
+}
@@ -1,9 +1,20 @@
-use rand::{prelude::SliceRandom, thread_rng, Rng};
+use rand::{prelude::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 
-pub fn shuffle_vec<T>(vec: &mut Vec<T>) {
-    vec.shuffle(&mut thread_rng());
+/// Random number generator of the game, owned by the map \
+/// Its state is part of the game snapshot (see `Game::snapshot`),
+/// so that a restored game resumes the exact same random stream
+pub type GameRng = ChaCha8Rng;
+
+/// Return a new generator, seeded from the system entropy
+pub fn new_rng() -> GameRng {
+    GameRng::from_entropy()
+}
+
+pub fn shuffle_vec<T>(vec: &mut Vec<T>, rng: &mut GameRng) {
+    vec.shuffle(rng);
 }
 
-pub fn random() -> f64 {
-    thread_rng().gen()
+pub fn random(rng: &mut GameRng) -> f64 {
+    rng.gen()
 }
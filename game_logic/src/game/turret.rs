@@ -1,28 +1,66 @@
+use serde::{Deserialize, Serialize};
+
 use super::{
-    core, Coord, Delayer, FrameContext, GameConfig, Identifiable, Player, Point, ProbeDeathCause,
-    State, StateHandler, Techs,
+    core, Coord, Delayer, FrameContext, GameConfig, GridProbe, Identifiable, Player, Point, Probe,
+    ProbeDeathCause, ProbeGrid, State, StateHandler, Techs,
 };
 
+#[derive(Clone, Serialize, Deserialize)]
 pub enum TurretPolicy {
     Ready,
     Wait,
 }
 
-#[derive(Clone, Debug)]
+/// Which probe in scope a turret fires at in priority,
+/// the ties are broken by distance (the nearest first),
+/// then by probe id (the lowest first)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum TurretTargetPriority {
+    /// The probe in scope with the lowest id (regardless of the distance),
+    /// which doesn't depend on the order in which the probes are stored
+    First,
+    /// The nearest probe
+    Nearest,
+    /// The probe with the lowest hp
+    LowestHp,
+    /// The probes with the Attack policy
+    Attackers,
+}
+
+impl TurretTargetPriority {
+    /// Create an instance from a string \
+    /// Return an error in case the `string` is invalid
+    pub fn from_string(string: &str) -> Result<Self, String> {
+        match string {
+            "First" => Ok(TurretTargetPriority::First),
+            "Nearest" => Ok(TurretTargetPriority::Nearest),
+            "LowestHp" => Ok(TurretTargetPriority::LowestHp),
+            "Attackers" => Ok(TurretTargetPriority::Attackers),
+            _ => Err(format!("Invalid turret target priority: {}", string)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum TurretDeathCause {
     Conquered,
     Scrapped,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 struct TurretConfig {
     turret_scope: f64,
     turret_damage: u32,
     turret_maintenance_costs: f64,
     tech_scope_increase: f64,
     tech_maintenance_costs_decrease: f64,
+    tech_damage_increase: u32,
+    target_priority: TurretTargetPriority,
+    splash_radius: f64,
+    splash_damage: u32,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TurretState {
     pub id: u128,
     /// Only specified once, when the turret dies
@@ -30,6 +68,14 @@ pub struct TurretState {
     pub coord: Option<Coord>,
     /// id of the probe that was shot
     pub shot_id: Option<u128>,
+    /// ids of the probes hit by the splash of the shot
+    pub splash_ids: Vec<u128>,
+    /// If the turret's tile is below the upkeep occupation
+    pub at_risk: Option<bool>,
+    /// Remaining time before the turret can fire again (sec), specified
+    /// when the turret starts reloading (clients derive the following values)
+    /// and when it is ready again (None)
+    pub fire_cooldown: Option<Option<f64>>,
 }
 
 impl Identifiable for TurretState {
@@ -47,6 +93,9 @@ impl State for TurretState {
             death: None,
             coord: None,
             shot_id: None,
+            splash_ids: Vec::new(),
+            at_risk: None,
+            fire_cooldown: None,
         }
     }
 
@@ -57,15 +106,26 @@ impl State for TurretState {
         if let Some(coord) = state.coord {
             self.coord = Some(coord);
         }
+        if let Some(at_risk) = state.at_risk {
+            self.at_risk = Some(at_risk);
+        }
+        if let Some(fire_cooldown) = state.fire_cooldown {
+            self.fire_cooldown = Some(fire_cooldown);
+        }
+        self.splash_ids.extend(state.splash_ids);
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Turret {
     pub id: u128,
     config: TurretConfig,
     state_handle: StateHandler<TurretState>,
     policy: TurretPolicy,
     pos: Coord,
+    /// Point where the turret fires in priority (if any),
+    /// cleared when out of scope
+    focus: Option<Coord>,
     /// Delay to wait to fire probe
     delayer_fire: Delayer,
 }
@@ -81,14 +141,38 @@ impl Turret {
                 turret_maintenance_costs: config.turret_maintenance_costs,
                 tech_scope_increase: config.tech_turret_scope_increase,
                 tech_maintenance_costs_decrease: config.tech_turret_maintenance_costs_decrease,
+                tech_damage_increase: config.tech_turret_damage_increase,
+                target_priority: config.turret_target_priority.clone(),
+                splash_radius: config.turret_splash_radius,
+                splash_damage: config.turret_splash_damage,
             },
             state_handle: StateHandler::new(&id),
             policy: TurretPolicy::Ready,
             pos: pos,
+            focus: None,
             delayer_fire: Delayer::new(config.turret_fire_delay),
         }
     }
 
+    /// Create a Turret instance from its (complete) state \
+    /// Return an error in case the state has no coordinate
+    pub fn from_state(config: &GameConfig, state: &TurretState) -> Result<Turret, String> {
+        let pos = match &state.coord {
+            Some(coord) => coord.clone(),
+            None => {
+                return Err(String::from("Missing turret coordinate"));
+            }
+        };
+        let mut turret = Turret::new(config, pos);
+        turret.id = state.id;
+        turret.state_handle = StateHandler::new(&state.id);
+        Ok(turret)
+    }
+
+    pub fn get_coord(&self) -> Coord {
+        self.pos.clone()
+    }
+
     /// Return complete current turret state
     pub fn get_complete_state(&self) -> TurretState {
         TurretState {
@@ -96,6 +180,18 @@ impl Turret {
             death: None,
             coord: Some(self.pos.clone()),
             shot_id: None,
+            splash_ids: Vec::new(),
+            at_risk: None,
+            fire_cooldown: Some(self.get_fire_cooldown()),
+        }
+    }
+
+    /// Return the remaining time before the turret can fire again,
+    /// `None` if the turret is ready
+    fn get_fire_cooldown(&self) -> Option<f64> {
+        match self.policy {
+            TurretPolicy::Ready => None,
+            TurretPolicy::Wait => Some(self.delayer_fire.get_remaining()),
         }
     }
 
@@ -111,14 +207,30 @@ impl Turret {
         self.delayer_fire.set_delay(delay);
     }
 
+    /// Set the point where the turret fires in priority
+    pub fn set_focus(&mut self, focus: Coord) {
+        self.focus = Some(focus);
+    }
+
     /// Return the turret scope, taking tech into account
-    fn get_scope(&self, player: &Player) -> f64 {
+    pub fn get_scope(&self, player: &Player) -> f64 {
         if player.has_tech(&Techs::TURRET_SCOPE) {
             return self.config.turret_scope + self.config.tech_scope_increase;
         }
         self.config.turret_scope
     }
 
+    /// Return the turret damage, taking tech into account
+    fn get_damage(&self, player: &Player) -> u32 {
+        if player.has_tech(&Techs::TURRET_DAMAGE) {
+            return self
+                .config
+                .turret_damage
+                .saturating_add(self.config.tech_damage_increase);
+        }
+        self.config.turret_damage
+    }
+
     /// Return turret income (costs)
     pub fn get_income(&self, player: &Player) -> f64 {
         if player.has_tech(&Techs::TURRET_MAINTENANCE_COSTS) {
@@ -136,27 +248,202 @@ impl Turret {
         dx * dx + dy * dy <= scope.powi(2)
     }
 
+    /// Return the id of the opponent probe in range
+    /// the closest to the focus point, if any
+    fn get_focused_probe(
+        &self,
+        focus: &Coord,
+        scope: f64,
+        opponents: &mut Vec<&mut Player>,
+    ) -> Option<u128> {
+        let focus = focus.as_point();
+        let mut closest: Option<(u128, f64)> = None;
+        for opp in opponents.iter_mut() {
+            for probe in opp.iter_mut_probes() {
+                if !self.is_in_range(&probe.pos, scope) {
+                    continue;
+                }
+                let dist = Point::new(probe.pos.x - focus.x, probe.pos.y - focus.y).norm();
+                if closest.map_or(true, |(_, d)| dist < d) {
+                    closest = Some((probe.id, dist));
+                }
+            }
+        }
+        closest.map(|(id, _)| id)
+    }
+
+    /// Fire at the probe closest to the focus point (if any),
+    /// clear the focus if it is out of scope \
+    /// Return if a probe was fired at
+    fn handle_fire_focus(
+        &mut self,
+        scope: f64,
+        damage: u32,
+        opponents: &mut Vec<&mut Player>,
+    ) -> bool {
+        let focus = match &self.focus {
+            Some(focus) => focus.clone(),
+            None => {
+                return false;
+            }
+        };
+        if !self.is_in_range(&focus.as_point(), scope) {
+            self.focus = None;
+            return false;
+        }
+        let id = match self.get_focused_probe(&focus, scope, opponents) {
+            Some(id) => id,
+            None => {
+                return false;
+            }
+        };
+        self.fire(id, damage, opponents)
+    }
+
+    /// Fire at the probe, inflict the splash damage (if any)
+    /// and switch to Wait policy \
+    /// Return if the probe was found
+    fn fire(&mut self, probe_id: u128, damage: u32, opponents: &mut Vec<&mut Player>) -> bool {
+        let mut pos = None;
+        for opp in opponents.iter_mut() {
+            if let Some(probe) = opp.get_mut_probe_by_id(probe_id) {
+                self.state_handle.get_mut().shot_id = Some(probe.id);
+                probe.inflict_damage(damage);
+                self.policy = TurretPolicy::Wait;
+                self.state_handle.get_mut().fire_cooldown = Some(self.get_fire_cooldown());
+                pos = Some(probe.pos.clone());
+                break;
+            }
+        }
+        match pos {
+            Some(pos) => {
+                self.handle_splash(&pos, probe_id, opponents);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Inflict the splash damage to the opponent probes
+    /// within the splash radius of `pos`, except the probe fired at
+    fn handle_splash(&mut self, pos: &Point, shot_id: u128, opponents: &mut Vec<&mut Player>) {
+        if self.config.splash_radius <= 0.0 || self.config.splash_damage == 0 {
+            return;
+        }
+        let radius = self.config.splash_radius.powi(2);
+        for opp in opponents.iter_mut() {
+            for probe in opp.iter_mut_probes() {
+                if probe.id == shot_id {
+                    continue;
+                }
+                let dx = probe.pos.x - pos.x;
+                let dy = probe.pos.y - pos.y;
+                if dx * dx + dy * dy <= radius {
+                    self.state_handle.get_mut().splash_ids.push(probe.id);
+                    probe.inflict_damage(self.config.splash_damage);
+                }
+            }
+        }
+    }
+
+    /// Return the priority of the probe as a target,
+    /// the lowest first (see `TurretTargetPriority`)
+    fn get_target_priority(&self, probe: &Probe) -> (u32, f64) {
+        let criterion = match self.config.target_priority {
+            TurretTargetPriority::First => {
+                return (0, 0.0);
+            }
+            TurretTargetPriority::Nearest => 0,
+            TurretTargetPriority::LowestHp => probe.get_hp(),
+            TurretTargetPriority::Attackers => match probe.is_attacking() {
+                true => 0,
+                false => 1,
+            },
+        };
+        (criterion, probe.get_distance(&self.pos))
+    }
+
+    /// Return the probe of the grid entry, if it still exists,
+    /// `player_idx` is the index the player would have among the opponents \
+    /// Note: the probes that died during the frame shift the indexes,
+    /// in which case the probe is searched by id
+    fn get_grid_probe<'a>(
+        entry: &GridProbe,
+        player_idx: usize,
+        opponents: &'a mut Vec<&mut Player>,
+    ) -> Option<&'a mut Probe> {
+        let idx = match entry.player_idx > player_idx {
+            true => entry.player_idx - 1,
+            false => entry.player_idx,
+        };
+        let idx = match opponents.get(idx) {
+            Some(opp) if opp.id == entry.player_id => idx,
+            _ => opponents.iter().position(|p| p.id == entry.player_id)?,
+        };
+        let opp = &mut opponents[idx];
+        let is_valid = opp
+            .get_mut_probe_at(entry.factory_idx, entry.probe_idx)
+            .map_or(false, |p| p.id == entry.probe_id);
+        match is_valid {
+            true => opp.get_mut_probe_at(entry.factory_idx, entry.probe_idx),
+            false => opp.get_mut_probe_by_id(entry.probe_id),
+        }
+    }
+
     /// Check for each probe of each opponent
-    /// if it is in range, in that case, kill probe (update its state)
-    /// and switch to Wait policy
-    fn handle_fire_probe(&mut self, player: &Player, opponents: &mut Vec<&mut Player>) {
+    /// if it is in range, fire at the one with the highest priority
+    /// (see `TurretTargetPriority`) and switch to Wait policy \
+    /// The probes close to the focus point (if any) are fired in priority
+    ///
+    /// Note: only the probes in the cells of the grid close to the turret
+    /// are checked, the probes created during the frame are ignored \
+    /// The ties are broken by probe id, so that the target doesn't
+    /// depend on the order in which the probes are iterated
+    fn handle_fire_probe(
+        &mut self,
+        player: &Player,
+        grid: &ProbeGrid,
+        opponents: &mut Vec<&mut Player>,
+    ) {
         let scope = self.get_scope(player);
-        for opp in opponents {
-            for probe in opp.iter_mut_probes() {
-                if self.is_in_range(&probe.pos, scope) {
-                    self.state_handle.get_mut().shot_id = Some(probe.id);
-                    probe.inflict_damage(self.config.turret_damage);
-                    self.policy = TurretPolicy::Wait;
-                    return;
+        let damage = self.get_damage(player);
+        if self.handle_fire_focus(scope, damage, opponents) {
+            return;
+        }
+        // the opponents are the players (sorted by id) without the player
+        let player_idx = opponents.iter().filter(|p| p.id < player.id).count();
+        // (probe id, priority) of the target
+        let mut target: Option<(u128, (u32, f64))> = None;
+        for entry in grid.query(&self.pos.as_point(), scope) {
+            let probe = match Turret::get_grid_probe(entry, player_idx, opponents) {
+                Some(probe) => probe,
+                None => {
+                    continue;
                 }
+            };
+            if !self.is_in_range(&probe.pos, scope) {
+                continue;
+            }
+            let priority = self.get_target_priority(probe);
+            let is_target = match target {
+                None => true,
+                Some((id, p)) => priority < p || (priority == p && probe.id < id),
+            };
+            if is_target {
+                target = Some((probe.id, priority));
             }
         }
+
+        if let Some((probe_id, _)) = target {
+            self.fire(probe_id, damage, opponents);
+        }
     }
 
-    /// Switch to Produce policy when having less than `max_probe`
+    /// Switch to Ready policy when the fire delay is reached
     fn wait(&mut self, ctx: &mut FrameContext) {
         if self.delayer_fire.wait(ctx.dt) {
             self.policy = TurretPolicy::Ready;
+            self.state_handle.get_mut().fire_cooldown = Some(None);
         }
     }
 
@@ -175,7 +462,7 @@ impl Turret {
 
         match self.policy {
             TurretPolicy::Ready => {
-                self.handle_fire_probe(player, opponents);
+                self.handle_fire_probe(player, &ctx.probe_grid, opponents);
             }
             TurretPolicy::Wait => {
                 self.wait(ctx);
@@ -185,3 +472,303 @@ impl Turret {
         self.state_handle.flush(&self.id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::random::{self, GameRng};
+    use crate::game::tests::test_config;
+    use crate::game::{geometry, Factory, FactoryDeathCause, Map};
+    use rand::SeedableRng;
+
+    /// Return an opponent (id: 2) with one probe at each of the `positions`
+    fn create_opponent(config: &GameConfig, positions: &[(f64, f64)]) -> Player {
+        let mut opponent = Player::new(2, config);
+        let mut factory = Factory::new(config, Coord::new(0, 0));
+        for (x, y) in positions {
+            factory.attach_probe(Probe::new(config, &opponent, Point::new(*x, *y)));
+        }
+        opponent.factories.push(factory);
+        opponent
+    }
+
+    /// Return the ids of the probes of the opponent, in order of creation
+    fn get_probe_ids(opponent: &mut Player) -> Vec<u128> {
+        opponent.iter_mut_probes().map(|p| p.id).collect()
+    }
+
+    /// Run the turret of the player for one frame of `dt` seconds
+    /// against the opponent \
+    /// Return the state of the turret and the opponent
+    fn run_turret(
+        turret: &mut Turret,
+        player: &Player,
+        config: &GameConfig,
+        opponent: Player,
+        dt: f64,
+    ) -> (Option<TurretState>, Player) {
+        let mut map = Map::new(config);
+        let mut players = vec![opponent];
+        let grid = ProbeGrid::build(&players, config.turret_scope, 0.0);
+        let mut ctx = FrameContext {
+            dt: dt,
+            is_income_tick: false,
+            config: config,
+            map: &mut map,
+            n_probes: 0,
+            probe_grid: grid,
+            events: Vec::new(),
+            is_advanced: false,
+        };
+        let mut opponents: Vec<&mut Player> = players.iter_mut().collect();
+        let state = turret.run(player, &mut ctx, &mut opponents);
+        (state, players.pop().unwrap())
+    }
+
+    #[test]
+    fn focused_turret_fires_near_focus() {
+        let mut config = test_config();
+        config.turret_target_priority = TurretTargetPriority::Nearest;
+        let player = Player::new(1, &config);
+        let mut opponent = create_opponent(&config, &[(11.0, 10.0), (10.0, 12.5)]);
+        let ids = get_probe_ids(&mut opponent);
+
+        // without focus: the probe the nearest to the turret
+        let mut turret = Turret::new(&config, Coord::new(10, 10));
+        let (state, opponent) = run_turret(&mut turret, &player, &config, opponent, 0.1);
+        assert_eq!(state.unwrap().shot_id, Some(ids[0]));
+
+        let mut turret = Turret::new(&config, Coord::new(10, 10));
+        turret.set_focus(Coord::new(10, 13));
+        let (state, _) = run_turret(&mut turret, &player, &config, opponent, 0.1);
+        assert_eq!(state.unwrap().shot_id, Some(ids[1]));
+        assert!(turret.focus.is_some());
+    }
+
+    #[test]
+    fn focus_out_of_scope_is_cleared() {
+        let mut config = test_config();
+        config.turret_target_priority = TurretTargetPriority::Nearest;
+        let player = Player::new(1, &config);
+        let mut opponent = create_opponent(&config, &[(11.0, 10.0), (10.0, 12.5)]);
+        let ids = get_probe_ids(&mut opponent);
+
+        let mut turret = Turret::new(&config, Coord::new(10, 10));
+        turret.set_focus(Coord::new(10, 15));
+        let (state, _) = run_turret(&mut turret, &player, &config, opponent, 0.1);
+        assert_eq!(state.unwrap().shot_id, Some(ids[0]));
+        assert!(turret.focus.is_none());
+    }
+
+    #[test]
+    fn upgraded_turret_one_shots_probe() {
+        let config = test_config();
+        let opponent = create_opponent(&config, &[(11.0, 10.0)]);
+
+        let player = Player::new(1, &config);
+        let mut turret = Turret::new(&config, Coord::new(10, 10));
+        let (_, mut opponent) = run_turret(&mut turret, &player, &config, opponent, 0.1);
+        let probe = opponent.iter_mut_probes().next().unwrap();
+        assert_eq!(probe.get_hp(), 1);
+
+        let mut player = Player::new(1, &config);
+        player.acquire_tech(Techs::TURRET_DAMAGE).unwrap();
+        let opponent = create_opponent(&config, &[(11.0, 10.0)]);
+        let mut turret = Turret::new(&config, Coord::new(10, 10));
+        let (_, mut opponent) = run_turret(&mut turret, &player, &config, opponent, 0.1);
+        let probe = opponent.iter_mut_probes().next().unwrap();
+        assert_eq!(probe.get_hp(), 0);
+    }
+
+    /// Return a player with 3 factories of 5 probes each, at random positions
+    fn create_random_player(config: &GameConfig, id: u128, rng: &mut GameRng) -> Player {
+        let mut player = Player::new(id, config);
+        for _ in 0..3 {
+            let mut factory = Factory::new(config, Coord::new(0, 0));
+            for _ in 0..5 {
+                let pos = Point::new(random::random(rng) * 20.0, random::random(rng) * 20.0);
+                factory.attach_probe(Probe::new(config, &player, pos));
+            }
+            player.factories.push(factory);
+        }
+        player
+    }
+
+    /// Return the target of the turret, checking all the probes of the opponents
+    fn get_brute_force_target(
+        turret: &Turret,
+        player: &Player,
+        opponents: &mut Vec<&mut Player>,
+    ) -> Option<u128> {
+        let scope = turret.get_scope(player);
+        let mut target: Option<(u128, (u32, f64))> = None;
+        for opp in opponents.iter_mut() {
+            for probe in opp.iter_mut_probes() {
+                if !turret.is_in_range(&probe.pos, scope) {
+                    continue;
+                }
+                let priority = turret.get_target_priority(probe);
+                let is_target = match target {
+                    None => true,
+                    Some((id, p)) => priority < p || (priority == p && probe.id < id),
+                };
+                if is_target {
+                    target = Some((probe.id, priority));
+                }
+            }
+        }
+        target.map(|(id, _)| id)
+    }
+
+    #[test]
+    fn grid_targets_match_brute_force() {
+        let mut config = test_config();
+        config.turret_target_priority = TurretTargetPriority::Nearest;
+        for seed in 0..50 {
+            let mut rng = GameRng::seed_from_u64(seed);
+            let mut players: Vec<Player> = (1..=4)
+                .map(|id| create_random_player(&config, id, &mut rng))
+                .collect();
+            let grid = ProbeGrid::build(&players, config.turret_scope, 0.0);
+            let player = players.remove(1);
+            // shift the indexes of the grid
+            if seed % 2 == 0 {
+                let factory_id = players[2].factories[0].id;
+                players[2].kill_factory(factory_id, FactoryDeathCause::Scrapped);
+            }
+            let mut opponents: Vec<&mut Player> = players.iter_mut().collect();
+
+            let pos = Coord::new(
+                (random::random(&mut rng) * 20.0) as i32,
+                (random::random(&mut rng) * 20.0) as i32,
+            );
+            let mut turret = Turret::new(&config, pos);
+            let expected = get_brute_force_target(&turret, &player, &mut opponents);
+            turret.handle_fire_probe(&player, &grid, &mut opponents);
+            let state = turret.state_handle.flush(&turret.id);
+            assert_eq!(state.and_then(|s| s.shot_id), expected, "seed: {}", seed);
+        }
+    }
+
+    #[test]
+    fn fire_cooldown_is_emitted_on_reload_and_ready() {
+        let config = test_config();
+        let player = Player::new(1, &config);
+        let mut opponent = create_opponent(&config, &[(11.0, 10.0)]);
+        let mut turret = Turret::new(&config, Coord::new(10, 10));
+        assert_eq!(turret.get_complete_state().fire_cooldown, Some(None));
+
+        let mut cooldowns = Vec::new();
+        let mut remainings = Vec::new();
+        for _ in 0..5 {
+            let (state, opp) = run_turret(&mut turret, &player, &config, opponent, 0.25);
+            opponent = opp;
+            cooldowns.push(state.and_then(|s| s.fire_cooldown));
+            remainings.push(turret.get_complete_state().fire_cooldown.unwrap());
+        }
+        // fired on the first frame, ready again after the fire delay (1 sec)
+        assert_eq!(
+            cooldowns,
+            vec![Some(Some(1.0)), None, None, None, Some(None)]
+        );
+        assert_eq!(
+            remainings,
+            vec![Some(1.0), Some(0.75), Some(0.5), Some(0.25), None]
+        );
+    }
+
+    #[test]
+    fn target_priority_modes() {
+        let mut config = test_config();
+        let player = Player::new(1, &config);
+        let mut map = Map::new(&config);
+        // a tile to attack
+        map.claim_tile(1, &Coord::new(15, 15), 2);
+        // nearest, damaged, attacking and out of scope probes
+        let positions = [(11.0, 10.0), (12.0, 10.0), (10.0, 12.5), (10.0, 14.0)];
+        let get_target = |config: &GameConfig, map: &mut Map| {
+            let mut opponent = create_opponent(config, &positions);
+            let ids = get_probe_ids(&mut opponent);
+            let mut probes: Vec<&mut Probe> = opponent.iter_mut_probes().collect();
+            probes[1].inflict_damage(1);
+            probes[2].set_attack(2, map);
+            let mut turret = Turret::new(config, Coord::new(10, 10));
+            let (state, _) = run_turret(&mut turret, &player, config, opponent, 0.1);
+            let shot_id = state.unwrap().shot_id.unwrap();
+            (ids.iter().position(|id| *id == shot_id).unwrap(), ids)
+        };
+
+        // ties broken by probe id, among the probes in scope
+        let (shot, ids) = get_target(&config, &mut map);
+        let first = (0..3).min_by_key(|i| ids[*i]).unwrap();
+        assert_eq!(shot, first);
+
+        config.turret_target_priority = TurretTargetPriority::Nearest;
+        assert_eq!(get_target(&config, &mut map).0, 0);
+        config.turret_target_priority = TurretTargetPriority::LowestHp;
+        assert_eq!(get_target(&config, &mut map).0, 1);
+        config.turret_target_priority = TurretTargetPriority::Attackers;
+        assert_eq!(get_target(&config, &mut map).0, 2);
+    }
+
+    #[test]
+    fn attackers_priority_falls_back_to_nearest() {
+        let mut config = test_config();
+        config.turret_target_priority = TurretTargetPriority::Attackers;
+        let player = Player::new(1, &config);
+        let mut opponent = create_opponent(&config, &[(12.0, 10.0), (11.0, 10.0)]);
+        let ids = get_probe_ids(&mut opponent);
+        let mut turret = Turret::new(&config, Coord::new(10, 10));
+        let (state, _) = run_turret(&mut turret, &player, &config, opponent, 0.1);
+        assert_eq!(state.unwrap().shot_id, Some(ids[1]));
+    }
+
+    #[test]
+    fn splash_damages_adjacent_probes() {
+        let mut config = test_config();
+        config.turret_target_priority = TurretTargetPriority::Nearest;
+        config.turret_splash_radius = 1.5;
+        config.turret_splash_damage = 1;
+        let player = Player::new(1, &config);
+        // the struck probe, an adjacent one and a distant one
+        let mut opponent = create_opponent(&config, &[(11.0, 10.0), (12.0, 10.0), (10.0, 12.5)]);
+        let ids = get_probe_ids(&mut opponent);
+
+        let mut turret = Turret::new(&config, Coord::new(10, 10));
+        let (state, mut opponent) = run_turret(&mut turret, &player, &config, opponent, 0.1);
+        let state = state.unwrap();
+        assert_eq!(state.shot_id, Some(ids[0]));
+        assert_eq!(state.splash_ids, vec![ids[1]]);
+        let hps: Vec<u32> = opponent.iter_mut_probes().map(|p| p.get_hp()).collect();
+        assert_eq!(hps, vec![1, 1, 2]);
+    }
+
+    #[test]
+    fn disk_matches_range() {
+        let config = test_config();
+        let turret = Turret::new(&config, Coord::new(10, 10));
+        for radius in [0.0, 1.0, 2.5] {
+            let coords = geometry::disk(&turret.pos, radius);
+            for coord in geometry::square(&turret.pos, 5) {
+                let is_in_range = turret.is_in_range(&coord.as_point(), radius);
+                assert_eq!(coords.contains(&coord), is_in_range);
+            }
+        }
+    }
+
+    #[test]
+    fn equidistant_probes_lower_id_is_shot() {
+        let mut config = test_config();
+        let player = Player::new(1, &config);
+        for priority in [TurretTargetPriority::First, TurretTargetPriority::Nearest] {
+            config.turret_target_priority = priority;
+            for _ in 0..20 {
+                let mut opponent = create_opponent(&config, &[(11.0, 10.0), (9.0, 10.0)]);
+                let ids = get_probe_ids(&mut opponent);
+                let mut turret = Turret::new(&config, Coord::new(10, 10));
+                let (state, _) = run_turret(&mut turret, &player, &config, opponent, 0.1);
+                assert_eq!(state.unwrap().shot_id, ids.iter().min().cloned());
+            }
+        }
+    }
+}
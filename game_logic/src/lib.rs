@@ -3,7 +3,11 @@ mod pybindings;
 
 use env_logger;
 use pybindings::{AsDict, FromDict};
-use pyo3::{exceptions, prelude::*, types::PyDict};
+use pyo3::{
+    exceptions,
+    prelude::*,
+    types::{PyBytes, PyDict, PyList},
+};
 
 #[pyclass]
 struct Game {
@@ -15,19 +19,192 @@ impl Game {
     #[new]
     fn new(player_ids: Vec<u128>, config: &PyDict) -> PyResult<Self> {
         let config = game::GameConfig::from_dict(&config)?;
+        if let Err(msg) = config.validate() {
+            return Err(PyErr::new::<exceptions::PyValueError, _>(msg));
+        }
+        if player_ids.len() != config.n_player as usize {
+            return Err(PyErr::new::<exceptions::PyValueError, _>(format!(
+                "Invalid n_player: doesn't match the number of players ({} != {})",
+                config.n_player,
+                player_ids.len()
+            )));
+        }
         Ok(Game {
             game: game::Game::new(player_ids, config),
         })
     }
 
+    /// Create a game from a state, as returned by `get_state`
+    #[staticmethod]
+    fn from_state(player_ids: Vec<u128>, config: &PyDict, state: &PyDict) -> PyResult<Self> {
+        let config = game::GameConfig::from_dict(&config)?;
+        let state = game::GameState::from_dict(&state)?;
+        match game::Game::from_state(player_ids, config, state) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(game) => Ok(Game { game: game }),
+        }
+    }
+
+    /// Create a game from a binary snapshot, as returned by `get_snapshot`
+    #[staticmethod]
+    fn restore(snapshot: &[u8]) -> PyResult<Self> {
+        match game::Game::restore(snapshot) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(game) => Ok(Game { game: game }),
+        }
+    }
+
+    /// Return a binary snapshot of the game,
+    /// faster than `get_state` for checkpointing
+    pub fn get_snapshot<'a>(&self, _py: Python<'a>) -> PyResult<&'a PyBytes> {
+        match self.game.snapshot() {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(bytes) => Ok(PyBytes::new(_py, &bytes)),
+        }
+    }
+
     pub fn get_state<'a>(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
         self.game.get_complete_state().to_dict(_py)
     }
 
+    /// Return the state of the game as seen by the player
+    /// (only the visible part of the map)
+    pub fn get_state_for<'a>(&self, _py: Python<'a>, player_id: u128) -> PyResult<&'a PyDict> {
+        match self.game.get_state_for_player(player_id) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(state) => state.to_dict(_py),
+        }
+    }
+
+    /// Return the complete state of the player only
+    /// (without the map and the opponents)
+    pub fn get_player_state<'a>(&self, _py: Python<'a>, player_id: u128) -> PyResult<&'a PyDict> {
+        match self.game.get_player_state(player_id) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(state) => state.to_dict(_py),
+        }
+    }
+
+    /// Return the (immutable) config of the game, as given on creation
+    pub fn get_config<'a>(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        self.game.get_config().to_dict(_py)
+    }
+
     pub fn get_stats<'a>(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
         self.game.get_players_stats().to_dict(_py)
     }
 
+    /// Return a snapshot of the game for the player to resume it
+    /// (ex: on reconnection)
+    pub fn get_resume_payload<'a>(&self, _py: Python<'a>, player_id: u128) -> PyResult<&'a PyDict> {
+        match self.game.get_resume_payload(player_id) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(payload) => payload.to_dict(_py),
+        }
+    }
+
+    /// Return the result of the game, `None` while the game is running
+    pub fn get_result<'a>(&self, _py: Python<'a>) -> PyResult<Option<&'a PyDict>> {
+        match self.game.get_result() {
+            None => Ok(None),
+            Some(result) => Ok(Some(result.to_dict(_py)?)),
+        }
+    }
+
+    /// Return the score of each living player, from the highest
+    /// to the lowest, as a list of `{player_id, score}`
+    pub fn get_scores<'a>(&self, _py: Python<'a>) -> PyResult<Vec<&'a PyDict>> {
+        let mut scores = Vec::new();
+        for (player_id, score) in self.game.get_scores() {
+            let dict = PyDict::new(_py);
+            dict.set_item("player_id", player_id)?;
+            dict.set_item("score", score)?;
+            scores.push(dict);
+        }
+        Ok(scores)
+    }
+
+    pub fn get_occupation_grid<'a>(&self, _py: Python<'a>) -> PyResult<&'a PyList> {
+        Ok(PyList::new(_py, self.game.get_occupation_grid()))
+    }
+
+    pub fn get_effective_stats<'a>(
+        &self,
+        _py: Python<'a>,
+        player_id: u128,
+    ) -> PyResult<&'a PyDict> {
+        match self.game.get_effective_stats(player_id) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(stats) => stats.to_dict(_py),
+        }
+    }
+
+    pub fn get_buildable_tiles<'a>(
+        &self,
+        _py: Python<'a>,
+        player_id: u128,
+    ) -> PyResult<Vec<&'a PyDict>> {
+        let coords = match self.game.get_buildable_tiles(player_id) {
+            Err(msg) => {
+                return Err(PyErr::new::<exceptions::PyValueError, _>(msg));
+            }
+            Ok(coords) => coords,
+        };
+        let mut dicts = Vec::with_capacity(coords.len());
+        for coord in coords.iter() {
+            dicts.push(coord.to_dict(_py)?);
+        }
+        Ok(dicts)
+    }
+
+    pub fn get_expansion_frontier<'a>(
+        &self,
+        _py: Python<'a>,
+        player_id: u128,
+    ) -> PyResult<Vec<&'a PyDict>> {
+        let coords = match self.game.get_expansion_frontier(player_id) {
+            Err(msg) => {
+                return Err(PyErr::new::<exceptions::PyValueError, _>(msg));
+            }
+            Ok(coords) => coords,
+        };
+        let mut dicts = Vec::with_capacity(coords.len());
+        for coord in coords.iter() {
+            dicts.push(coord.to_dict(_py)?);
+        }
+        Ok(dicts)
+    }
+
+    pub fn get_building_footprint<'a>(
+        &self,
+        _py: Python<'a>,
+        building_id: u128,
+    ) -> PyResult<Vec<&'a PyDict>> {
+        let coords = match self.game.get_building_footprint(building_id) {
+            Err(msg) => {
+                return Err(PyErr::new::<exceptions::PyValueError, _>(msg));
+            }
+            Ok(coords) => coords,
+        };
+        let mut dicts = Vec::with_capacity(coords.len());
+        for coord in coords.iter() {
+            dicts.push(coord.to_dict(_py)?);
+        }
+        Ok(dicts)
+    }
+
+    pub fn get_living_player_count(&self) -> usize {
+        self.game.get_living_player_count()
+    }
+
+    pub fn get_living_player_ids(&self) -> Vec<u128> {
+        self.game.get_living_player_ids()
+    }
+
+    pub fn describe(&self) -> String {
+        self.game.describe()
+    }
+
     pub fn run<'a>(&mut self, _py: Python<'a>, dt: f64) -> PyResult<Option<&'a PyDict>> {
         log::debug!("[lib.rs] run...");
         let state = self.game.run(dt);
@@ -45,6 +222,20 @@ impl Game {
         }
     }
 
+    pub fn action_abandon<'a>(&mut self, _py: Python<'a>, player_id: u128) -> PyResult<()> {
+        match self.game.abandon_player(player_id) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
+
+    pub fn action_resume<'a>(&mut self, _py: Python<'a>, player_id: u128) -> PyResult<()> {
+        match self.game.resume_player(player_id) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
+
     pub fn action_build_factory<'a>(
         &mut self,
         _py: Python<'a>,
@@ -109,6 +300,210 @@ impl Game {
         }
     }
 
+    pub fn action_create_group<'a>(
+        &mut self,
+        _py: Python<'a>,
+        player_id: u128,
+        name: String,
+    ) -> PyResult<u128> {
+        match self.game.create_group(player_id, name) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
+
+    pub fn action_assign_probes<'a>(
+        &mut self,
+        _py: Python<'a>,
+        player_id: u128,
+        group_id: u128,
+        ids: Vec<u128>,
+    ) -> PyResult<()> {
+        match self.game.assign_probes(player_id, group_id, ids) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
+
+    pub fn action_move_group<'a>(
+        &mut self,
+        _py: Python<'a>,
+        player_id: u128,
+        group_id: u128,
+        target_x: i32,
+        target_y: i32,
+    ) -> PyResult<()> {
+        match self
+            .game
+            .move_group(player_id, group_id, target_x, target_y)
+        {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
+
+    pub fn action_explode_group<'a>(
+        &mut self,
+        _py: Python<'a>,
+        player_id: u128,
+        group_id: u128,
+    ) -> PyResult<()> {
+        match self.game.explode_group(player_id, group_id) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
+
+    pub fn action_group_attack<'a>(
+        &mut self,
+        _py: Python<'a>,
+        player_id: u128,
+        group_id: u128,
+    ) -> PyResult<()> {
+        match self.game.group_attack(player_id, group_id) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
+
+    pub fn action_hold_probes<'a>(
+        &mut self,
+        _py: Python<'a>,
+        player_id: u128,
+        ids: Vec<u128>,
+    ) -> PyResult<()> {
+        match self.game.hold_probes(player_id, ids) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
+
+    pub fn action_stop_probes<'a>(
+        &mut self,
+        _py: Python<'a>,
+        player_id: u128,
+        ids: Vec<u128>,
+    ) -> PyResult<()> {
+        match self.game.stop_probes(player_id, ids) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
+
+    pub fn action_farm_region<'a>(
+        &mut self,
+        _py: Python<'a>,
+        player_id: u128,
+        ids: Vec<u128>,
+        center_x: i32,
+        center_y: i32,
+        radius: u32,
+    ) -> PyResult<()> {
+        match self
+            .game
+            .farm_region_probes(player_id, ids, center_x, center_y, radius)
+        {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
+
+    pub fn action_patrol_probes<'a>(
+        &mut self,
+        _py: Python<'a>,
+        player_id: u128,
+        ids: Vec<u128>,
+        a_x: i32,
+        a_y: i32,
+        b_x: i32,
+        b_y: i32,
+    ) -> PyResult<()> {
+        match self.game.patrol_probes(player_id, ids, a_x, a_y, b_x, b_y) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
+
+    pub fn action_siege_probes<'a>(
+        &mut self,
+        _py: Python<'a>,
+        player_id: u128,
+        ids: Vec<u128>,
+        target_x: i32,
+        target_y: i32,
+    ) -> PyResult<()> {
+        match self.game.siege_probes(player_id, ids, target_x, target_y) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
+
+    pub fn action_picket_probes<'a>(
+        &mut self,
+        _py: Python<'a>,
+        player_id: u128,
+        ids: Vec<u128>,
+    ) -> PyResult<()> {
+        match self.game.picket_probes(player_id, ids) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
+
+    pub fn action_set_default_probe_policy<'a>(
+        &mut self,
+        _py: Python<'a>,
+        player_id: u128,
+        policy: &str,
+    ) -> PyResult<()> {
+        match self.game.set_default_probe_policy(player_id, policy) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
+
+    pub fn action_set_factory_rally<'a>(
+        &mut self,
+        _py: Python<'a>,
+        player_id: u128,
+        factory_id: u128,
+        target_x: i32,
+        target_y: i32,
+    ) -> PyResult<()> {
+        match self
+            .game
+            .set_factory_rally(player_id, factory_id, target_x, target_y)
+        {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
+
+    pub fn action_set_factory_policy<'a>(
+        &mut self,
+        _py: Python<'a>,
+        player_id: u128,
+        factory_id: u128,
+        policy: &str,
+    ) -> PyResult<()> {
+        match self.game.set_factory_policy(player_id, factory_id, policy) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
+
+    pub fn action_reexpand_factory<'a>(
+        &mut self,
+        _py: Python<'a>,
+        player_id: u128,
+        factory_id: u128,
+    ) -> PyResult<()> {
+        match self.game.reexpand_factory(player_id, factory_id) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
+
     pub fn action_acquire_tech<'a>(
         &mut self,
         _py: Python<'a>,
@@ -120,6 +515,68 @@ impl Game {
             Ok(v) => Ok(v),
         }
     }
+
+    pub fn action_refund_tech<'a>(
+        &mut self,
+        _py: Python<'a>,
+        player_id: u128,
+        tech: &str,
+    ) -> PyResult<()> {
+        match self.game.refund_tech(player_id, tech) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
+
+    pub fn action_scrap_factory<'a>(
+        &mut self,
+        _py: Python<'a>,
+        player_id: u128,
+        factory_id: u128,
+    ) -> PyResult<()> {
+        match self.game.scrap_factory(player_id, factory_id) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
+
+    pub fn action_focus_turret<'a>(
+        &mut self,
+        _py: Python<'a>,
+        player_id: u128,
+        turret_id: u128,
+        target_x: i32,
+        target_y: i32,
+    ) -> PyResult<()> {
+        match self
+            .game
+            .focus_turret(player_id, turret_id, target_x, target_y)
+        {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
+
+    pub fn action_scrap_turret<'a>(
+        &mut self,
+        _py: Python<'a>,
+        player_id: u128,
+        turret_id: u128,
+    ) -> PyResult<()> {
+        match self.game.scrap_turret(player_id, turret_id) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
+
+    /// Perform a batch of binary encoded actions
+    /// (see `game::protocol` for the wire format)
+    pub fn action_apply_bytes<'a>(&mut self, _py: Python<'a>, buf: &[u8]) -> PyResult<()> {
+        match self.game.apply_actions_bytes(buf) {
+            Err(msg) => Err(PyErr::new::<exceptions::PyValueError, _>(msg)),
+            Ok(v) => Ok(v),
+        }
+    }
 }
 
 #[pyfunction]
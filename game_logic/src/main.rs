@@ -1,5 +1,7 @@
 mod game;
 
+use std::collections::HashMap;
+
 use game::*;
 
 fn display(origin: &Coord, coords: &Vec<Coord>) {
@@ -20,29 +22,61 @@ fn display(origin: &Coord, coords: &Vec<Coord>) {
 fn test_game() {
     let config = GameConfig {
         dim: Coord { x: 10, y: 10 },
+        obstacles: Vec::new(),
+        bonus_tiles: Vec::new(),
+        vision_radius: 0.0,
         n_player: 3,
+        symmetric_spawns: false,
+        symmetric_layout: false,
         initial_money: 20.0,
         initial_n_probes: 3,
         base_income: 0.0,
+        align_income_ticks: false,
         building_occupation_min: 0,
+        building_surcharge: 0.0,
+        building_surcharge_distance: 0,
         factory_price: 0.0,
         factory_expansion_size: 4,
+        factory_expansion_cost: 0.0,
         factory_max_probe: 0,
+        max_probes_total: 0,
         factory_build_probe_delay: 0.0,
         max_occupation: 0,
         probe_speed: 0.0,
         probe_hp: 0,
+        probe_hp_regen_per_sec: 0.0,
         probe_price: 0.0,
+        probe_lifespan: 0.0,
+        probe_max_range: 0.0,
         probe_claim_delay: 0.0,
         factory_maintenance_costs: 0.0,
         probe_maintenance_costs: 0.0,
         turret_price: 0.0,
+        turret_scrap_refund_rate: 0.0,
         turret_damage: 0,
         turret_fire_delay: 0.0,
         turret_scope: 0.0,
+        turret_target_priority: TurretTargetPriority::First,
+        turret_splash_radius: 0.0,
+        turret_splash_damage: 0,
         turret_maintenance_costs: 0.0,
         income_rate: 0.0,
+        occupation_upkeep_rate: 0.0,
+        income_occupation_min: 0,
+        first_blood_bonus: 0.0,
+        kill_streak_bounty: 0.0,
+        max_events_per_frame: 0,
+        tick_rate: 0.0,
+        parallel: false,
+        time_limit: 0.0,
+        score_weights: ScoreWeights {
+            occupation: 1.0,
+            money: 0.0,
+            buildings: 0.0,
+        },
+        building_upkeep_occupation: 0,
         deprecate_rate: 0.0,
+        occupation_spread_rate: 0.0,
         tech_probe_explosion_intensity_increase: 0,
         tech_probe_explosion_intensity_price: 0.0,
         tech_probe_claim_intensity_increase: 0,
@@ -59,10 +93,30 @@ fn test_game() {
         tech_turret_fire_delay_price: 0.0,
         tech_turret_maintenance_costs_decrease: 0.0,
         tech_turret_maintenance_costs_price: 0.0,
+        tech_turret_damage_increase: 0,
+        tech_turret_damage_price: 0.0,
+        tech_refund_rate: 0.0,
+        factions: Vec::new(),
+        player_factions: Vec::new(),
+        per_player_overrides: HashMap::new(),
+        tech_exclusion_groups: Techs::default_exclusion_groups(),
         tech_probe_hp_increase: 0,
         tech_probe_hp_price: 0.0,
+        tech_probe_speed_increase: 0.0,
+        tech_probe_speed_price: 0.0,
         probe_claim_intensity: 0,
         probe_explosion_intensity: 0,
+        probe_explosion_reinforce: false,
+        probe_idle_without_target: false,
+        probe_farm_prefer_own: false,
+        adjacency: Adjacency::VonNeumann,
+        farm_capture_max_enemy_occupation: 3,
+        probe_avoid_turrets: false,
+        probe_picket_intensity_factor: 1.0,
+        probe_separation: 0.0,
+        probe_reinforce_distance: 0.0,
+        probe_attack_prefer_buildings: false,
+        probe_attack_spread: false,
     };
     let player_ids = vec![1, 2, 3];
     let mut game = Game::new(player_ids, config);
@@ -1,10 +1,14 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::game::PlayerStats;
+use crate::game::{
+    Adjacency, GameEvent, IncomeBreakdown, PlayerEffectiveStats, PlayerStats, ProbePolicy, State,
+    Techs, TurretTargetPriority,
+};
 
 use super::game::{
-    Coord, FactoryState, GameConfig, GameState, MapState, PlayerState, Point, ProbeState,
-    TileState, TurretState, NOT_IDENTIFIABLE,
+    Coord, FactionConfig, FactoryState, GameConfig, GameResult, GameState, MapState, PartialConfig,
+    PlayerState, Point, ProbeGroup, ProbeState, ResumePayload, ScoreWeights, TileState,
+    TurretState, NOT_IDENTIFIABLE,
 };
 use pyo3::{exceptions, types::PyDict, FromPyObject, PyErr, PyResult, Python, ToPyObject};
 
@@ -81,6 +85,48 @@ where
     }
 }
 
+/// Extract item from a dict,
+/// return `default` in case the key is missing
+fn get_item_or<'a, T>(dict: &'a PyDict, key: &str, default: T) -> PyResult<T>
+where
+    T: FromPyObject<'a>,
+{
+    match dict.get_item(key) {
+        Some(x) => Ok(x.extract::<'a, T>()?),
+        None => Ok(default),
+    }
+}
+
+/// Extract a dict item from a dict (if present)
+fn get_dict_item<T>(dict: &PyDict, key: &str) -> PyResult<Option<T>>
+where
+    T: FromDict,
+{
+    match dict.get_item(key) {
+        Some(x) => Ok(Some(T::from_dict(x.extract()?)?)),
+        None => Ok(None),
+    }
+}
+
+/// Extract a list of dict items from a dict,
+/// return an empty vec in case the key is missing
+fn get_vec_dict_item<T>(dict: &PyDict, key: &str) -> PyResult<Vec<T>>
+where
+    T: FromDict,
+{
+    let dicts: Vec<&PyDict> = get_item_or(dict, key, Vec::new())?;
+    let mut items = Vec::with_capacity(dicts.len());
+    for dict in dicts {
+        items.push(T::from_dict(dict)?);
+    }
+    Ok(items)
+}
+
+/// Convert an error message of the game into a python error
+fn to_py_err(msg: String) -> PyErr {
+    PyErr::new::<exceptions::PyValueError, _>(msg)
+}
+
 impl<'a, K, V> AsDict<'a> for HashMap<K, V>
 where
     V: AsDict<'a>,
@@ -113,6 +159,16 @@ impl<'a> AsDict<'a> for Point {
     }
 }
 
+/// Bonus tile (see `GameConfig.bonus_tiles`)
+impl<'a> AsDict<'a> for (Coord, f64) {
+    fn to_dict(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        let dict = PyDict::new(_py);
+        dict.set_item("coord", self.0.to_dict(_py)?)?;
+        dict.set_item("bonus", self.1)?;
+        Ok(dict)
+    }
+}
+
 impl<'a> AsDict<'a> for TileState {
     fn to_dict(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
         let dict = PyDict::new(_py);
@@ -120,6 +176,13 @@ impl<'a> AsDict<'a> for TileState {
         set_dict_item(_py, dict, "coord", &self.coord)?;
         set_item(dict, "occupation", &self.occupation)?;
         set_item(dict, "owner_id", &self.owner_id)?;
+        set_item(dict, "previous_owner_id", &self.previous_owner_id)?;
+        set_item(dict, "is_obstacle", &self.is_obstacle)?;
+        set_item(dict, "bonus", &self.bonus)?;
+
+        if let Some(change) = &self.change {
+            dict.set_item("change", format!("{:?}", change))?;
+        }
 
         Ok(dict)
     }
@@ -142,12 +205,22 @@ impl<'a> AsDict<'a> for ProbeState {
         if let Some(death) = &self.death {
             dict.set_item("death", format!("{:?}", death))?;
         }
-        if let Some(policy) = &self.policy {
-            dict.set_item("policy", format!("{:?}", policy))?;
+        match &self.policy {
+            Some(ProbePolicy::Patrol { a, b }) => {
+                dict.set_item("policy", "Patrol")?;
+                dict.set_item("patrol", vec![a.to_dict(_py)?, b.to_dict(_py)?])?;
+            }
+            Some(policy) => {
+                dict.set_item("policy", format!("{:?}", policy))?;
+            }
+            None => {}
         }
 
         set_dict_item(_py, dict, "pos", &self.pos)?;
         set_dict_item(_py, dict, "target", &self.target)?;
+        set_item(dict, "hp", &self.hp)?;
+        set_item(dict, "max_hp", &self.max_hp)?;
+        set_item(dict, "travelled", &self.travelled)?;
 
         Ok(dict)
     }
@@ -163,6 +236,7 @@ impl<'a> AsDict<'a> for FactoryState {
         }
 
         set_dict_item(_py, dict, "coord", &self.coord)?;
+        set_item(dict, "at_risk", &self.at_risk)?;
         set_vec_dict_item(_py, dict, "probes", &self.probes)?;
 
         Ok(dict)
@@ -179,6 +253,13 @@ impl<'a> AsDict<'a> for TurretState {
         }
         set_dict_item(_py, dict, "coord", &self.coord)?;
         set_item(dict, "shot_id", &self.shot_id)?;
+        if !self.splash_ids.is_empty() {
+            dict.set_item("splash_ids", self.splash_ids.clone())?;
+        }
+        set_item(dict, "at_risk", &self.at_risk)?;
+        if let Some(fire_cooldown) = &self.fire_cooldown {
+            dict.set_item("fire_cooldown", fire_cooldown)?;
+        }
 
         Ok(dict)
     }
@@ -196,6 +277,14 @@ impl<'a> AsDict<'a> for PlayerState {
 
         set_item(dict, "money", &self.money)?;
         set_item(dict, "income", &self.income)?;
+        set_dict_item(_py, dict, "income_breakdown", &self.income_breakdown)?;
+
+        if let Some(policy) = &self.default_probe_policy {
+            dict.set_item("default_probe_policy", format!("{:?}", policy))?;
+        }
+        if let Some(groups) = &self.groups {
+            set_vec_dict_item(_py, dict, "groups", groups)?;
+        }
         set_vec_dict_item(_py, dict, "factories", &self.factories)?;
         set_vec_dict_item(_py, dict, "turrets", &self.turrets)?;
 
@@ -205,6 +294,30 @@ impl<'a> AsDict<'a> for PlayerState {
         }
         dict.set_item("techs", techs)?;
 
+        if !self.refunded_techs.is_empty() {
+            let techs: Vec<String> = self
+                .refunded_techs
+                .iter()
+                .map(|t| format!("{:?}", t))
+                .collect();
+            dict.set_item("refunded_techs", techs)?;
+        }
+
+        Ok(dict)
+    }
+}
+
+impl<'a> AsDict<'a> for ProbeGroup {
+    fn to_dict(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        let dict = PyDict::new(_py);
+
+        dict.set_item("id", self.id)?;
+        dict.set_item("name", &self.name)?;
+        dict.set_item(
+            "probe_ids",
+            self.probe_ids.iter().cloned().collect::<Vec<u128>>(),
+        )?;
+
         Ok(dict)
     }
 }
@@ -219,6 +332,68 @@ impl<'a> AsDict<'a> for MapState {
     }
 }
 
+impl<'a> AsDict<'a> for GameEvent {
+    fn to_dict(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        let dict = PyDict::new(_py);
+
+        match self {
+            GameEvent::IncomeNegative { player_id, income } => {
+                dict.set_item("type", "IncomeNegative")?;
+                dict.set_item("player_id", player_id)?;
+                dict.set_item("income", income)?;
+            }
+            GameEvent::FirstBlood { player_id, bonus } => {
+                dict.set_item("type", "FirstBlood")?;
+                dict.set_item("player_id", player_id)?;
+                dict.set_item("bonus", bonus)?;
+            }
+            GameEvent::KillStreak {
+                player_id,
+                streak,
+                bounty,
+            } => {
+                dict.set_item("type", "KillStreak")?;
+                dict.set_item("player_id", player_id)?;
+                dict.set_item("streak", streak)?;
+                dict.set_item("bounty", bounty)?;
+            }
+            GameEvent::ProbeExploded {
+                player_id,
+                probe_id,
+                coord,
+            } => {
+                dict.set_item("type", "ProbeExploded")?;
+                dict.set_item("player_id", player_id)?;
+                dict.set_item("probe_id", probe_id)?;
+                dict.set_item("coord", coord.to_dict(_py)?)?;
+            }
+            GameEvent::BuildingConquered {
+                player_id,
+                building_id,
+            } => {
+                dict.set_item("type", "BuildingConquered")?;
+                dict.set_item("player_id", player_id)?;
+                dict.set_item("building_id", building_id)?;
+            }
+            GameEvent::TechAcquired { player_id, tech } => {
+                dict.set_item("type", "TechAcquired")?;
+                dict.set_item("player_id", player_id)?;
+                dict.set_item("tech", format!("{:?}", tech))?;
+            }
+            GameEvent::PlayerDefeated { player_id } => {
+                dict.set_item("type", "PlayerDefeated")?;
+                dict.set_item("player_id", player_id)?;
+            }
+            GameEvent::EventsTruncated { n_dropped } => {
+                dict.set_item("type", "EventsTruncated")?;
+                dict.set_item("n_dropped", n_dropped)?;
+            }
+        }
+
+        Ok(dict)
+    }
+}
+
 impl<'a> AsDict<'a> for GameState {
     fn to_dict(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
         let dict = PyDict::new(_py);
@@ -226,6 +401,251 @@ impl<'a> AsDict<'a> for GameState {
         dict.set_item("game_ended", self.game_ended)?;
         set_dict_item(_py, dict, "map", &self.map)?;
         set_vec_dict_item(_py, dict, "players", &self.players)?;
+        set_vec_dict_item(_py, dict, "events", &self.events)?;
+
+        Ok(dict)
+    }
+}
+
+impl<'a> AsDict<'a> for ResumePayload {
+    fn to_dict(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        let dict = PyDict::new(_py);
+
+        dict.set_item("schema_version", self.schema_version)?;
+        dict.set_item("player_id", self.player_id)?;
+        dict.set_item("state", self.state.to_dict(_py)?)?;
+        dict.set_item("effective_stats", self.effective_stats.to_dict(_py)?)?;
+        dict.set_item("frame", self.frame)?;
+        dict.set_item("time", self.time)?;
+        dict.set_item("config", self.config.to_dict(_py)?)?;
+
+        Ok(dict)
+    }
+}
+
+impl<'a> AsDict<'a> for GameResult {
+    fn to_dict(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        let dict = PyDict::new(_py);
+
+        dict.set_item("winner_id", self.winner_id)?;
+        dict.set_item("is_draw", self.winner_id.is_none())?;
+        dict.set_item("rankings", self.rankings.clone())?;
+        dict.set_item("n_frames", self.n_frames)?;
+        dict.set_item("duration", self.duration)?;
+
+        Ok(dict)
+    }
+}
+
+/// Note: the keys are the same as expected by `GameConfig::from_dict`
+impl<'a> AsDict<'a> for GameConfig {
+    fn to_dict(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        let dict = PyDict::new(_py);
+
+        dict.set_item("dim", self.dim.to_dict(_py)?)?;
+        set_vec_dict_item(_py, dict, "obstacles", &self.obstacles)?;
+        set_vec_dict_item(_py, dict, "bonus_tiles", &self.bonus_tiles)?;
+        dict.set_item("vision_radius", self.vision_radius)?;
+        dict.set_item("n_player", self.n_player)?;
+        dict.set_item("symmetric_spawns", self.symmetric_spawns)?;
+        dict.set_item("symmetric_layout", self.symmetric_layout)?;
+        dict.set_item("initial_money", self.initial_money)?;
+        dict.set_item("initial_n_probes", self.initial_n_probes)?;
+        dict.set_item("base_income", self.base_income)?;
+        dict.set_item("align_income_ticks", self.align_income_ticks)?;
+        dict.set_item("building_occupation_min", self.building_occupation_min)?;
+        dict.set_item("building_surcharge", self.building_surcharge)?;
+        dict.set_item(
+            "building_surcharge_distance",
+            self.building_surcharge_distance,
+        )?;
+        dict.set_item("factory_price", self.factory_price)?;
+        dict.set_item("factory_expansion_size", self.factory_expansion_size)?;
+        dict.set_item("factory_expansion_cost", self.factory_expansion_cost)?;
+        dict.set_item("factory_maintenance_costs", self.factory_maintenance_costs)?;
+        dict.set_item("factory_max_probe", self.factory_max_probe)?;
+        dict.set_item("max_probes_total", self.max_probes_total)?;
+        dict.set_item("factory_build_probe_delay", self.factory_build_probe_delay)?;
+        dict.set_item("max_occupation", self.max_occupation)?;
+        dict.set_item("probe_speed", self.probe_speed)?;
+        dict.set_item("probe_hp", self.probe_hp)?;
+        dict.set_item("probe_hp_regen_per_sec", self.probe_hp_regen_per_sec)?;
+        dict.set_item("probe_claim_intensity", self.probe_claim_intensity)?;
+        dict.set_item("probe_explosion_intensity", self.probe_explosion_intensity)?;
+        dict.set_item("probe_explosion_reinforce", self.probe_explosion_reinforce)?;
+        dict.set_item("probe_idle_without_target", self.probe_idle_without_target)?;
+        dict.set_item("probe_farm_prefer_own", self.probe_farm_prefer_own)?;
+        dict.set_item("adjacency", format!("{:?}", self.adjacency))?;
+        dict.set_item(
+            "farm_capture_max_enemy_occupation",
+            self.farm_capture_max_enemy_occupation,
+        )?;
+        dict.set_item("probe_avoid_turrets", self.probe_avoid_turrets)?;
+        dict.set_item(
+            "probe_picket_intensity_factor",
+            self.probe_picket_intensity_factor,
+        )?;
+        dict.set_item("probe_separation", self.probe_separation)?;
+        dict.set_item("probe_reinforce_distance", self.probe_reinforce_distance)?;
+        dict.set_item(
+            "probe_attack_prefer_buildings",
+            self.probe_attack_prefer_buildings,
+        )?;
+        dict.set_item("probe_attack_spread", self.probe_attack_spread)?;
+        dict.set_item("probe_price", self.probe_price)?;
+        dict.set_item("probe_lifespan", self.probe_lifespan)?;
+        dict.set_item("probe_max_range", self.probe_max_range)?;
+        dict.set_item("probe_claim_delay", self.probe_claim_delay)?;
+        dict.set_item("probe_maintenance_costs", self.probe_maintenance_costs)?;
+        dict.set_item("turret_price", self.turret_price)?;
+        dict.set_item("turret_scrap_refund_rate", self.turret_scrap_refund_rate)?;
+        dict.set_item("turret_damage", self.turret_damage)?;
+        dict.set_item("turret_fire_delay", self.turret_fire_delay)?;
+        dict.set_item("turret_scope", self.turret_scope)?;
+        dict.set_item(
+            "turret_target_priority",
+            format!("{:?}", self.turret_target_priority),
+        )?;
+        dict.set_item("turret_splash_radius", self.turret_splash_radius)?;
+        dict.set_item("turret_splash_damage", self.turret_splash_damage)?;
+        dict.set_item("turret_maintenance_costs", self.turret_maintenance_costs)?;
+        dict.set_item("income_rate", self.income_rate)?;
+        dict.set_item("occupation_upkeep_rate", self.occupation_upkeep_rate)?;
+        dict.set_item("income_occupation_min", self.income_occupation_min)?;
+        dict.set_item("first_blood_bonus", self.first_blood_bonus)?;
+        dict.set_item("kill_streak_bounty", self.kill_streak_bounty)?;
+        dict.set_item("max_events_per_frame", self.max_events_per_frame)?;
+        dict.set_item("tick_rate", self.tick_rate)?;
+        dict.set_item("parallel", self.parallel)?;
+        dict.set_item("time_limit", self.time_limit)?;
+        dict.set_item("score_weights", self.score_weights.to_dict(_py)?)?;
+        dict.set_item(
+            "building_upkeep_occupation",
+            self.building_upkeep_occupation,
+        )?;
+        dict.set_item("deprecate_rate", self.deprecate_rate)?;
+        dict.set_item("occupation_spread_rate", self.occupation_spread_rate)?;
+        dict.set_item(
+            "tech_probe_explosion_intensity_increase",
+            self.tech_probe_explosion_intensity_increase,
+        )?;
+        dict.set_item(
+            "tech_probe_explosion_intensity_price",
+            self.tech_probe_explosion_intensity_price,
+        )?;
+        dict.set_item(
+            "tech_probe_claim_intensity_increase",
+            self.tech_probe_claim_intensity_increase,
+        )?;
+        dict.set_item(
+            "tech_probe_claim_intensity_price",
+            self.tech_probe_claim_intensity_price,
+        )?;
+        dict.set_item("tech_probe_hp_increase", self.tech_probe_hp_increase)?;
+        dict.set_item("tech_probe_hp_price", self.tech_probe_hp_price)?;
+        dict.set_item("tech_probe_speed_increase", self.tech_probe_speed_increase)?;
+        dict.set_item("tech_probe_speed_price", self.tech_probe_speed_price)?;
+        dict.set_item(
+            "tech_factory_build_delay_decrease",
+            self.tech_factory_build_delay_decrease,
+        )?;
+        dict.set_item(
+            "tech_factory_build_delay_price",
+            self.tech_factory_build_delay_price,
+        )?;
+        dict.set_item(
+            "tech_factory_probe_price_decrease",
+            self.tech_factory_probe_price_decrease,
+        )?;
+        dict.set_item(
+            "tech_factory_probe_price_price",
+            self.tech_factory_probe_price_price,
+        )?;
+        dict.set_item(
+            "tech_factory_max_probe_increase",
+            self.tech_factory_max_probe_increase,
+        )?;
+        dict.set_item(
+            "tech_factory_max_probe_price",
+            self.tech_factory_max_probe_price,
+        )?;
+        dict.set_item(
+            "tech_turret_scope_increase",
+            self.tech_turret_scope_increase,
+        )?;
+        dict.set_item("tech_turret_scope_price", self.tech_turret_scope_price)?;
+        dict.set_item(
+            "tech_turret_fire_delay_decrease",
+            self.tech_turret_fire_delay_decrease,
+        )?;
+        dict.set_item(
+            "tech_turret_fire_delay_price",
+            self.tech_turret_fire_delay_price,
+        )?;
+        dict.set_item(
+            "tech_turret_maintenance_costs_decrease",
+            self.tech_turret_maintenance_costs_decrease,
+        )?;
+        dict.set_item(
+            "tech_turret_maintenance_costs_price",
+            self.tech_turret_maintenance_costs_price,
+        )?;
+        dict.set_item(
+            "tech_turret_damage_increase",
+            self.tech_turret_damage_increase,
+        )?;
+        dict.set_item("tech_turret_damage_price", self.tech_turret_damage_price)?;
+        dict.set_item("tech_refund_rate", self.tech_refund_rate)?;
+        set_vec_dict_item(_py, dict, "factions", &self.factions)?;
+        dict.set_item("player_factions", self.player_factions.clone())?;
+        dict.set_item(
+            "per_player_overrides",
+            self.per_player_overrides.to_dict(_py)?,
+        )?;
+
+        let mut groups = Vec::with_capacity(self.tech_exclusion_groups.len());
+        for group in self.tech_exclusion_groups.iter() {
+            let mut techs: Vec<String> = group.iter().map(|t| format!("{:?}", t)).collect();
+            techs.sort();
+            groups.push(techs);
+        }
+        dict.set_item("tech_exclusion_groups", groups)?;
+
+        Ok(dict)
+    }
+}
+
+impl<'a> AsDict<'a> for FactionConfig {
+    fn to_dict(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        let dict = PyDict::new(_py);
+
+        let techs: Vec<String> = self.techs.iter().map(|t| format!("{:?}", t)).collect();
+        dict.set_item("techs", techs)?;
+        dict.set_item("tech_price_factor", self.tech_price_factor)?;
+
+        Ok(dict)
+    }
+}
+
+impl<'a> AsDict<'a> for PartialConfig {
+    fn to_dict(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        let dict = PyDict::new(_py);
+
+        set_item(dict, "initial_money", &self.initial_money)?;
+        set_item(dict, "income_rate", &self.income_rate)?;
+        set_item(dict, "probe_speed", &self.probe_speed)?;
+
+        Ok(dict)
+    }
+}
+
+impl<'a> AsDict<'a> for ScoreWeights {
+    fn to_dict(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        let dict = PyDict::new(_py);
+
+        dict.set_item("occupation", self.occupation)?;
+        dict.set_item("money", self.money)?;
+        dict.set_item("buildings", self.buildings)?;
 
         Ok(dict)
     }
@@ -235,11 +655,56 @@ impl<'a> AsDict<'a> for PlayerStats {
     fn to_dict(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
         let dict = PyDict::new(_py);
 
+        dict.set_item("time", self.time.clone())?;
         dict.set_item("money", self.money.clone())?;
         dict.set_item("occupation", self.occupation.clone())?;
         dict.set_item("factories", self.factories.clone())?;
         dict.set_item("turrets", self.turrets.clone())?;
         dict.set_item("probes", self.probes.clone())?;
+        dict.set_item("peak_money", self.peak_money)?;
+        dict.set_item("peak_occupation", self.peak_occupation)?;
+        dict.set_item("peak_probes", self.peak_probes)?;
+        dict.set_item("probes_produced", self.probes_produced)?;
+        dict.set_item("probes_lost", self.probes_lost)?;
+        dict.set_item("buildings_built", self.buildings_built)?;
+        dict.set_item("buildings_lost", self.buildings_lost)?;
+
+        Ok(dict)
+    }
+}
+
+impl<'a> AsDict<'a> for IncomeBreakdown {
+    fn to_dict(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        let dict = PyDict::new(_py);
+
+        dict.set_item("base", self.base)?;
+        dict.set_item("occupation", self.occupation)?;
+        dict.set_item("bonus", self.bonus)?;
+        dict.set_item("factory_maintenance", self.factory_maintenance)?;
+        dict.set_item("probe_maintenance", self.probe_maintenance)?;
+        dict.set_item("turret_maintenance", self.turret_maintenance)?;
+        dict.set_item("occupation_upkeep", self.occupation_upkeep)?;
+        dict.set_item("total", self.total())?;
+
+        Ok(dict)
+    }
+}
+
+impl<'a> AsDict<'a> for PlayerEffectiveStats {
+    fn to_dict(&self, _py: Python<'a>) -> PyResult<&'a PyDict> {
+        let dict = PyDict::new(_py);
+
+        dict.set_item("probe_speed", self.probe_speed)?;
+        dict.set_item("probe_hp", self.probe_hp)?;
+        dict.set_item("probe_claim_intensity", self.probe_claim_intensity)?;
+        dict.set_item("probe_explosion_intensity", self.probe_explosion_intensity)?;
+        dict.set_item("factory_max_probe", self.factory_max_probe)?;
+        dict.set_item("factory_build_probe_delay", self.factory_build_probe_delay)?;
+        dict.set_item("factory_probe_price", self.factory_probe_price)?;
+        dict.set_item("turret_scope", self.turret_scope)?;
+        dict.set_item("turret_damage", self.turret_damage)?;
+        dict.set_item("turret_fire_delay", self.turret_fire_delay)?;
+        dict.set_item("turret_maintenance_costs", self.turret_maintenance_costs)?;
 
         Ok(dict)
     }
@@ -253,6 +718,170 @@ impl FromDict for Coord {
     }
 }
 
+impl FromDict for Point {
+    fn from_dict(dict: &PyDict) -> PyResult<Self> {
+        let x: f64 = get_item(dict, "x")?;
+        let y: f64 = get_item(dict, "y")?;
+        Ok(Point::new(x, y))
+    }
+}
+
+/// Bonus tile (see `GameConfig.bonus_tiles`)
+impl FromDict for (Coord, f64) {
+    fn from_dict(dict: &PyDict) -> PyResult<Self> {
+        let coord: Option<Coord> = get_dict_item(dict, "coord")?;
+        match coord {
+            Some(coord) => Ok((coord, get_item(dict, "bonus")?)),
+            None => Err(PyErr::new::<exceptions::PyValueError, _>(format!(
+                "Missing 'coord' key in {:?}",
+                dict
+            ))),
+        }
+    }
+}
+
+/// Note: `change` and `previous_owner_id` are transient, they are ignored
+impl FromDict for TileState {
+    fn from_dict(dict: &PyDict) -> PyResult<Self> {
+        Ok(TileState {
+            id: get_item(dict, "id")?,
+            coord: get_dict_item(dict, "coord")?,
+            occupation: get_item_or(dict, "occupation", None)?,
+            owner_id: get_item_or(dict, "owner_id", None)?,
+            change: None,
+            previous_owner_id: None,
+            is_obstacle: None,
+            bonus: None,
+        })
+    }
+}
+
+/// Note: `death` is transient, it is ignored
+impl FromDict for ProbeState {
+    fn from_dict(dict: &PyDict) -> PyResult<Self> {
+        let id: u128 = get_item(dict, "id")?;
+        let mut state = ProbeState::new(&id);
+        state.pos = get_dict_item(dict, "pos")?;
+        state.target = get_dict_item(dict, "target")?;
+        state.hp = get_item_or(dict, "hp", None)?;
+        state.travelled = get_item_or(dict, "travelled", None)?;
+        match get_item_or::<Option<&str>>(dict, "policy", None)? {
+            Some("Patrol") => {
+                let points: Vec<Point> = get_vec_dict_item(dict, "patrol")?;
+                if points.len() != 2 {
+                    return Err(PyErr::new::<exceptions::PyValueError, _>(
+                        "patrol has to contain two points",
+                    ));
+                }
+                let mut points = points.into_iter();
+                state.policy = Some(ProbePolicy::Patrol {
+                    a: points.next().unwrap(),
+                    b: points.next().unwrap(),
+                });
+            }
+            Some(policy) => {
+                state.policy = Some(ProbePolicy::from_string(policy).map_err(to_py_err)?);
+            }
+            None => {}
+        }
+        Ok(state)
+    }
+}
+
+/// Note: `death` is transient, it is ignored
+impl FromDict for FactoryState {
+    fn from_dict(dict: &PyDict) -> PyResult<Self> {
+        let id: u128 = get_item(dict, "id")?;
+        let mut state = FactoryState::new(&id);
+        state.coord = get_dict_item(dict, "coord")?;
+        state.at_risk = get_item_or(dict, "at_risk", None)?;
+        state.probes = get_vec_dict_item(dict, "probes")?;
+        Ok(state)
+    }
+}
+
+/// Note: `death` and `shot_id` are transient, they are ignored
+impl FromDict for TurretState {
+    fn from_dict(dict: &PyDict) -> PyResult<Self> {
+        let id: u128 = get_item(dict, "id")?;
+        let mut state = TurretState::new(&id);
+        state.coord = get_dict_item(dict, "coord")?;
+        state.at_risk = get_item_or(dict, "at_risk", None)?;
+        Ok(state)
+    }
+}
+
+/// Note: `total` is derived, it is ignored
+impl FromDict for IncomeBreakdown {
+    fn from_dict(dict: &PyDict) -> PyResult<Self> {
+        Ok(IncomeBreakdown {
+            base: get_item_or(dict, "base", 0.0)?,
+            occupation: get_item_or(dict, "occupation", 0.0)?,
+            bonus: get_item_or(dict, "bonus", 0.0)?,
+            factory_maintenance: get_item_or(dict, "factory_maintenance", 0.0)?,
+            probe_maintenance: get_item_or(dict, "probe_maintenance", 0.0)?,
+            turret_maintenance: get_item_or(dict, "turret_maintenance", 0.0)?,
+            occupation_upkeep: get_item_or(dict, "occupation_upkeep", 0.0)?,
+        })
+    }
+}
+
+impl FromDict for ProbeGroup {
+    fn from_dict(dict: &PyDict) -> PyResult<Self> {
+        let probe_ids: Vec<u128> = get_item_or(dict, "probe_ids", Vec::new())?;
+        Ok(ProbeGroup {
+            id: get_item(dict, "id")?,
+            name: get_item(dict, "name")?,
+            probe_ids: probe_ids.into_iter().collect(),
+        })
+    }
+}
+
+/// Note: `death` and `refunded_techs` are transient, they are ignored
+impl FromDict for PlayerState {
+    fn from_dict(dict: &PyDict) -> PyResult<Self> {
+        let id: u128 = get_item(dict, "id")?;
+        let mut state = PlayerState::new(&id);
+        state.money = get_item_or(dict, "money", None)?;
+        state.income = get_item_or(dict, "income", None)?;
+        state.income_breakdown = get_dict_item(dict, "income_breakdown")?;
+        if let Some(policy) = get_item_or::<Option<&str>>(dict, "default_probe_policy", None)? {
+            state.default_probe_policy = Some(ProbePolicy::from_string(policy).map_err(to_py_err)?);
+        }
+        let techs: Vec<&str> = get_item_or(dict, "techs", Vec::new())?;
+        for tech in techs {
+            state
+                .techs
+                .push(Techs::from_string(tech).map_err(to_py_err)?);
+        }
+        if dict.get_item("groups").is_some() {
+            state.groups = Some(get_vec_dict_item(dict, "groups")?);
+        }
+        state.factories = get_vec_dict_item(dict, "factories")?;
+        state.turrets = get_vec_dict_item(dict, "turrets")?;
+        Ok(state)
+    }
+}
+
+impl FromDict for MapState {
+    fn from_dict(dict: &PyDict) -> PyResult<Self> {
+        let mut state = MapState::new(&());
+        state.tiles = get_vec_dict_item(dict, "tiles")?;
+        Ok(state)
+    }
+}
+
+/// Note: `events` are transient, they are ignored
+impl FromDict for GameState {
+    fn from_dict(dict: &PyDict) -> PyResult<Self> {
+        let mut state = GameState::new(&());
+        state.game_ended = get_item_or(dict, "game_ended", false)?;
+        state.map = get_dict_item(dict, "map")?;
+        state.players = get_vec_dict_item(dict, "players")?;
+        Ok(state)
+    }
+}
+
 impl FromDict for GameConfig {
     fn from_dict(dict: &PyDict) -> PyResult<Self> {
         let dim = match dict.get_item("dim") {
@@ -272,31 +901,91 @@ impl FromDict for GameConfig {
 
         Ok(GameConfig {
             dim: dim,
+            obstacles: get_vec_dict_item(dict, "obstacles")?,
+            bonus_tiles: get_vec_dict_item(dict, "bonus_tiles")?,
+            vision_radius: get_item_or(dict, "vision_radius", 0.0)?,
             n_player: get_item(dict, "n_player")?,
+            symmetric_spawns: get_item_or(dict, "symmetric_spawns", false)?,
+            symmetric_layout: get_item_or(dict, "symmetric_layout", false)?,
             initial_money: get_item(dict, "initial_money")?,
             initial_n_probes: get_item(dict, "initial_n_probes")?,
             base_income: get_item(dict, "base_income")?,
+            align_income_ticks: get_item_or(dict, "align_income_ticks", false)?,
             building_occupation_min: get_item(dict, "building_occupation_min")?,
+            building_surcharge: get_item_or(dict, "building_surcharge", 0.0)?,
+            building_surcharge_distance: get_item_or(dict, "building_surcharge_distance", 0)?,
             factory_price: get_item(dict, "factory_price")?,
             factory_expansion_size: get_item(dict, "factory_expansion_size")?,
+            factory_expansion_cost: get_item_or(dict, "factory_expansion_cost", 0.0)?,
             factory_maintenance_costs: get_item(dict, "factory_maintenance_costs")?,
             factory_max_probe: get_item(dict, "factory_max_probe")?,
+            max_probes_total: get_item_or(dict, "max_probes_total", 0)?,
             factory_build_probe_delay: get_item(dict, "factory_build_probe_delay")?,
             max_occupation: get_item(dict, "max_occupation")?,
             probe_speed: get_item(dict, "probe_speed")?,
             probe_hp: get_item(dict, "probe_hp")?,
+            probe_hp_regen_per_sec: get_item_or(dict, "probe_hp_regen_per_sec", 0.0)?,
             probe_claim_intensity: get_item(dict, "probe_claim_intensity")?,
             probe_explosion_intensity: get_item(dict, "probe_explosion_intensity")?,
+            probe_explosion_reinforce: get_item_or(dict, "probe_explosion_reinforce", false)?,
+            probe_idle_without_target: get_item_or(dict, "probe_idle_without_target", false)?,
+            probe_farm_prefer_own: get_item_or(dict, "probe_farm_prefer_own", false)?,
+            adjacency: Adjacency::from_string(get_item_or(dict, "adjacency", "VonNeumann")?)
+                .map_err(to_py_err)?,
+            farm_capture_max_enemy_occupation: get_item_or(
+                dict,
+                "farm_capture_max_enemy_occupation",
+                3,
+            )?,
+            probe_avoid_turrets: get_item_or(dict, "probe_avoid_turrets", false)?,
+            probe_picket_intensity_factor: get_item_or(dict, "probe_picket_intensity_factor", 1.0)?,
+            probe_separation: get_item_or(dict, "probe_separation", 0.0)?,
+            probe_reinforce_distance: get_item_or(dict, "probe_reinforce_distance", 0.0)?,
+            probe_attack_prefer_buildings: get_item_or(
+                dict,
+                "probe_attack_prefer_buildings",
+                false,
+            )?,
+            probe_attack_spread: get_item_or(dict, "probe_attack_spread", false)?,
             probe_price: get_item(dict, "probe_price")?,
+            probe_lifespan: get_item_or(dict, "probe_lifespan", 0.0)?,
+            probe_max_range: get_item_or(dict, "probe_max_range", 0.0)?,
             probe_claim_delay: get_item(dict, "probe_claim_delay")?,
             probe_maintenance_costs: get_item(dict, "probe_maintenance_costs")?,
             turret_price: get_item(dict, "turret_price")?,
+            turret_scrap_refund_rate: get_item_or(dict, "turret_scrap_refund_rate", 0.0)?,
             turret_damage: get_item(dict, "turret_damage")?,
             turret_fire_delay: get_item(dict, "turret_fire_delay")?,
             turret_scope: get_item(dict, "turret_scope")?,
+            turret_target_priority: TurretTargetPriority::from_string(get_item_or(
+                dict,
+                "turret_target_priority",
+                "First",
+            )?)
+            .map_err(to_py_err)?,
+            turret_splash_radius: get_item_or(dict, "turret_splash_radius", 0.0)?,
+            turret_splash_damage: get_item_or(dict, "turret_splash_damage", 0)?,
             turret_maintenance_costs: get_item(dict, "turret_maintenance_costs")?,
             income_rate: get_item(dict, "income_rate")?,
+            occupation_upkeep_rate: get_item_or(dict, "occupation_upkeep_rate", 0.0)?,
+            income_occupation_min: get_item_or(dict, "income_occupation_min", 0)?,
+            first_blood_bonus: get_item_or(dict, "first_blood_bonus", 0.0)?,
+            kill_streak_bounty: get_item_or(dict, "kill_streak_bounty", 0.0)?,
+            max_events_per_frame: get_item_or(dict, "max_events_per_frame", 0)?,
+            tick_rate: get_item_or(dict, "tick_rate", 0.0)?,
+            parallel: get_item_or(dict, "parallel", false)?,
+            time_limit: get_item_or(dict, "time_limit", 0.0)?,
+            score_weights: match get_dict_item(dict, "score_weights")? {
+                Some(weights) => weights,
+                None => ScoreWeights {
+                    occupation: 1.0,
+                    money: 0.0,
+                    buildings: 0.0,
+                },
+            },
+            building_upkeep_occupation: get_item_or(dict, "building_upkeep_occupation", 0)?,
             deprecate_rate: get_item(dict, "deprecate_rate")?,
+            occupation_spread_rate: get_item_or(dict, "occupation_spread_rate", 0.0)?,
             tech_probe_explosion_intensity_increase: get_item(
                 dict,
                 "tech_probe_explosion_intensity_increase",
@@ -312,6 +1001,8 @@ impl FromDict for GameConfig {
             tech_probe_claim_intensity_price: get_item(dict, "tech_probe_claim_intensity_price")?,
             tech_probe_hp_increase: get_item(dict, "tech_probe_hp_increase")?,
             tech_probe_hp_price: get_item(dict, "tech_probe_hp_price")?,
+            tech_probe_speed_increase: get_item_or(dict, "tech_probe_speed_increase", 0.0)?,
+            tech_probe_speed_price: get_item_or(dict, "tech_probe_speed_price", 0.0)?,
             tech_factory_build_delay_decrease: get_item(dict, "tech_factory_build_delay_decrease")?,
             tech_factory_build_delay_price: get_item(dict, "tech_factory_build_delay_price")?,
             tech_factory_probe_price_decrease: get_item(dict, "tech_factory_probe_price_decrease")?,
@@ -330,6 +1021,69 @@ impl FromDict for GameConfig {
                 dict,
                 "tech_turret_maintenance_costs_price",
             )?,
+            tech_turret_damage_increase: get_item_or(dict, "tech_turret_damage_increase", 0)?,
+            tech_turret_damage_price: get_item_or(dict, "tech_turret_damage_price", 0.0)?,
+            tech_refund_rate: get_item_or(dict, "tech_refund_rate", 0.0)?,
+            factions: get_vec_dict_item(dict, "factions")?,
+            player_factions: get_item_or(dict, "player_factions", Vec::new())?,
+            per_player_overrides: {
+                let dicts: HashMap<u128, &PyDict> =
+                    get_item_or(dict, "per_player_overrides", HashMap::new())?;
+                let mut overrides = HashMap::with_capacity(dicts.len());
+                for (id, dict) in dicts {
+                    overrides.insert(id, PartialConfig::from_dict(dict)?);
+                }
+                overrides
+            },
+            tech_exclusion_groups: match dict.get_item("tech_exclusion_groups") {
+                Some(groups) => {
+                    let groups: Vec<Vec<&str>> = groups.extract()?;
+                    let mut tech_groups = Vec::with_capacity(groups.len());
+                    for group in groups {
+                        let mut techs = HashSet::with_capacity(group.len());
+                        for name in group {
+                            techs.insert(Techs::from_string(name).map_err(to_py_err)?);
+                        }
+                        tech_groups.push(techs);
+                    }
+                    tech_groups
+                }
+                None => Techs::default_exclusion_groups(),
+            },
+        })
+    }
+}
+
+impl FromDict for FactionConfig {
+    fn from_dict(dict: &PyDict) -> PyResult<Self> {
+        let names: Vec<&str> = get_item(dict, "techs")?;
+        let mut techs = Vec::with_capacity(names.len());
+        for name in names {
+            techs.push(Techs::from_string(name).map_err(to_py_err)?);
+        }
+        Ok(FactionConfig {
+            techs: techs,
+            tech_price_factor: get_item_or(dict, "tech_price_factor", 1.0)?,
+        })
+    }
+}
+
+impl FromDict for ScoreWeights {
+    fn from_dict(dict: &PyDict) -> PyResult<Self> {
+        Ok(ScoreWeights {
+            occupation: get_item_or(dict, "occupation", 0.0)?,
+            money: get_item_or(dict, "money", 0.0)?,
+            buildings: get_item_or(dict, "buildings", 0.0)?,
+        })
+    }
+}
+
+impl FromDict for PartialConfig {
+    fn from_dict(dict: &PyDict) -> PyResult<Self> {
+        Ok(PartialConfig {
+            initial_money: get_item_or(dict, "initial_money", None)?,
+            income_rate: get_item_or(dict, "income_rate", None)?,
+            probe_speed: get_item_or(dict, "probe_speed", None)?,
         })
     }
 }